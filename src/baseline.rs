@@ -0,0 +1,66 @@
+//! Comparison of a run's throughput with a saved baseline (`--baseline`), so
+//! iobench can serve as a regression gate for storage changes.
+
+use crate::{
+    output::{report, RunRecord, SavedRun},
+    stats::Summary,
+    units::format_size,
+};
+
+/// Compare the mean throughput of each engine, thread count and buffer size in
+/// `records` with that of the baseline's runs of the same configuration,
+/// returning the largest fall from the baseline as a fraction, or None if
+/// the baseline has none of them.
+pub fn compare(baseline: &[SavedRun], records: &[RunRecord]) -> Option<f64> {
+    let mut configs = Vec::new();
+    for record in records {
+        let config = (record.engine.name(), record.threads, record.buf_size);
+        if !configs.contains(&config) {
+            configs.push(config);
+        }
+    }
+    let mut worst = None::<f64>;
+    for (engine, threads, buf_size) in configs {
+        let current = Summary::of(
+            &records
+                .iter()
+                .filter(|record| {
+                    (record.engine.name(), record.threads, record.buf_size)
+                        == (engine, threads, buf_size)
+                })
+                .map(RunRecord::mb_per_s)
+                .collect::<Vec<_>>(),
+        );
+        let saved = baseline
+            .iter()
+            .filter(|run| {
+                (run.engine.as_str(), run.threads, run.buf_size) == (engine, threads, buf_size)
+            })
+            .map(SavedRun::mb_per_s)
+            .collect::<Vec<_>>();
+        let config = format!(
+            "{threads} threads, {} buffer, {engine} engine",
+            format_size(buf_size)
+        );
+        if saved.is_empty() {
+            report!("-- baseline: {config}: no runs to compare with");
+            continue;
+        }
+        let saved = Summary::of(&saved);
+        if !(saved.mean > 0.0 && saved.mean.is_finite()) {
+            report!(
+                "-- baseline: {config}: the baseline's runs have no throughput to compare with"
+            );
+            continue;
+        }
+        let change = current.mean / saved.mean - 1.0;
+        report!(
+            "-- baseline: {config}: {:.0} MB/s against {:.0} MB/s ({:+.1}%)",
+            current.mean,
+            saved.mean,
+            100.0 * change
+        );
+        worst = Some(worst.map_or(-change, |worst| worst.max(-change)));
+    }
+    worst
+}
@@ -0,0 +1,167 @@
+//! Results of a read pass broken down by part of the tree (`--group-by`), for
+//! trees whose parts perform very differently.
+
+use std::{collections::HashMap, path::Path};
+
+use crate::output::report;
+
+/// What to group the files of a pass by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupBy {
+    /// The directory this many levels below the listed path the file is in.
+    Dir(usize),
+    /// The file's extension, ignoring case.
+    Ext,
+    /// The size class of the file, from SIZE_CLASSES.
+    Size,
+}
+
+/// Upper bounds and names of the file size classes for `--group-by size`.
+const SIZE_CLASSES: [(u64, &str); 5] = [
+    (4 << 10, "under 4k"),
+    (64 << 10, "4k to 64k"),
+    (1 << 20, "64k to 1M"),
+    (100 << 20, "1M to 100M"),
+    (u64::MAX, "100M and over"),
+];
+
+impl GroupBy {
+    /// Parse `dir`, `dir:DEPTH`, `ext` or `size`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let (kind, depth) = match s.split_once(':') {
+            Some((kind, depth)) => (kind, Some(depth)),
+            None => (s, None),
+        };
+        match kind {
+            "dir" => {
+                let depth = match depth {
+                    Some(depth) => depth
+                        .parse()
+                        .ok()
+                        .filter(|&depth| depth > 0)
+                        .ok_or_else(|| format!("invalid directory depth: {depth:?}"))?,
+                    None => 1,
+                };
+                Ok(GroupBy::Dir(depth))
+            }
+            "ext" if depth.is_none() => Ok(GroupBy::Ext),
+            "size" if depth.is_none() => Ok(GroupBy::Size),
+            _ => Err(format!(
+                "invalid grouping {s:?}: expected dir, dir:DEPTH, ext or size"
+            )),
+        }
+    }
+
+    /// The group of a file of `size` bytes at `depth` below the listed path it
+    /// was found under.
+    pub fn key(self, path: &Path, depth: usize, size: u64) -> String {
+        match self {
+            GroupBy::Dir(levels) => {
+                // The file's ancestors go up one level at a time to the listed path.
+                let up = depth - levels.min(depth.saturating_sub(1));
+                path.ancestors()
+                    .nth(up)
+                    .unwrap_or(path)
+                    .to_string_lossy()
+                    .into_owned()
+            }
+            GroupBy::Ext => match path.extension() {
+                Some(ext) => format!(".{}", ext.to_string_lossy().to_lowercase()),
+                None => "(no extension)".to_string(),
+            },
+            GroupBy::Size => SIZE_CLASSES
+                .iter()
+                .find(|&&(limit, _)| size < limit)
+                .map_or(SIZE_CLASSES[4].1, |&(_, name)| name)
+                .to_string(),
+        }
+    }
+
+    fn title(self) -> String {
+        match self {
+            GroupBy::Dir(1) => "directory".to_string(),
+            GroupBy::Dir(depth) => format!("directory at depth {depth}"),
+            GroupBy::Ext => "extension".to_string(),
+            GroupBy::Size => "file size".to_string(),
+        }
+    }
+}
+
+/// What was read from the files of one group.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GroupStats {
+    pub files: u64,
+    pub bytes: u64,
+    /// Time spent opening and reading the files, including ones that failed.
+    pub busy_ns: u64,
+    pub errors: u64,
+}
+
+impl GroupStats {
+    fn add(&mut self, other: &GroupStats) {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.busy_ns += other.busy_ns;
+        self.errors += other.errors;
+    }
+}
+
+/// Stats for each group of the files read.
+#[derive(Debug, Default)]
+pub struct Groups {
+    by: Option<GroupBy>,
+    stats: HashMap<String, GroupStats>,
+}
+
+impl Groups {
+    /// The group of a single file.
+    pub fn one(by: GroupBy, key: String, stats: GroupStats) -> Self {
+        Self {
+            by: Some(by),
+            stats: HashMap::from([(key, stats)]),
+        }
+    }
+
+    pub fn combine(mut self, other: Self) -> Self {
+        if self.stats.len() < other.stats.len() {
+            return other.combine(self);
+        }
+        self.by = self.by.or(other.by);
+        for (key, stats) in other.stats {
+            self.stats.entry(key).or_default().add(&stats);
+        }
+        self
+    }
+
+    /// Print a line per group, most bytes first or in size order. Groups are
+    /// read at the same time, so their MB/s and files/s are per reader thread:
+    /// over the time spent reading them.
+    pub fn print(&self) {
+        let Some(by) = self.by else {
+            return;
+        };
+        let total_ns = self.stats.values().map(|stats| stats.busy_ns).sum::<u64>();
+        let mut groups = self.stats.iter().collect::<Vec<_>>();
+        if by == GroupBy::Size {
+            groups.sort_by_key(|(key, _)| SIZE_CLASSES.iter().position(|(_, name)| name == key));
+        } else {
+            groups.sort_by(|a, b| b.1.bytes.cmp(&a.1.bytes).then_with(|| a.0.cmp(b.0)));
+        }
+        report!("-- by {}:", by.title());
+        for (key, stats) in groups {
+            let errors = if stats.errors > 0 {
+                format!("  ({} errors)", stats.errors)
+            } else {
+                String::new()
+            };
+            report!(
+                "   {:>9} files  {:>10.1} MB  {:>8.1} MB/s  {:>7.0} files/s per thread  {:>3.0}% of read time  {key}{errors}",
+                stats.files,
+                stats.bytes as f64 / 1e6,
+                stats.bytes as f64 * 1e3 / stats.busy_ns.max(1) as f64,
+                stats.files as f64 * 1e9 / stats.busy_ns.max(1) as f64,
+                100.0 * stats.busy_ns as f64 / total_ns.max(1) as f64,
+            );
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Control over the kernel's page cache, for measuring cold-cache reads.
+
+use std::{
+    fs::{self, File},
+    io,
+    os::fd::AsRawFd,
+    path::Path,
+    time::Instant,
+};
+
+use clap::ValueEnum;
+
+use crate::output::report;
+
+const DROP_CACHES: &str = "/proc/sys/vm/drop_caches";
+
+/// Write back dirty data, then ask the kernel to drop the page cache and the
+/// dentry and inode caches, printing how it went. Failure isn't fatal: the
+/// run goes ahead with whatever is cached, but says so.
+pub fn drop_caches() {
+    let t1 = Instant::now();
+    // SAFETY: sync has no preconditions.
+    unsafe { libc::sync() };
+    match fs::write(DROP_CACHES, "3\n") {
+        Ok(()) => report!("-- dropped caches in {} s", t1.elapsed().as_secs_f64()),
+        Err(err) => {
+            let reason = match err.kind() {
+                io::ErrorKind::PermissionDenied => {
+                    "needs root (CAP_SYS_ADMIN) in the initial user namespace".to_string()
+                }
+                io::ErrorKind::NotFound => "no /proc/sys/vm on this system".to_string(),
+                _ => err.to_string(),
+            };
+            report!("-- could not drop caches: {reason}; cached data may inflate the results");
+        }
+    }
+}
+
+/// Access-pattern hints given to the kernel with posix_fadvise.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fadvise {
+    /// The file will be read sequentially: readahead is doubled.
+    Sequential,
+    /// The file will be read at random offsets: readahead is disabled.
+    Random,
+    /// The whole file will be needed soon: start reading it into the cache.
+    Willneed,
+    /// The data will be read once only.
+    Noreuse,
+}
+
+impl Fadvise {
+    pub fn name(self) -> &'static str {
+        match self {
+            Fadvise::Sequential => "sequential",
+            Fadvise::Random => "random",
+            Fadvise::Willneed => "willneed",
+            Fadvise::Noreuse => "noreuse",
+        }
+    }
+
+    fn advice(self) -> libc::c_int {
+        match self {
+            Fadvise::Sequential => libc::POSIX_FADV_SEQUENTIAL,
+            Fadvise::Random => libc::POSIX_FADV_RANDOM,
+            Fadvise::Willneed => libc::POSIX_FADV_WILLNEED,
+            Fadvise::Noreuse => libc::POSIX_FADV_NOREUSE,
+        }
+    }
+}
+
+/// Give the kernel a hint about how the whole of a file will be accessed.
+pub fn advise(f: &File, hint: Fadvise) -> Result<(), io::Error> {
+    fadvise(f, hint.advice())
+}
+
+/// Ask the kernel to drop the cached pages of a file, so that the next read of
+/// it comes from the device. Only clean pages are dropped.
+pub fn evict(f: &File) -> Result<(), io::Error> {
+    fadvise(f, libc::POSIX_FADV_DONTNEED)
+}
+
+fn fadvise(f: &File, advice: libc::c_int) -> Result<(), io::Error> {
+    // SAFETY: fd is an open file; posix_fadvise returns an error number.
+    let err = unsafe { libc::posix_fadvise(f.as_raw_fd(), 0, 0, advice) };
+    if err == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::from_raw_os_error(err))
+    }
+}
+
+/// Start reading the first `len` bytes of a file into the page cache in the
+/// background, with readahead(2), without waiting for the data.
+pub fn readahead(path: &Path, len: u64) -> Result<(), io::Error> {
+    let f = File::open(path)?;
+    let len = usize::try_from(len).unwrap_or(usize::MAX);
+    // SAFETY: fd is an open file.
+    if unsafe { libc::readahead(f.as_raw_fd(), 0, len) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// The size of a page of memory, the unit in which files are cached.
+pub fn page_size() -> usize {
+    // SAFETY: sysconf has no preconditions.
+    usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(4096)
+}
+
+/// Length of file mapped per mincore() call, bounding the size of its vector.
+const MINCORE_CHUNK: usize = 1 << 30;
+
+/// Count how many bytes of the first `size` bytes of a file are resident in
+/// the page cache, with mincore(2) on a mapping of it that is never touched.
+pub fn resident_bytes(f: &File, size: u64) -> Result<u64, io::Error> {
+    if size == 0 {
+        return Ok(0);
+    }
+    let len = usize::try_from(size).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let page_size = page_size();
+    // SAFETY: a fresh read-only shared mapping of an open file, never read from.
+    let addr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            f.as_raw_fd(),
+            0,
+        )
+    };
+    if addr == libc::MAP_FAILED {
+        return Err(io::Error::last_os_error());
+    }
+    let mut vec = vec![0u8; MINCORE_CHUNK / page_size];
+    let mut resident = 0;
+    let mut offset = 0;
+    let result = loop {
+        if offset >= len {
+            break Ok(resident);
+        }
+        let chunk = (len - offset).min(MINCORE_CHUNK);
+        // SAFETY: offset is a multiple of the page size within the mapping, and
+        // vec has an entry for each of the chunk's pages.
+        if unsafe { libc::mincore(addr.byte_add(offset), chunk, vec.as_mut_ptr()) } < 0 {
+            break Err(io::Error::last_os_error());
+        }
+        for (page, &state) in vec[..chunk.div_ceil(page_size)].iter().enumerate() {
+            if state & 1 != 0 {
+                resident += page_size.min(chunk - page * page_size) as u64;
+            }
+        }
+        offset += chunk;
+    };
+    // SAFETY: unmapping the region mapped above.
+    unsafe {
+        libc::munmap(addr, len);
+    }
+    result
+}
+
+/// cachestat(2), added in Linux 6.5, has the same number on every architecture.
+const SYS_CACHESTAT: libc::c_long = 451;
+
+/// Page cache state of a file, as reported by cachestat(2), in pages.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CacheStat {
+    pub cached: u64,
+    pub dirty: u64,
+    pub writeback: u64,
+    /// Pages of the file evicted from the cache since it was first cached.
+    pub evicted: u64,
+    /// Evicted pages that would still have been cached with a bit more memory.
+    pub recently_evicted: u64,
+}
+
+impl CacheStat {
+    pub fn add(&mut self, other: &Self) {
+        self.cached += other.cached;
+        self.dirty += other.dirty;
+        self.writeback += other.writeback;
+        self.evicted += other.evicted;
+        self.recently_evicted += other.recently_evicted;
+    }
+}
+
+/// Query the page cache state of the whole of a file. Kernels before 6.5
+/// fail with ENOSYS.
+pub fn cachestat(f: &File) -> Result<CacheStat, io::Error> {
+    // struct cachestat_range: an offset and length, where length 0 means to the end.
+    let range = [0u64, 0u64];
+    let mut stat = CacheStat::default();
+    // SAFETY: range and stat match the kernel's struct cachestat_range and
+    // struct cachestat, and outlive the call.
+    let ret = unsafe {
+        libc::syscall(
+            SYS_CACHESTAT,
+            f.as_raw_fd(),
+            range.as_ptr(),
+            &mut stat as *mut CacheStat,
+            0,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat)
+}
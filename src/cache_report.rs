@@ -0,0 +1,102 @@
+//! Page cache residency report: how much of each file in a tree is cached,
+//! like fincore(1), to tell whether a read benchmark would measure RAM or disk.
+
+use std::{fs::File, io, path::PathBuf, time::Instant};
+
+use clap::Args;
+use jwalk::rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use tracing::debug;
+
+use crate::{
+    cache::resident_bytes,
+    read_tree::build_pool,
+    walk::{paths_or_cwd, walk_files, WalkArgs},
+};
+
+/// Options for the `cache-report` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct CacheReportArgs {
+    /// Number of concurrent threads to use, for both listing and checking files.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+    /// Print the residency of every file, not just the totals.
+    #[arg(long)]
+    pub files: bool,
+
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    /// Directories to check [default: CWD].
+    pub paths: Vec<PathBuf>,
+}
+
+pub fn cache_report(options: &CacheReportArgs) {
+    let dirs = paths_or_cwd(&options.paths);
+    let threads = options.threads.max(1);
+    println!("-- page cache residency of {dirs:?} using {threads} threads");
+
+    let t1 = Instant::now();
+    let mut files = Vec::new();
+    for dir in &dirs {
+        files.extend(walk_files(dir, threads, &options.walk).map(|entry| entry.path()));
+    }
+    let results = build_pool(threads).install(|| {
+        files
+            .par_iter()
+            .map(|path| {
+                let f = File::open(path)?;
+                let size = f.metadata()?.len();
+                Ok::<_, io::Error>((size, resident_bytes(&f, size)?))
+            })
+            .collect::<Vec<_>>()
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+
+    let (mut size, mut cached) = (0u64, 0u64);
+    let (mut full, mut partial, mut cold, mut errors) = (0u64, 0u64, 0u64, 0u64);
+    for (path, result) in files.iter().zip(results) {
+        let (file_size, file_cached) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                debug!("error checking file {}: {err}", path.to_string_lossy());
+                errors += 1;
+                continue;
+            }
+        };
+        if options.files {
+            println!(
+                "-- {:>5.1}%  {:>10.3} of {:>10.3} MB  {}",
+                percent(file_cached, file_size),
+                file_cached as f64 / 1e6,
+                file_size as f64 / 1e6,
+                path.display(),
+            );
+        }
+        size += file_size;
+        cached += file_cached;
+        match file_cached {
+            0 if file_size > 0 => cold += 1,
+            cached if cached < file_size => partial += 1,
+            _ => full += 1,
+        }
+    }
+    println!(
+        "-- cached: {:.1} MB of {:.1} MB ({:.1}%) in {} files, checked in {dur_s} s",
+        cached as f64 / 1e6,
+        size as f64 / 1e6,
+        percent(cached, size),
+        full + partial + cold,
+    );
+    println!("-- files: {full} fully cached, {partial} partly cached, {cold} not cached");
+    if errors > 0 {
+        println!("-- errors: {errors}");
+    }
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    if whole == 0 {
+        100.0
+    } else {
+        100.0 * part as f64 / whole as f64
+    }
+}
@@ -0,0 +1,108 @@
+//! Building blocks for the SVG charts in `--report` and `plot`: a fixed-size
+//! plot area with a linear y axis, round tick steps and text escaping.
+
+use std::fmt::Write as _;
+
+/// Size of each chart, in SVG user units (pixels at 100% zoom).
+pub const WIDTH: f64 = 760.0;
+pub const HEIGHT: f64 = 300.0;
+/// Space around the plot area for the axes and their labels.
+pub const LEFT: f64 = 70.0;
+pub const RIGHT: f64 = 20.0;
+pub const TOP: f64 = 20.0;
+pub const BOTTOM: f64 = 60.0;
+pub const PLOT_WIDTH: f64 = WIDTH - LEFT - RIGHT;
+pub const PLOT_HEIGHT: f64 = HEIGHT - TOP - BOTTOM;
+/// Line colors for the series in a chart, reused in order when there are more.
+pub const COLORS: [&str; 6] = [
+    "#1f77b4", "#d62728", "#2ca02c", "#ff7f0e", "#9467bd", "#8c564b",
+];
+
+/// Styles kept inside each chart, so it looks the same on its own as in a page.
+const STYLE: &str = "text { font-family: sans-serif; font-size: 12px; fill: #444; }
+.grid { stroke: #e4e4e4; }
+.axis { stroke: #888; }
+.bar { fill: #1f77b4; }";
+
+/// Start a chart with its y axis, grid lines and tick labels for values up
+/// to `max`, returning the value at the top of the axis.
+pub fn axes(svg: &mut String, max: f64, y_title: &str) -> f64 {
+    let step = tick_step(max, 5);
+    let ticks = (max / step).ceil().max(1.0) as u64;
+    let y_max = ticks as f64 * step;
+    let _ = writeln!(
+        svg,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{WIDTH}\" height=\"{HEIGHT}\" viewBox=\"0 0 {WIDTH} {HEIGHT}\">\n<style>\n{STYLE}\n</style>"
+    );
+    for tick in (0..=ticks).map(|i| i as f64 * step) {
+        let y = TOP + PLOT_HEIGHT * (1.0 - tick / y_max);
+        let _ = writeln!(
+            svg,
+            "<line class=\"grid\" x1=\"{LEFT}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\"/>\n<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\">{tick}</text>",
+            WIDTH - RIGHT,
+            LEFT - 6.0,
+            y + 4.0,
+        );
+    }
+    let bottom = TOP + PLOT_HEIGHT;
+    let _ = writeln!(
+        svg,
+        "<line class=\"axis\" x1=\"{LEFT}\" y1=\"{TOP}\" x2=\"{LEFT}\" y2=\"{bottom:.1}\"/>\n<line class=\"axis\" x1=\"{LEFT}\" y1=\"{bottom:.1}\" x2=\"{:.1}\" y2=\"{bottom:.1}\"/>",
+        WIDTH - RIGHT,
+    );
+    let middle = TOP + PLOT_HEIGHT / 2.0;
+    let _ = writeln!(
+        svg,
+        "<text x=\"16\" y=\"{middle:.1}\" text-anchor=\"middle\" transform=\"rotate(-90 16 {middle:.1})\">{}</text>",
+        escape(y_title),
+    );
+    y_max
+}
+
+/// Label the x axis, below the tick labels.
+pub fn x_title(svg: &mut String, title: &str) {
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{}</text>",
+        LEFT + PLOT_WIDTH / 2.0,
+        HEIGHT - 8.0,
+        escape(title),
+    );
+}
+
+/// Add the `index`th entry of a legend in the top left corner of the plot.
+pub fn legend(svg: &mut String, index: usize, label: &str) {
+    let _ = writeln!(
+        svg,
+        "<text x=\"{:.1}\" y=\"{:.1}\" style=\"fill: {}\">{}</text>",
+        LEFT + 10.0,
+        TOP + 14.0 * (index as f64 + 1.0),
+        COLORS[index % COLORS.len()],
+        escape(label),
+    );
+}
+
+pub fn finish(mut svg: String) -> String {
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// A round step (1, 2 or 5 times a power of ten, and at least 1) that divides
+/// `max` into at most `ticks` intervals.
+pub fn tick_step(max: f64, ticks: usize) -> f64 {
+    let raw = (max / ticks as f64).max(1.0);
+    let magnitude = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0]
+        .into_iter()
+        .map(|m| m * magnitude)
+        .find(|&step| step >= raw)
+        .unwrap_or(10.0 * magnitude)
+}
+
+/// Escape text for HTML or SVG.
+pub fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,161 @@
+//! Checkpoints of a long read-tree pass (`--checkpoint`), so a run that was
+//! interrupted can carry on where it stopped with `--resume` rather than
+//! start over.
+//!
+//! A checkpoint is a text file of `key: value` lines. It records how many
+//! files at the start of the ordered list were done, and the totals for them.
+//! Resuming lists and orders the tree again with the same seed, so it's only
+//! valid while the tree is unchanged.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+/// Where a run had got to.
+#[derive(Debug, Clone, Default)]
+pub struct Checkpoint {
+    pub paths: Vec<PathBuf>,
+    /// Seed the files were sampled and ordered with.
+    pub seed: u64,
+    /// Files listed, after sampling.
+    pub listed: u64,
+    /// Files at the start of the list that were done, read or failed.
+    pub done: u64,
+    /// The last of those, to check that the list is the same on resuming.
+    pub last: Option<PathBuf>,
+    pub bytes: u64,
+    pub errors: u64,
+    /// Time spent reading them.
+    pub duration_s: f64,
+}
+
+impl Checkpoint {
+    /// Write the checkpoint to `path`, replacing it atomically so an
+    /// interruption never leaves half a checkpoint.
+    pub fn write(&self, path: &Path) -> Result<(), io::Error> {
+        let mut out = "Checkpoint of iobench read-tree; resume with --resume.\n".to_string();
+        for dir in &self.paths {
+            let _ = writeln!(out, "path: {}", dir.to_string_lossy());
+        }
+        let _ = writeln!(out, "seed: {}", self.seed);
+        let _ = writeln!(out, "listed: {}", self.listed);
+        let _ = writeln!(out, "done: {}", self.done);
+        if let Some(last) = &self.last {
+            let _ = writeln!(out, "last: {}", last.to_string_lossy());
+        }
+        let _ = writeln!(out, "bytes: {}", self.bytes);
+        let _ = writeln!(out, "errors: {}", self.errors);
+        let _ = writeln!(out, "duration_s: {}", self.duration_s);
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        fs::write(&tmp, out)?;
+        fs::rename(&tmp, path)
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+        let mut checkpoint = Checkpoint::default();
+        let mut seen = Vec::new();
+        for (key, value) in contents.lines().filter_map(|line| line.split_once(": ")) {
+            let number = || {
+                value
+                    .parse::<u64>()
+                    .map_err(|_| format!("invalid {key}: {value:?}"))
+            };
+            match key {
+                "path" => checkpoint.paths.push(PathBuf::from(value)),
+                "seed" => checkpoint.seed = number()?,
+                "listed" => checkpoint.listed = number()?,
+                "done" => checkpoint.done = number()?,
+                "last" => checkpoint.last = Some(PathBuf::from(value)),
+                "bytes" => checkpoint.bytes = number()?,
+                "errors" => checkpoint.errors = number()?,
+                "duration_s" => {
+                    checkpoint.duration_s = value
+                        .parse()
+                        .map_err(|_| format!("invalid {key}: {value:?}"))?
+                }
+                _ => continue,
+            }
+            seen.push(key);
+        }
+        for key in ["path", "seed", "listed", "done", "bytes", "duration_s"] {
+            if !seen.contains(&key) {
+                return Err(format!("not a checkpoint: no {key}"));
+            }
+        }
+        Ok(checkpoint)
+    }
+}
+
+/// Files finished so far in a pass, for its checkpoints. Files finish out of
+/// order, so those after the first one still being read are held back until
+/// it is done.
+pub struct Progress {
+    /// Where the pass started: the checkpoint resumed, or nothing done yet.
+    base: Checkpoint,
+    state: Mutex<ProgressState>,
+}
+
+#[derive(Default)]
+struct ProgressState {
+    /// Files done at the start of the pass's list.
+    done: u64,
+    /// Bytes read from them and how many failed.
+    bytes: u64,
+    errors: u64,
+    /// Files done after the first one not yet done, with their bytes and
+    /// whether they failed.
+    pending: BTreeMap<u64, (u64, bool)>,
+}
+
+impl Progress {
+    pub fn new(base: Checkpoint) -> Self {
+        Self {
+            base,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Count a file as done: the one at `index` in the pass's list.
+    pub fn finish_file(&self, index: usize, bytes: u64, failed: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.pending.insert(index as u64, (bytes, failed));
+        loop {
+            let next = state.done;
+            let Some((bytes, failed)) = state.pending.remove(&next) else {
+                break;
+            };
+            state.done += 1;
+            state.bytes += bytes;
+            state.errors += u64::from(failed);
+        }
+    }
+
+    /// The checkpoint after `duration_s` seconds of the pass, whose list
+    /// starts at the first file the base checkpoint hadn't done. `last_path`
+    /// gives the path of a file in that list.
+    pub fn checkpoint(
+        &self,
+        duration_s: f64,
+        last_path: impl FnOnce(usize) -> PathBuf,
+    ) -> Checkpoint {
+        let state = self.state.lock().unwrap();
+        let last = match state.done {
+            0 => self.base.last.clone(),
+            done => Some(last_path(done as usize - 1)),
+        };
+        Checkpoint {
+            done: self.base.done + state.done,
+            last,
+            bytes: self.base.bytes + state.bytes,
+            errors: self.base.errors + state.errors,
+            duration_s: self.base.duration_s + duration_s,
+            ..self.base.clone()
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! XXH3 (64-bit, seed 0) checksums of file contents, computed incrementally
+//! as the data is read.
+//!
+//! This follows the reference implementation at github.com/Cyan4973/xxHash,
+//! using its scalar code path, so the results match `xxhsum -H3`.
+
+use clap::ValueEnum;
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumKind {
+    /// 64-bit XXH3, as `xxhsum -H3` computes it.
+    Xxh3,
+}
+
+impl ChecksumKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumKind::Xxh3 => "xxh3",
+        }
+    }
+}
+
+const PRIME32_1: u64 = 0x9E37_79B1;
+const PRIME32_2: u64 = 0x85EB_CA77;
+const PRIME32_3: u64 = 0xC2B2_AE3D;
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+const PRIME_MX1: u64 = 0x1656_6791_9E37_79F9;
+const PRIME_MX2: u64 = 0x9FB2_1C65_1E98_DF25;
+
+/// The default secret from the reference implementation.
+const SECRET: [u8; 192] = [
+    0xb8, 0xfe, 0x6c, 0x39, 0x23, 0xa4, 0x4b, 0xbe, 0x7c, 0x01, 0x81, 0x2c, 0xf7, 0x21, 0xad, 0x1c,
+    0xde, 0xd4, 0x6d, 0xe9, 0x83, 0x90, 0x97, 0xdb, 0x72, 0x40, 0xa4, 0xa4, 0xb7, 0xb3, 0x67, 0x1f,
+    0xcb, 0x79, 0xe6, 0x4e, 0xcc, 0xc0, 0xe5, 0x78, 0x82, 0x5a, 0xd0, 0x7d, 0xcc, 0xff, 0x72, 0x21,
+    0xb8, 0x08, 0x46, 0x74, 0xf7, 0x43, 0x24, 0x8e, 0xe0, 0x35, 0x90, 0xe6, 0x81, 0x3a, 0x26, 0x4c,
+    0x3c, 0x28, 0x52, 0xbb, 0x91, 0xc3, 0x00, 0xcb, 0x88, 0xd0, 0x65, 0x8b, 0x1b, 0x53, 0x2e, 0xa3,
+    0x71, 0x64, 0x48, 0x97, 0xa2, 0x0d, 0xf9, 0x4e, 0x38, 0x19, 0xef, 0x46, 0xa9, 0xde, 0xac, 0xd8,
+    0xa8, 0xfa, 0x76, 0x3f, 0xe3, 0x9c, 0x34, 0x3f, 0xf9, 0xdc, 0xbb, 0xc7, 0xc7, 0x0b, 0x4f, 0x1d,
+    0x8a, 0x51, 0xe0, 0x4b, 0xcd, 0xb4, 0x59, 0x31, 0xc8, 0x9f, 0x7e, 0xc9, 0xd9, 0x78, 0x73, 0x64,
+    0xea, 0xc5, 0xac, 0x83, 0x34, 0xd3, 0xeb, 0xc3, 0xc5, 0x81, 0xa0, 0xff, 0xfa, 0x13, 0x63, 0xeb,
+    0x17, 0x0d, 0xdd, 0x51, 0xb7, 0xf0, 0xda, 0x49, 0xd3, 0x16, 0x55, 0x26, 0x29, 0xd4, 0x68, 0x9e,
+    0x2b, 0x16, 0xbe, 0x58, 0x7d, 0x47, 0xa1, 0xfc, 0x8f, 0xf8, 0xb8, 0xd1, 0x7a, 0xd0, 0x31, 0xce,
+    0x45, 0xcb, 0x3a, 0x8f, 0x95, 0x16, 0x04, 0x28, 0xaf, 0xd7, 0xfb, 0xca, 0xbb, 0x4b, 0x40, 0x7e,
+];
+
+const STRIPE_LEN: usize = 64;
+const STRIPES_PER_BLOCK: usize = (SECRET.len() - STRIPE_LEN) / 8;
+/// Inputs up to this length are hashed in one go at the end, not in stripes.
+const MIDSIZE_MAX: usize = 240;
+
+fn read32(b: &[u8], at: usize) -> u64 {
+    u64::from(u32::from_le_bytes(b[at..at + 4].try_into().unwrap()))
+}
+
+fn read64(b: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(b[at..at + 8].try_into().unwrap())
+}
+
+fn mul128_fold64(a: u64, b: u64) -> u64 {
+    let product = u128::from(a) * u128::from(b);
+    (product as u64) ^ ((product >> 64) as u64)
+}
+
+fn xxh64_avalanche(mut h: u64) -> u64 {
+    h ^= h >> 33;
+    h = h.wrapping_mul(PRIME64_2);
+    h ^= h >> 29;
+    h = h.wrapping_mul(PRIME64_3);
+    h ^ (h >> 32)
+}
+
+fn avalanche(mut h: u64) -> u64 {
+    h ^= h >> 37;
+    h = h.wrapping_mul(PRIME_MX1);
+    h ^ (h >> 32)
+}
+
+fn rrmxmx(mut h: u64, len: u64) -> u64 {
+    h ^= h.rotate_left(49) ^ h.rotate_left(24);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^= (h >> 35).wrapping_add(len);
+    h = h.wrapping_mul(PRIME_MX2);
+    h ^ (h >> 28)
+}
+
+fn mix16(input: &[u8], at: usize, secret_at: usize) -> u64 {
+    mul128_fold64(
+        read64(input, at) ^ read64(&SECRET, secret_at),
+        read64(input, at + 8) ^ read64(&SECRET, secret_at + 8),
+    )
+}
+
+/// Hash of an input of at most [`MIDSIZE_MAX`] bytes.
+fn hash_short(input: &[u8]) -> u64 {
+    let len = input.len();
+    let len64 = len as u64;
+    match len {
+        0 => xxh64_avalanche(read64(&SECRET, 56) ^ read64(&SECRET, 64)),
+        1..=3 => {
+            let combined = (u64::from(input[0]) << 16)
+                | (u64::from(input[len >> 1]) << 24)
+                | u64::from(input[len - 1])
+                | (len64 << 8);
+            xxh64_avalanche(combined ^ (read32(&SECRET, 0) ^ read32(&SECRET, 4)))
+        }
+        4..=8 => {
+            let bitflip = read64(&SECRET, 8) ^ read64(&SECRET, 16);
+            let input64 = read32(input, len - 4).wrapping_add(read32(input, 0) << 32);
+            rrmxmx(input64 ^ bitflip, len64)
+        }
+        9..=16 => {
+            let lo = read64(input, 0) ^ (read64(&SECRET, 24) ^ read64(&SECRET, 32));
+            let hi = read64(input, len - 8) ^ (read64(&SECRET, 40) ^ read64(&SECRET, 48));
+            let acc = len64
+                .wrapping_add(lo.swap_bytes())
+                .wrapping_add(hi)
+                .wrapping_add(mul128_fold64(lo, hi));
+            avalanche(acc)
+        }
+        17..=128 => {
+            let mut acc = len64.wrapping_mul(PRIME64_1);
+            let pairs = (len - 1) / 32;
+            for i in (0..=pairs).rev() {
+                acc = acc
+                    .wrapping_add(mix16(input, 16 * i, 32 * i))
+                    .wrapping_add(mix16(input, len - 16 * (i + 1), 32 * i + 16));
+            }
+            avalanche(acc)
+        }
+        _ => {
+            let mut acc = len64.wrapping_mul(PRIME64_1);
+            for i in 0..8 {
+                acc = acc.wrapping_add(mix16(input, 16 * i, 16 * i));
+            }
+            acc = avalanche(acc);
+            for i in 8..len / 16 {
+                acc = acc.wrapping_add(mix16(input, 16 * i, 16 * (i - 8) + 3));
+            }
+            acc = acc.wrapping_add(mix16(input, len - 16, 136 - 17));
+            avalanche(acc)
+        }
+    }
+}
+
+/// Incremental XXH3 state.
+pub struct Xxh3 {
+    acc: [u64; 8],
+    /// Input not yet consumed as stripes. Once past [`MIDSIZE_MAX`], at least
+    /// the last full stripe is always kept for the final step.
+    pending: Vec<u8>,
+    stripes_in_block: usize,
+    total: u64,
+}
+
+impl Default for Xxh3 {
+    fn default() -> Self {
+        Self {
+            acc: [
+                PRIME32_3, PRIME64_1, PRIME64_2, PRIME64_3, PRIME64_4, PRIME32_2, PRIME64_5,
+                PRIME32_1,
+            ],
+            pending: Vec::new(),
+            stripes_in_block: 0,
+            total: 0,
+        }
+    }
+}
+
+impl Xxh3 {
+    pub fn update(&mut self, data: &[u8]) {
+        self.total += data.len() as u64;
+        self.pending.extend_from_slice(data);
+        if self.total as usize > MIDSIZE_MAX {
+            // Consume stripes while a full stripe would still remain after them.
+            let mut at = 0;
+            while self.pending.len() - at >= 2 * STRIPE_LEN {
+                Self::stripe(
+                    &mut self.acc,
+                    &mut self.stripes_in_block,
+                    &self.pending[at..],
+                );
+                at += STRIPE_LEN;
+            }
+            self.pending.drain(..at);
+        }
+    }
+
+    pub fn digest(&self) -> u64 {
+        if self.total as usize <= MIDSIZE_MAX {
+            return hash_short(&self.pending);
+        }
+        let mut acc = self.acc;
+        let mut stripes_in_block = self.stripes_in_block;
+        let mut at = 0;
+        // Every stripe that ends before the input does is consumed normally...
+        while self.pending.len() - at > STRIPE_LEN {
+            Self::stripe(&mut acc, &mut stripes_in_block, &self.pending[at..]);
+            at += STRIPE_LEN;
+        }
+        // ...then the last 64 bytes (which may overlap them) with a shifted secret.
+        let last = self.pending.len() - STRIPE_LEN;
+        accumulate_512(
+            &mut acc,
+            &self.pending[last..],
+            SECRET.len() - STRIPE_LEN - 7,
+        );
+
+        let mut result = self.total.wrapping_mul(PRIME64_1);
+        for i in 0..4 {
+            result = result.wrapping_add(mul128_fold64(
+                acc[2 * i] ^ read64(&SECRET, 11 + 16 * i),
+                acc[2 * i + 1] ^ read64(&SECRET, 11 + 16 * i + 8),
+            ));
+        }
+        avalanche(result)
+    }
+
+    fn stripe(acc: &mut [u64; 8], stripes_in_block: &mut usize, input: &[u8]) {
+        accumulate_512(acc, input, 8 * *stripes_in_block);
+        *stripes_in_block += 1;
+        if *stripes_in_block == STRIPES_PER_BLOCK {
+            scramble(acc);
+            *stripes_in_block = 0;
+        }
+    }
+}
+
+fn accumulate_512(acc: &mut [u64; 8], input: &[u8], secret_at: usize) {
+    for i in 0..8 {
+        let value = read64(input, 8 * i);
+        let key = value ^ read64(&SECRET, secret_at + 8 * i);
+        acc[i ^ 1] = acc[i ^ 1].wrapping_add(value);
+        acc[i] = acc[i].wrapping_add((key & 0xFFFF_FFFF).wrapping_mul(key >> 32));
+    }
+}
+
+fn scramble(acc: &mut [u64; 8]) {
+    for (i, a) in acc.iter_mut().enumerate() {
+        let key = read64(&SECRET, SECRET.len() - STRIPE_LEN + 8 * i);
+        *a = ((*a ^ (*a >> 47)) ^ key).wrapping_mul(PRIME32_1);
+    }
+}
@@ -0,0 +1,91 @@
+//! Delete benchmark: remove a tree generated by `write-tree`, measuring unlink
+//! and rmdir throughput.
+
+use std::{fs, path::PathBuf, time::Instant};
+
+use clap::Args;
+use jwalk::rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use tracing::{debug, error};
+
+use crate::{
+    read_tree::build_pool,
+    walk::{walk_entries, WalkArgs},
+    write_tree::MARKER_FILE,
+};
+
+/// Options for the `cleanup` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct CleanupArgs {
+    /// Root of a tree created by `write-tree` or `create-tree`.
+    pub dir: PathBuf,
+    /// Number of concurrent threads to use.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+}
+
+pub fn cleanup(options: &CleanupArgs) {
+    let root = &options.dir;
+    let marker = root.join(MARKER_FILE);
+    if !marker.is_file() {
+        error!(
+            "{} has no {MARKER_FILE} marker, so it wasn't generated by iobench; not deleting it",
+            root.to_string_lossy()
+        );
+        std::process::exit(1);
+    }
+    let threads = options.threads.max(1);
+    println!("-- deleting {root:?} using {threads} threads");
+
+    let (mut dirs, files): (Vec<_>, Vec<_>) = walk_entries(root, threads, &WalkArgs::default())
+        .filter(|entry| entry.path() != marker)
+        .partition(|entry| entry.file_type.is_dir());
+
+    let pool = build_pool(threads);
+    let t1 = Instant::now();
+    let unlinked = pool.install(|| {
+        files
+            .par_iter()
+            .filter(|entry| {
+                let path = entry.path();
+                fs::remove_file(&path)
+                    .map_err(|err| debug!("error removing {}: {err}", path.to_string_lossy()))
+                    .is_ok()
+            })
+            .count()
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- unlink: {:.0} files/s  ({unlinked} files in {dur_s} s)",
+        unlinked as f64 / dur_s,
+    );
+
+    // Children before parents; the root goes last, once the marker is gone.
+    dirs.sort_by_key(|entry| std::cmp::Reverse(entry.depth));
+    dirs.retain(|entry| entry.depth > 0);
+    let t1 = Instant::now();
+    let mut removed = 0;
+    for entry in &dirs {
+        let path = entry.path();
+        match fs::remove_dir(&path) {
+            Ok(()) => removed += 1,
+            Err(err) => debug!("error removing {}: {err}", path.to_string_lossy()),
+        }
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- rmdir: {:.0} dirs/s  ({removed} dirs in {dur_s} s)",
+        removed as f64 / dur_s,
+    );
+
+    if unlinked < files.len() || removed < dirs.len() {
+        error!(
+            "some entries could not be removed; leaving {} in place",
+            root.to_string_lossy()
+        );
+        std::process::exit(1);
+    }
+    if let Err(err) = fs::remove_file(&marker).and_then(|()| fs::remove_dir(root)) {
+        error!("error removing {}: {err}", root.to_string_lossy());
+        std::process::exit(1);
+    }
+}
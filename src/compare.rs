@@ -0,0 +1,285 @@
+//! Side-by-side comparison of two results files (`compare`), for seeing what
+//! changed between two sets of runs without doing the arithmetic by hand.
+
+use std::path::{Path, PathBuf};
+
+use clap::Args;
+use tracing::error;
+
+use crate::{
+    output::{load_records, SavedRun},
+    stats::{Summary, TTest},
+    units::{format_latency, format_size, parse_percent},
+};
+
+/// Options for the `compare` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct CompareArgs {
+    /// Mark changes of at least this much (e.g. 5%) as significant, where
+    /// either side has a single run so they can't be tested.
+    #[arg(long, value_parser = parse_percent, default_value = "5%")]
+    pub threshold: f64,
+    /// Where both sides have several runs, test changes for significance at
+    /// this confidence level, with Welch's t-test.
+    #[arg(long, value_parser = parse_percent, default_value = "95%")]
+    pub confidence: f64,
+    /// Results to compare against, written by read-tree --output json (or the
+    /// output of --quiet).
+    pub before: PathBuf,
+    /// Results to compare with them.
+    pub after: PathBuf,
+}
+
+/// A metric of a run that is compared.
+struct Metric {
+    name: &'static str,
+    /// Whether a higher value is an improvement, if it's either.
+    higher_is_better: Option<bool>,
+    value: fn(&SavedRun) -> Option<f64>,
+    format: fn(f64) -> String,
+}
+
+const METRICS: [Metric; 11] = [
+    Metric {
+        name: "MB/s",
+        higher_is_better: Some(true),
+        value: |run| Some(run.mb_per_s()),
+        format: |value| format!("{value:.1}"),
+    },
+    Metric {
+        name: "files/s",
+        higher_is_better: Some(true),
+        value: |run| Some(run.files as f64 / run.duration_s),
+        format: |value| format!("{value:.0}"),
+    },
+    Metric {
+        name: "MB read",
+        higher_is_better: None,
+        value: |run| Some(run.bytes as f64 / 1e6),
+        format: |value| format!("{value:.1}"),
+    },
+    Metric {
+        name: "files",
+        higher_is_better: None,
+        value: |run| Some(run.files as f64),
+        format: |value| format!("{value:.0}"),
+    },
+    Metric {
+        name: "errors",
+        higher_is_better: Some(false),
+        value: |run| Some(run.errors as f64),
+        format: |value| format!("{value:.0}"),
+    },
+    Metric {
+        name: "seconds",
+        higher_is_better: Some(false),
+        value: |run| Some(run.duration_s),
+        format: |value| format!("{value:.3}"),
+    },
+    Metric {
+        name: "p50 latency",
+        higher_is_better: Some(false),
+        value: |run| latency_at(run, 50.0),
+        format: |value| format_latency(value as u64),
+    },
+    Metric {
+        name: "p90 latency",
+        higher_is_better: Some(false),
+        value: |run| latency_at(run, 90.0),
+        format: |value| format_latency(value as u64),
+    },
+    Metric {
+        name: "p99 latency",
+        higher_is_better: Some(false),
+        value: |run| latency_at(run, 99.0),
+        format: |value| format_latency(value as u64),
+    },
+    Metric {
+        name: "p99.9 latency",
+        higher_is_better: Some(false),
+        value: |run| latency_at(run, 99.9),
+        format: |value| format_latency(value as u64),
+    },
+    Metric {
+        name: "max latency",
+        higher_is_better: Some(false),
+        value: |run| latency_at(run, 100.0),
+        format: |value| format_latency(value as u64),
+    },
+];
+
+/// The latency at a saved percentile of a run, in nanoseconds.
+fn latency_at(run: &SavedRun, percentile: f64) -> Option<f64> {
+    run.latency
+        .iter()
+        .find(|&&(p, _)| p == percentile)
+        .map(|&(_, ns)| ns as f64)
+}
+
+pub fn compare(options: &CompareArgs) {
+    let before = load(&options.before);
+    let after = load(&options.after);
+    let names = names(&options.before, &options.after);
+    let width = names
+        .iter()
+        .map(|name| name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max(14);
+
+    // Each engine, thread count and buffer size run, in the order first run.
+    let mut configs = Vec::new();
+    for run in before.iter().chain(&after) {
+        let config = (run.engine.as_str(), run.threads, run.buf_size);
+        if !configs.contains(&config) {
+            configs.push(config);
+        }
+    }
+    for (engine, threads, buf_size) in configs {
+        let runs = |runs: &[SavedRun]| {
+            runs.iter()
+                .filter(|run| {
+                    (run.engine.as_str(), run.threads, run.buf_size) == (engine, threads, buf_size)
+                })
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let (before, after) = (runs(&before), runs(&after));
+        let config = format!(
+            "{threads} threads, {} buffer, {engine} engine",
+            format_size(buf_size)
+        );
+        match (before.is_empty(), after.is_empty()) {
+            (false, false) => {}
+            (true, _) => {
+                println!("-- {config}: only in {}", names[1]);
+                continue;
+            }
+            (_, true) => {
+                println!("-- {config}: only in {}", names[0]);
+                continue;
+            }
+        }
+        let tested = before.len() > 1 && after.len() > 1;
+        println!(
+            "-- {config}: {} runs in {}, {} in {}{}",
+            before.len(),
+            names[0],
+            after.len(),
+            names[1],
+            if tested {
+                format!(
+                    ", changes tested at {}% confidence",
+                    100.0 * options.confidence
+                )
+            } else {
+                format!(
+                    ", too few to test changes; those of {}% or more are marked",
+                    100.0 * options.threshold
+                )
+            }
+        );
+        println!(
+            "   {:<14} {:>width$} {:>width$} {:>12}",
+            "", names[0], names[1], "change"
+        );
+        let mut throughput = None;
+        for metric in &METRICS {
+            let summary = |runs: &[SavedRun]| {
+                let values = runs.iter().filter_map(metric.value).collect::<Vec<_>>();
+                (!values.is_empty()).then(|| Summary::of(&values))
+            };
+            let (Some(old), Some(new)) = (summary(&before), summary(&after)) else {
+                continue;
+            };
+            let delta = new.mean - old.mean;
+            let sign = if delta < 0.0 { "-" } else { "+" };
+            let change = if old.mean == 0.0 {
+                0.0
+            } else {
+                delta / old.mean
+            };
+            let test = TTest::welch(&old, &new, options.confidence);
+            let significant = delta != 0.0
+                && match test {
+                    Some(test) => test.p < 1.0 - options.confidence,
+                    None => change.abs() >= options.threshold,
+                };
+            let verdict = match metric.higher_is_better {
+                _ if !significant => "",
+                Some(higher) if higher == (delta > 0.0) => "better",
+                Some(_) => "worse",
+                None => "changed",
+            };
+            let line = format!(
+                "   {:<14} {:>width$} {:>width$} {:>12} {:>+7.1}%  {:<8} {verdict}",
+                metric.name,
+                (metric.format)(old.mean),
+                (metric.format)(new.mean),
+                format!("{sign}{}", (metric.format)(delta.abs())),
+                100.0 * change,
+                test.map_or_else(String::new, |test| format_p(test.p)),
+            );
+            println!("{}", line.trim_end());
+            if metric.name == "MB/s" {
+                throughput = test.map(|test| (test, old.mean, significant));
+            }
+        }
+        if let Some((test, old, significant)) = throughput {
+            println!(
+                "   throughput changed by {:+.1} MB/s ({}% confidence interval {:+.1}% to {:+.1}%): {}",
+                test.difference,
+                100.0 * options.confidence,
+                100.0 * (test.difference - test.margin) / old,
+                100.0 * (test.difference + test.margin) / old,
+                if significant {
+                    "a significant change"
+                } else {
+                    "not significant"
+                }
+            );
+        }
+    }
+}
+
+/// A p-value, to three places.
+fn format_p(p: f64) -> String {
+    if p < 0.001 {
+        "p<0.001".to_string()
+    } else {
+        format!("p={p:.3}")
+    }
+}
+
+fn load(path: &Path) -> Vec<SavedRun> {
+    match load_records(path) {
+        Ok(runs) if runs.is_empty() => {
+            error!("no runs found in {}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+        Ok(runs) => runs,
+        Err(err) => {
+            error!("error reading results {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Column labels for the two files: their names, or their paths if the names
+/// are the same, or which is which if the paths are too.
+fn names(before: &Path, after: &Path) -> [String; 2] {
+    let file_name = |path: &Path| {
+        path.file_name()
+            .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+            .into_owned()
+    };
+    let names = [file_name(before), file_name(after)];
+    if names[0] != names[1] {
+        return names;
+    }
+    let paths = [before.to_string_lossy(), after.to_string_lossy()];
+    if paths[0] != paths[1] {
+        return paths.map(|path| path.into_owned());
+    }
+    ["before".to_string(), "after".to_string()]
+}
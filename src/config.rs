@@ -0,0 +1,248 @@
+//! Default options from a TOML configuration file (`--config`), so a long
+//! invocation can be kept in a file rather than spelled out every time.
+//!
+//! Keys are long option names. Those in a table named after a subcommand,
+//! `[read-tree]`, are its options; those before any table are options of
+//! every subcommand that has them. They are put on the command line ahead of
+//! the options given there, leaving out any that an option given sets or
+//! conflicts with, so the command line always wins.
+
+use std::{
+    env,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use clap::{parser::ValueSource, Arg, ArgAction, ArgMatches, Command, Parser};
+use tracing::{debug, error};
+
+use crate::toml::{self, Value};
+
+/// Name of the configuration file looked for when --config isn't given.
+const FILE_NAME: &str = "iobench.toml";
+
+/// Parse the command line, with the options from the configuration file.
+pub fn parse<C: Parser>() -> C {
+    let command = C::command()
+        .arg(
+            Arg::new("config")
+                .long("config")
+                .value_name("FILE")
+                .value_parser(clap::value_parser!(PathBuf))
+                .global(true)
+                .help(format!(
+                    "Read default options from this TOML file, instead of ./{FILE_NAME} or ~/.config/iobench/{FILE_NAME}"
+                )),
+        )
+        .arg(
+            Arg::new("no_config")
+                .long("no-config")
+                .action(ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with("config")
+                .help("Don't read options from any configuration file"),
+        );
+    let args = env::args_os().collect::<Vec<_>>();
+    // Parse once to find the subcommand and which options were given, but
+    // leave errors to the parse with the configured options, which may be
+    // what's missing.
+    let args = match command
+        .clone()
+        .ignore_errors(true)
+        .try_get_matches_from(&args)
+    {
+        Ok(matches) => with_configured(&command, &matches, args),
+        Err(_) => args,
+    };
+    let matches = command.get_matches_from(args);
+    C::from_arg_matches(&matches).unwrap_or_else(|err| err.exit())
+}
+
+/// The command line with the configured options of its subcommand inserted
+/// after the subcommand's name.
+fn with_configured(
+    command: &Command,
+    matches: &ArgMatches,
+    mut args: Vec<OsString>,
+) -> Vec<OsString> {
+    let Some((name, given)) = matches.subcommand() else {
+        return args;
+    };
+    if matches.get_flag("no_config") {
+        return args;
+    }
+    let path = match matches.get_one::<PathBuf>("config") {
+        Some(path) => path.clone(),
+        None => match default_path() {
+            Some(path) => path,
+            None => return args,
+        },
+    };
+    let subcommand = command
+        .find_subcommand(name)
+        .expect("the subcommand parsed");
+    let options = match configured(command, subcommand, &path) {
+        Ok(options) => options,
+        Err(err) => {
+            error!("error in configuration {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    };
+    let on_command_line = |arg: &Arg| {
+        matches!(
+            given.value_source(arg.get_id().as_str()),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        )
+    };
+    let overridden = subcommand
+        .get_arguments()
+        .filter(|&arg| on_command_line(arg))
+        .collect::<Vec<_>>();
+    let mut inserted = Vec::new();
+    for (arg, values) in options {
+        let conflicts = |a: &Arg, b: &Arg| {
+            subcommand
+                .get_arg_conflicts_with(a)
+                .iter()
+                .any(|conflict| conflict.get_id() == b.get_id())
+        };
+        if on_command_line(arg)
+            || overridden
+                .iter()
+                .any(|&given| conflicts(arg, given) || conflicts(given, arg))
+        {
+            continue;
+        }
+        inserted.extend(values);
+    }
+    if inserted.is_empty() {
+        return args;
+    }
+    debug!(
+        "options from {}: {}",
+        path.to_string_lossy(),
+        inserted.join(" ")
+    );
+    let at = subcommand_index(&args).map_or(args.len(), |index| index + 1);
+    args.splice(at..at, inserted.into_iter().map(OsString::from));
+    args
+}
+
+/// The configuration file to read without --config: ./iobench.toml, or the
+/// one in the user's configuration directory.
+fn default_path() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")));
+    [
+        Some(PathBuf::from(FILE_NAME)),
+        config_dir.map(|dir| dir.join("iobench").join(FILE_NAME)),
+    ]
+    .into_iter()
+    .flatten()
+    .find(|path| path.is_file())
+}
+
+/// The options the file at `path` sets for `subcommand`, with the arguments
+/// that set each, those of its own table replacing the general ones.
+fn configured<'a>(
+    command: &Command,
+    subcommand: &'a Command,
+    path: &Path,
+) -> Result<Vec<(&'a Arg, Vec<String>)>, String> {
+    let contents = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut options: Vec<(&Arg, Vec<String>)> = Vec::new();
+    for table in toml::parse(&contents)? {
+        let general = table.name.is_empty();
+        let named = if general {
+            None
+        } else {
+            Some(
+                command
+                    .find_subcommand(&table.name)
+                    .ok_or_else(|| format!("there is no subcommand {:?}", table.name))?,
+            )
+        };
+        for (key, value, line) in &table.entries {
+            // Check the options of other subcommands too, so a mistake
+            // shows up whichever is run.
+            let known = match named {
+                Some(named) => find_option(named, key).is_some(),
+                None => command
+                    .get_subcommands()
+                    .any(|sub| find_option(sub, key).is_some()),
+            };
+            if !known {
+                return Err(format!("unknown option {key:?} on line {line}"));
+            }
+            if named.is_some_and(|named| named.get_name() != subcommand.get_name()) {
+                continue;
+            }
+            let Some(arg) = find_option(subcommand, key) else {
+                continue;
+            };
+            let values = option_args(arg, value).map_err(|err| format!("{err} on line {line}"))?;
+            options.retain(|(other, _)| other.get_id() != arg.get_id());
+            options.push((arg, values));
+        }
+    }
+    Ok(options)
+}
+
+/// The option of `subcommand` called `key`, by its long name or an alias.
+fn find_option<'a>(subcommand: &'a Command, key: &str) -> Option<&'a Arg> {
+    subcommand.get_arguments().find(|arg| {
+        arg.get_long() == Some(key)
+            || arg
+                .get_all_aliases()
+                .is_some_and(|aliases| aliases.contains(&key))
+    })
+}
+
+/// The command-line arguments that give `arg` a value from the file.
+fn option_args(arg: &Arg, value: &Value) -> Result<Vec<String>, String> {
+    let long = arg.get_long().unwrap_or_default();
+    match (arg.get_action(), value) {
+        (ArgAction::SetTrue, Value::Bool(true)) => Ok(vec![format!("--{long}")]),
+        (ArgAction::SetTrue, Value::Bool(false)) => Ok(Vec::new()),
+        (ArgAction::SetTrue, _) => Err(format!("{long} takes true or false")),
+        (action, _) if !action.takes_values() => Err(format!("{long} can't be configured")),
+        // Options that take several values get one each, and the others get
+        // them as a list, e.g. for --sweep-buf.
+        (ArgAction::Append, Value::Array(items)) => items
+            .iter()
+            .map(|item| Ok(format!("--{long}={}", scalar(item)?)))
+            .collect(),
+        (_, Value::Array(items)) => {
+            let items = items.iter().map(scalar).collect::<Result<Vec<_>, _>>()?;
+            Ok(vec![format!("--{long}={}", items.join(","))])
+        }
+        (_, value) => Ok(vec![format!("--{long}={}", scalar(value)?)]),
+    }
+}
+
+fn scalar(value: &Value) -> Result<String, String> {
+    match value {
+        Value::Bool(b) => Ok(b.to_string()),
+        Value::Integer(n) => Ok(n.to_string()),
+        Value::Float(x) => Ok(x.to_string()),
+        Value::String(s) => Ok(s.clone()),
+        Value::Array(_) => Err("nested arrays aren't supported".to_string()),
+    }
+}
+
+/// Where the subcommand's name is on the command line: the first argument
+/// that isn't an option or --config's value.
+fn subcommand_index(args: &[OsString]) -> Option<usize> {
+    let mut index = 1;
+    while let Some(arg) = args.get(index) {
+        if arg == "--config" {
+            index += 2;
+        } else if arg.as_encoded_bytes().starts_with(b"-") {
+            index += 1;
+        } else {
+            return Some(index);
+        }
+    }
+    None
+}
@@ -0,0 +1,478 @@
+//! Copy benchmark: read every file of a tree and write it to a new tree, in
+//! parallel, as a backup or restore job does.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::{Args, ValueEnum};
+use jwalk::rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use tracing::{debug, error};
+
+use crate::{
+    histogram::Histogram,
+    output::print_latency,
+    read_tree::build_pool,
+    units::{format_size, parse_size},
+    walk::{walk_files, WalkArgs},
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// read() into a buffer and write() it out, in user space.
+    #[default]
+    ReadWrite,
+    /// copy_file_range(), copying inside the kernel (or on the server, for
+    /// NFS and SMB), falling back to sendfile() where unsupported.
+    CopyFileRange,
+    /// sendfile(), copying inside the kernel.
+    Sendfile,
+    /// FICLONE, sharing the source's blocks (a reflink) on filesystems that
+    /// support it, such as btrfs and XFS; other files are copied with
+    /// copy_file_range() instead.
+    Clone,
+    /// FICLONERANGE, cloning the blocks in --buf-size ranges, a multiple of
+    /// the filesystem's block size.
+    CloneRange,
+}
+
+impl CopyMethod {
+    fn name(self) -> &'static str {
+        match self {
+            CopyMethod::ReadWrite => "read/write",
+            CopyMethod::CopyFileRange => "copy_file_range",
+            CopyMethod::Sendfile => "sendfile",
+            CopyMethod::Clone => "FICLONE",
+            CopyMethod::CloneRange => "FICLONERANGE",
+        }
+    }
+
+    fn clones(self) -> bool {
+        matches!(self, CopyMethod::Clone | CopyMethod::CloneRange)
+    }
+}
+
+/// Options for the `copy-tree` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct CopyTreeArgs {
+    /// The directory tree to copy.
+    pub src: PathBuf,
+    /// Where to copy it to; created if missing, and existing files are overwritten.
+    pub dst: PathBuf,
+    /// Number of concurrent threads to use, for both listing and copying.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+    /// How to copy the contents of each file.
+    #[arg(long, value_enum, default_value_t = CopyMethod::ReadWrite)]
+    pub method: CopyMethod,
+    /// Size of each read and write, or of each in-kernel copy call (e.g. 64k, 1M).
+    #[arg(long, default_value = "1M", value_parser = parse_size)]
+    pub buf_size: u64,
+    /// Call fsync on each copied file before closing it.
+    #[arg(long)]
+    pub fsync: bool,
+
+    #[command(flatten)]
+    pub walk: WalkArgs,
+}
+
+#[derive(Default)]
+struct CopyStats {
+    files: u64,
+    bytes: u64,
+    errors: u64,
+    /// Time to copy each successfully copied file, in nanoseconds.
+    latency: Histogram,
+    /// Time spent opening the source and creating the destination.
+    open_ns: u64,
+    read_ns: u64,
+    /// Time spent writing, including any fsync.
+    write_ns: u64,
+    /// Time spent in copy_file_range, sendfile or clone calls.
+    kernel_ns: u64,
+    /// Files copied with sendfile because copy_file_range failed.
+    fallbacks: u64,
+    cloned: u64,
+    /// Files copied because they couldn't be cloned, with the first error.
+    uncloned: u64,
+    clone_error: Option<io::Error>,
+}
+
+impl CopyStats {
+    fn combine(mut self, other: Self) -> Self {
+        self.files += other.files;
+        self.bytes += other.bytes;
+        self.errors += other.errors;
+        self.latency.merge(&other.latency);
+        self.open_ns += other.open_ns;
+        self.read_ns += other.read_ns;
+        self.write_ns += other.write_ns;
+        self.kernel_ns += other.kernel_ns;
+        self.fallbacks += other.fallbacks;
+        self.cloned += other.cloned;
+        self.uncloned += other.uncloned;
+        self.clone_error = self.clone_error.or(other.clone_error);
+        self
+    }
+}
+
+pub fn copy_tree(options: &CopyTreeArgs) {
+    let (src, dst) = (&options.src, &options.dst);
+    let threads = options.threads.max(1);
+    println!(
+        "-- copying {src:?} to {dst:?} using {threads} threads ({}, {} chunks)",
+        options.method.name(),
+        format_size(options.buf_size),
+    );
+
+    let t1 = Instant::now();
+    let files = walk_files(src, threads, &options.walk)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let relative = path.strip_prefix(src).ok()?.to_path_buf();
+            Some((path, relative))
+        })
+        .collect::<Vec<_>>();
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- list: {:.0} files/s  ({} files in {dur_s} s)",
+        files.len() as f64 / dur_s,
+        files.len(),
+    );
+
+    let t1 = Instant::now();
+    let mut dirs = files
+        .iter()
+        .filter_map(|(_, relative)| relative.parent())
+        .map(|parent| dst.join(parent))
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.dedup();
+    for dir in &dirs {
+        if let Err(err) = fs::create_dir_all(dir) {
+            error!("error creating directory {}: {err}", dir.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- mkdir: {:.0} dirs/s  ({} dirs in {dur_s} s)",
+        dirs.len() as f64 / dur_s,
+        dirs.len(),
+    );
+
+    if options.method == CopyMethod::CloneRange {
+        match block_size(dst) {
+            Ok(block) if !options.buf_size.is_multiple_of(block) => {
+                error!(
+                    "FICLONERANGE needs ranges of whole blocks, but --buf-size {} isn't a multiple of the {} blocks of {}",
+                    format_size(options.buf_size),
+                    format_size(block),
+                    dst.to_string_lossy()
+                );
+                std::process::exit(1);
+            }
+            Ok(_) => {}
+            Err(err) => {
+                error!(
+                    "error checking the block size of {}: {err}",
+                    dst.to_string_lossy()
+                );
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let t1 = Instant::now();
+    let stats = build_pool(threads).install(|| {
+        files
+            .par_iter()
+            .map_init(
+                || vec![0; options.buf_size.max(1) as usize],
+                |buf, (path, relative)| copy_one(path, &dst.join(relative), buf, options),
+            )
+            .reduce(CopyStats::default, CopyStats::combine)
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+    let total_size_mb = stats.bytes as f64 / 1_000_000.0;
+    println!(
+        "-- copy: {:.0} MB/s   {:.0} files/s  ({} MB in {} s)",
+        total_size_mb / dur_s,
+        stats.files as f64 / dur_s,
+        total_size_mb,
+        dur_s,
+    );
+    print_latency("copy", &stats.latency);
+    print_breakdown(&stats);
+    if options.method.clones() {
+        println!(
+            "-- clone: {:.0} clones/s  ({} of {} files cloned)",
+            stats.cloned as f64 / dur_s,
+            stats.cloned,
+            stats.files,
+        );
+    }
+    if let Some(err) = &stats.clone_error {
+        println!(
+            "-- {} files were copied instead: cloning failed ({err}); reflinks need a filesystem \
+             that supports them (btrfs, XFS with reflink=1, bcachefs, ...) with SRC and DST on it",
+            stats.uncloned,
+        );
+    }
+    if stats.fallbacks > 0 {
+        println!(
+            "-- {} files copied with sendfile, as copy_file_range isn't supported for them",
+            stats.fallbacks
+        );
+    }
+    if stats.errors > 0 {
+        println!("-- errors: {}", stats.errors);
+    }
+}
+
+/// The block size of the filesystem `path` is on.
+fn block_size(path: &Path) -> Result<u64, io::Error> {
+    let dir = File::open(path)?;
+    // SAFETY: statvfs is plain data, for which all zeros is a valid value.
+    let mut stat = unsafe { std::mem::zeroed::<libc::statvfs>() };
+    // SAFETY: dir is an open file and stat a statvfs for fstatvfs to fill in.
+    if unsafe { libc::fstatvfs(dir.as_raw_fd(), &mut stat) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(stat.f_bsize.max(1))
+}
+
+/// Print how the copy time summed over all threads was split between opening,
+/// reading and writing (or copying in the kernel).
+fn print_breakdown(stats: &CopyStats) {
+    let phases = [
+        ("open", stats.open_ns),
+        ("read", stats.read_ns),
+        ("write", stats.write_ns),
+        ("kernel copy", stats.kernel_ns),
+    ];
+    let total_ns = phases.iter().map(|&(_, ns)| ns).sum::<u64>().max(1) as f64;
+    let parts = phases
+        .iter()
+        .filter(|&&(name, ns)| ns > 0 || name == "open")
+        .map(|&(name, ns)| {
+            format!(
+                "{name} {:.3} s ({:.0}%)",
+                ns as f64 / 1e9,
+                100.0 * ns as f64 / total_ns
+            )
+        })
+        .collect::<Vec<_>>();
+    println!("-- copy time: {}", parts.join("   "));
+}
+
+fn copy_one(src: &Path, dst: &Path, buf: &mut [u8], options: &CopyTreeArgs) -> CopyStats {
+    let mut stats = CopyStats::default();
+    let t1 = Instant::now();
+    match copy_file(src, dst, buf, options, &mut stats) {
+        Ok(()) => {
+            stats.files += 1;
+            stats.latency.record(t1.elapsed().as_nanos() as u64);
+        }
+        Err(err) => {
+            stats.errors += 1;
+            debug!("error copying {}: {err}", src.to_string_lossy());
+        }
+    }
+    stats
+}
+
+fn copy_file(
+    src: &Path,
+    dst: &Path,
+    buf: &mut [u8],
+    options: &CopyTreeArgs,
+    stats: &mut CopyStats,
+) -> Result<(), io::Error> {
+    let t1 = Instant::now();
+    let mut from = File::open(src)?;
+    let mut to = File::create(dst)?;
+    stats.open_ns += t1.elapsed().as_nanos() as u64;
+    match options.method {
+        CopyMethod::ReadWrite => read_write(&mut from, &mut to, buf, stats)?,
+        CopyMethod::CopyFileRange => {
+            let t_copy = Instant::now();
+            let result = copy_in_kernel(src, &from, &to, buf.len(), stats);
+            stats.kernel_ns += t_copy.elapsed().as_nanos() as u64;
+            result?;
+        }
+        CopyMethod::Clone | CopyMethod::CloneRange => {
+            let t_copy = Instant::now();
+            let result = clone(&from, &to, options.method, buf.len() as u64).and_then(|()| {
+                stats.bytes += from.metadata()?.len();
+                stats.cloned += 1;
+                Ok(())
+            });
+            let result = match result {
+                Err(err) if is_clone_unsupported(&err) => {
+                    debug!("clone failed ({err}), copying: {}", src.to_string_lossy());
+                    stats.uncloned += 1;
+                    stats.clone_error.get_or_insert(err);
+                    copy_in_kernel(src, &from, &to, buf.len(), stats)
+                }
+                result => result,
+            };
+            stats.kernel_ns += t_copy.elapsed().as_nanos() as u64;
+            result?;
+        }
+        CopyMethod::Sendfile => {
+            let t_copy = Instant::now();
+            let result = kernel_copy(&from, &to, buf.len(), sendfile, &mut stats.bytes);
+            stats.kernel_ns += t_copy.elapsed().as_nanos() as u64;
+            result?;
+        }
+    }
+    if options.fsync {
+        let t_sync = Instant::now();
+        to.sync_all()?;
+        stats.write_ns += t_sync.elapsed().as_nanos() as u64;
+    }
+    Ok(())
+}
+
+/// Copy with copy_file_range, or with sendfile if that isn't supported.
+fn copy_in_kernel(
+    src: &Path,
+    from: &File,
+    to: &File,
+    chunk: usize,
+    stats: &mut CopyStats,
+) -> Result<(), io::Error> {
+    let start = stats.bytes;
+    match kernel_copy(from, to, chunk, copy_file_range, &mut stats.bytes) {
+        // Nothing has been copied yet, so sendfile can start from the beginning.
+        Err(err) if stats.bytes == start && is_unsupported(&err) => {
+            debug!(
+                "copy_file_range failed ({err}), using sendfile: {}",
+                src.to_string_lossy()
+            );
+            stats.fallbacks += 1;
+            kernel_copy(from, to, chunk, sendfile, &mut stats.bytes)
+        }
+        result => result,
+    }
+}
+
+/// Make `to` share the blocks of `from`, all at once with FICLONE or in
+/// ranges of `chunk` bytes with FICLONERANGE.
+fn clone(from: &File, to: &File, method: CopyMethod, chunk: u64) -> Result<(), io::Error> {
+    let check = |result: i32| {
+        if result < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    };
+    if method == CopyMethod::Clone {
+        // SAFETY: FICLONE takes the source file descriptor as its argument.
+        return check(unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONE, from.as_raw_fd()) });
+    }
+    let size = from.metadata()?.len();
+    let mut offset = 0;
+    // An empty file still gets a call, so it is only counted as cloned where
+    // the filesystem can clone, as with FICLONE.
+    loop {
+        // Ranges must be block-aligned, except that the last may run to the end of the file.
+        let len = if size - offset <= chunk { 0 } else { chunk };
+        let range = libc::file_clone_range {
+            src_fd: i64::from(from.as_raw_fd()),
+            src_offset: offset,
+            src_length: len,
+            dest_offset: offset,
+        };
+        // SAFETY: FICLONERANGE reads a file_clone_range, which outlives the call.
+        check(unsafe { libc::ioctl(to.as_raw_fd(), libc::FICLONERANGE, &range) })?;
+        offset += chunk;
+        if offset >= size {
+            return Ok(());
+        }
+    }
+}
+
+/// Copy through a user-space buffer.
+fn read_write(
+    from: &mut File,
+    to: &mut File,
+    buf: &mut [u8],
+    stats: &mut CopyStats,
+) -> Result<(), io::Error> {
+    loop {
+        let t_read = Instant::now();
+        let n = from.read(buf)?;
+        let t_write = Instant::now();
+        stats.read_ns += (t_write - t_read).as_nanos() as u64;
+        if n == 0 {
+            break;
+        }
+        to.write_all(&buf[..n])?;
+        stats.write_ns += t_write.elapsed().as_nanos() as u64;
+        stats.bytes += n as u64;
+    }
+    Ok(())
+}
+
+/// An in-kernel copy call: copy up to `len` bytes from the current offset of
+/// one file descriptor to another, returning the number copied, 0 at the end
+/// of the source, or -1 with errno set.
+type KernelCopy = fn(from: i32, to: i32, len: usize) -> isize;
+
+fn copy_file_range(from: i32, to: i32, len: usize) -> isize {
+    // SAFETY: null offsets make the kernel use and update the file offsets.
+    unsafe { libc::copy_file_range(from, std::ptr::null_mut(), to, std::ptr::null_mut(), len, 0) }
+}
+
+fn sendfile(from: i32, to: i32, len: usize) -> isize {
+    // SAFETY: a null offset makes the kernel use and update the source file offset.
+    unsafe { libc::sendfile(to, from, std::ptr::null_mut(), len) }
+}
+
+/// Copy the rest of `from` to `to` with repeated calls to `call`, each of at most `chunk` bytes.
+fn kernel_copy(
+    from: &File,
+    to: &File,
+    chunk: usize,
+    call: KernelCopy,
+    bytes: &mut u64,
+) -> Result<(), io::Error> {
+    loop {
+        let n = call(from.as_raw_fd(), to.as_raw_fd(), chunk);
+        match n {
+            0 => return Ok(()),
+            n if n < 0 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            n => *bytes += n as u64,
+        }
+    }
+}
+
+/// Whether FICLONE or FICLONERANGE failed because the filesystems can't
+/// share blocks between these files. EINVAL isn't one: it means a range that
+/// isn't block-aligned, which the check of --buf-size should rule out.
+fn is_clone_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::ENOTTY)
+    )
+}
+
+/// Whether copy_file_range failed because it can't be used for these files,
+/// e.g. across filesystems on older kernels or on filesystems that lack it.
+fn is_unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EINVAL)
+    )
+}
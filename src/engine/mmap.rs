@@ -0,0 +1,110 @@
+//! Memory-mapped reads: map each file and fault in every page.
+
+use std::{fs::File, io, os::fd::AsRawFd, ptr};
+
+use tracing::debug;
+
+use crate::checksum::Xxh3;
+
+use super::{count_read, mark_first_byte, IoEngine};
+
+pub struct MmapEngine {
+    populate: bool,
+    page_size: usize,
+}
+
+impl MmapEngine {
+    pub fn new(populate: bool) -> Self {
+        // SAFETY: sysconf has no preconditions.
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        Self {
+            populate,
+            page_size: usize::try_from(page_size).unwrap_or(4096),
+        }
+    }
+}
+
+impl IoEngine for MmapEngine {
+    fn read_contents(
+        &mut self,
+        f: &mut File,
+        size: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+        hasher: Option<&mut Xxh3>,
+    ) -> Result<(), io::Error> {
+        // Touching pages past the end of a file raises SIGBUS, so map only what
+        // the file holds now, not what the listing saw.
+        let current = f.metadata()?.len();
+        if current != size {
+            debug!("file size changed from {size} to {current} since listing: {pathstr}");
+        }
+        self.touch_pages(f, current.min(size) as usize, bytes, hasher)
+    }
+
+    fn read_head(
+        &mut self,
+        f: &mut File,
+        len: u64,
+        _pathstr: &str,
+        bytes: &mut u64,
+    ) -> Result<(), io::Error> {
+        let current = f.metadata()?.len();
+        self.touch_pages(f, current.min(len) as usize, bytes, None)
+    }
+}
+
+impl MmapEngine {
+    /// Map the first `len` bytes of `f` and read one byte from every page, or
+    /// all of them if they are to be hashed.
+    fn touch_pages(
+        &self,
+        f: &File,
+        len: usize,
+        bytes: &mut u64,
+        hasher: Option<&mut Xxh3>,
+    ) -> Result<(), io::Error> {
+        if len == 0 {
+            return Ok(());
+        }
+        let mut flags = libc::MAP_PRIVATE;
+        if self.populate {
+            flags |= libc::MAP_POPULATE;
+        }
+        // SAFETY: a fresh read-only private mapping of an open file.
+        let addr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                flags,
+                f.as_raw_fd(),
+                0,
+            )
+        };
+        if addr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let data = addr.cast::<u8>();
+        // SAFETY: the mapping is len > 0 bytes long.
+        unsafe { ptr::read_volatile(data) };
+        mark_first_byte();
+        if let Some(hasher) = hasher {
+            // SAFETY: the mapping is len bytes long and stays mapped until below.
+            hasher.update(unsafe { std::slice::from_raw_parts(data, len) });
+        } else {
+            let mut checksum = 0u8;
+            for offset in (0..len).step_by(self.page_size) {
+                // SAFETY: offset < len, within the mapping; volatile so the read isn't elided.
+                checksum ^= unsafe { ptr::read_volatile(data.add(offset)) };
+            }
+            std::hint::black_box(checksum);
+        }
+        // SAFETY: unmapping the region mapped above.
+        unsafe {
+            libc::munmap(addr, len);
+        }
+        count_read(bytes, len as u64);
+        Ok(())
+    }
+}
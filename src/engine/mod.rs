@@ -0,0 +1,594 @@
+//! Read engines: how the read phase transfers the contents of each file.
+//!
+//! Each engine implements [`IoEngine`]. Engines hold per-thread state (buffers,
+//! rings), so every reader thread builds its own instance on first use via
+//! [`EngineArgs::with_thread_engine`].
+
+use std::{
+    alloc::{self, Layout},
+    cell::{Cell, RefCell},
+    fmt,
+    fs::{File, OpenOptions},
+    io,
+    ops::{Deref, DerefMut},
+    os::{fd::AsRawFd, unix::fs::OpenOptionsExt},
+    path::Path,
+    ptr::NonNull,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use clap::{Args, ValueEnum};
+use tracing::debug;
+
+use crate::{
+    cache::{self, Fadvise},
+    checksum::{ChecksumKind, Xxh3},
+    output::report,
+    units::{format_size, parse_size},
+};
+
+mod mmap;
+mod ring;
+mod splice;
+mod sync;
+mod uring;
+
+/// A way of reading the contents of an open file.
+pub trait IoEngine {
+    /// Open a file for reading.
+    fn open(&self, path: &Path) -> Result<File, io::Error> {
+        File::open(path)
+    }
+
+    /// Read the whole of `f`, whose size was `size` when listed, adding the
+    /// number of bytes read to `bytes` as the read progresses, and feeding
+    /// the data to `hasher` if one is given.
+    fn read_contents(
+        &mut self,
+        f: &mut File,
+        size: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+        hasher: Option<&mut Xxh3>,
+    ) -> Result<(), io::Error>;
+
+    /// Read only the first `len` bytes of `f`, or all of it if it is shorter.
+    fn read_head(
+        &mut self,
+        f: &mut File,
+        len: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+    ) -> Result<(), io::Error> {
+        let size = f.metadata()?.len().min(len);
+        self.read_contents(f, size, pathstr, bytes, None)
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Blocking read() calls into a single buffer.
+    #[default]
+    Sync,
+    /// Map each file into memory and touch every page.
+    Mmap,
+    /// io_uring, with several reads per file in flight at once.
+    Uring,
+    /// Blocking read() calls on files opened with O_DIRECT (same as sync with --direct).
+    Direct,
+    /// splice() through a pipe into /dev/null, without copying to user space.
+    Splice,
+}
+
+/// What to do about the holes in sparse files, for `--sparse`.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sparse {
+    /// Find the holes, and report how much of the files they make up, but
+    /// read them as zeros like everything else.
+    Report,
+    /// Read only the data regions, skipping the holes.
+    Skip,
+}
+
+impl Engine {
+    pub fn name(self) -> &'static str {
+        match self {
+            Engine::Sync => "sync",
+            Engine::Mmap => "mmap",
+            Engine::Uring => "uring",
+            Engine::Direct => "direct",
+            Engine::Splice => "splice",
+        }
+    }
+}
+
+/// Options selecting and configuring the read engine.
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+pub struct EngineArgs {
+    /// I/O engine used to read file contents.
+    #[arg(long, value_enum, default_value_t = Engine::Sync)]
+    pub engine: Engine,
+    /// Size of each read (e.g. 4k, 1M), for engines that read into buffers, or
+    /// of the pipe for the splice engine.
+    #[arg(long, default_value = "64k", value_parser = parse_size)]
+    pub buf_size: u64,
+    /// Submission queue depth per thread for the uring engine.
+    #[arg(long, default_value_t = 32)]
+    pub queue_depth: u32,
+    /// Prefault mappings with MAP_POPULATE for the mmap engine.
+    #[arg(long)]
+    pub mmap_populate: bool,
+    /// Open files with O_DIRECT to bypass the page cache (sync and uring engines).
+    #[arg(long)]
+    pub direct: bool,
+    /// Read only the first SIZE bytes of each file (e.g. 64k), like a
+    /// thumbnailer, virus scanner or file(1) sniffing the file type.
+    #[arg(long, value_name = "SIZE", value_parser = parse_size)]
+    pub head: Option<u64>,
+    /// Compute a checksum of each file as it is read, and save them in a
+    /// manifest (see --manifest).
+    #[arg(long, value_enum, conflicts_with = "head")]
+    pub checksum: Option<ChecksumKind>,
+    /// Drop each file from the page cache after reading it, with
+    /// posix_fadvise(POSIX_FADV_DONTNEED), so repeated runs read mostly from
+    /// the device without needing root for --drop-caches.
+    #[arg(long)]
+    pub evict_after_read: bool,
+    /// Access-pattern hint given with posix_fadvise after opening each file.
+    #[arg(long, value_enum)]
+    pub fadvise: Option<Fadvise>,
+    /// Set O_NOATIME on each file, where permitted (for its owner, or with
+    /// CAP_FOWNER), so reads don't cause atime writes.
+    #[arg(long)]
+    pub noatime: bool,
+    /// Try each read with preadv2(RWF_NOWAIT) first, which only succeeds if
+    /// the data is in the page cache, to report the cache hit ratio (sync
+    /// engine).
+    #[arg(long)]
+    pub probe_cache: bool,
+    /// Find the holes in each file with lseek(SEEK_DATA/SEEK_HOLE), and report
+    /// them or skip reading them (sync engine).
+    #[arg(long, value_enum)]
+    pub sparse: Option<Sparse>,
+}
+
+thread_local! {
+    /// This thread's engine and the options it was built with.
+    static THREAD_ENGINE: RefCell<Option<(EngineArgs, Box<dyn IoEngine>)>> = const { RefCell::new(None) };
+    /// When the first data of the file being read on this thread arrived.
+    static FIRST_BYTE: Cell<Option<Instant>> = const { Cell::new(None) };
+}
+
+/// Add `n` bytes just read to a file's count, noting the time if they are the
+/// first.
+fn count_read(bytes: &mut u64, n: u64) {
+    if *bytes == 0 && n > 0 {
+        mark_first_byte();
+    }
+    *bytes += n;
+}
+
+fn mark_first_byte() {
+    FIRST_BYTE.with(|first| {
+        if first.get().is_none() {
+            first.set(Some(Instant::now()));
+        }
+    });
+}
+
+/// When the first data of the file read last on this thread arrived, if any
+/// did, clearing it for the next file.
+pub fn take_first_byte() -> Option<Instant> {
+    FIRST_BYTE.with(Cell::take)
+}
+
+impl EngineArgs {
+    /// Whether files should be opened with O_DIRECT.
+    fn use_direct(&self) -> bool {
+        self.direct || self.engine == Engine::Direct
+    }
+
+    /// The buffer size actually used: O_DIRECT transfers must be whole blocks,
+    /// so the requested size is rounded up to the alignment in that case.
+    pub fn effective_buf_size(&self) -> usize {
+        let size = self.buf_size.max(1) as usize;
+        if self.use_direct() {
+            size.next_multiple_of(DIRECT_ALIGN)
+        } else {
+            size
+        }
+    }
+
+    fn build(&self) -> Result<Box<dyn IoEngine>, io::Error> {
+        let buf_size = self.effective_buf_size();
+        let engine: Box<dyn IoEngine> = match self.engine {
+            Engine::Sync | Engine::Direct => Box::new(sync::SyncEngine::new(
+                buf_size,
+                self.use_direct(),
+                self.probe_cache,
+                self.sparse,
+            )),
+            Engine::Mmap => Box::new(mmap::MmapEngine::new(self.mmap_populate)),
+            Engine::Uring => Box::new(uring::UringEngine::new(
+                self.queue_depth,
+                buf_size,
+                self.use_direct(),
+            )?),
+            Engine::Splice => Box::new(splice::SpliceEngine::new(buf_size)?),
+        };
+        if self.fadvise.is_none() && !self.noatime {
+            return Ok(engine);
+        }
+        Ok(Box::new(AfterOpen {
+            inner: engine,
+            fadvise: self.fadvise,
+            noatime: self.noatime,
+        }))
+    }
+
+    /// Check that the engine can be used at all, so a missing kernel feature or
+    /// an invalid combination of options is reported once rather than as an
+    /// error for every file.
+    pub fn check_available(&self) -> Result<(), io::Error> {
+        if self.direct && self.engine == Engine::Mmap {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--direct cannot be used with memory-mapped reads",
+            ));
+        }
+        if self.direct && self.engine == Engine::Splice {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--direct cannot be used with spliced reads, which go through the page cache",
+            ));
+        }
+        if self.checksum.is_some() && self.engine == Engine::Uring {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--checksum cannot be used with io_uring reads, which complete out of order",
+            ));
+        }
+        if self.checksum.is_some() && self.engine == Engine::Splice {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--checksum cannot be used with spliced reads, which never reach user space",
+            ));
+        }
+        if self.probe_cache && (self.engine != Engine::Sync || self.direct) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--probe-cache needs the sync engine, reading through the page cache",
+            ));
+        }
+        if self.sparse.is_some() && !matches!(self.engine, Engine::Sync | Engine::Direct) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--sparse needs the sync or direct engine",
+            ));
+        }
+        if self.sparse == Some(Sparse::Skip) && self.checksum.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--sparse skip cannot be used with --checksum, which needs the holes' zeros too",
+            ));
+        }
+        self.build().map(|_| ())
+    }
+
+    /// Apply the per-file cache options to a file that has just been read,
+    /// returning whether it was evicted from the page cache.
+    pub fn finish_file(&self, f: &File, pathstr: &str) -> bool {
+        if !self.evict_after_read {
+            return false;
+        }
+        match cache::evict(f) {
+            Ok(()) => true,
+            Err(err) => {
+                debug!("error evicting file from the page cache: {pathstr}: {err}");
+                false
+            }
+        }
+    }
+
+    /// Run `f` with this thread's engine, building it first if this thread has
+    /// none yet or it was built with different options.
+    pub fn with_thread_engine<T>(
+        &self,
+        f: impl FnOnce(&mut dyn IoEngine) -> Result<T, io::Error>,
+    ) -> Result<T, io::Error> {
+        THREAD_ENGINE.with_borrow_mut(|slot| {
+            if slot.as_ref().is_none_or(|(args, _)| args != self) {
+                *slot = Some((self.clone(), self.build()?));
+            }
+            let (_, engine) = slot.as_mut().expect("thread engine initialized");
+            f(engine.as_mut())
+        })
+    }
+}
+
+impl fmt::Display for EngineArgs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} engine", self.engine.name())?;
+        if self.direct && self.engine != Engine::Direct {
+            write!(f, " with O_DIRECT")?;
+        }
+        match self.engine {
+            Engine::Sync | Engine::Direct => {
+                let buf_size = format_size(self.effective_buf_size() as u64);
+                write!(f, ", {buf_size} buffer")?;
+            }
+            Engine::Uring => {
+                let buf_size = format_size(self.effective_buf_size() as u64);
+                write!(f, ", {buf_size} buffers, queue depth {}", self.queue_depth)?;
+            }
+            Engine::Splice => {
+                let buf_size = format_size(self.effective_buf_size() as u64);
+                write!(f, ", {buf_size} pipe")?;
+            }
+            Engine::Mmap if self.mmap_populate => write!(f, " with MAP_POPULATE")?,
+            Engine::Mmap => {}
+        }
+        if let Some(head) = self.head {
+            write!(f, ", first {} of each file", format_size(head))?;
+        }
+        if self.checksum.is_some() {
+            write!(f, ", with checksums")?;
+        }
+        if let Some(hint) = self.fadvise {
+            write!(f, ", fadvise {}", hint.name())?;
+        }
+        if self.noatime {
+            write!(f, ", O_NOATIME")?;
+        }
+        if self.probe_cache {
+            write!(f, ", probing for cache hits")?;
+        }
+        if self.evict_after_read {
+            write!(f, ", evicting files after reading")?;
+        }
+        match self.sparse {
+            Some(Sparse::Report) => write!(f, ", finding holes")?,
+            Some(Sparse::Skip) => write!(f, ", skipping holes")?,
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+/// Number of files on which --noatime set O_NOATIME, and on which it was refused.
+static NOATIME_SET: AtomicU64 = AtomicU64::new(0);
+static NOATIME_REFUSED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of reads that --probe-cache found in the page cache, that had to
+/// wait for the device, and that couldn't be probed.
+static CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+static CACHE_UNPROBED: AtomicU64 = AtomicU64::new(0);
+
+/// Files whose holes --sparse looked for, their total size, how many had
+/// holes, and the bytes in the holes.
+static SPARSE_MAPPED: AtomicU64 = AtomicU64::new(0);
+static SPARSE_APPARENT: AtomicU64 = AtomicU64::new(0);
+static SPARSE_FILES: AtomicU64 = AtomicU64::new(0);
+static SPARSE_HOLES: AtomicU64 = AtomicU64::new(0);
+
+/// Print what the per-file options found for the files read since the last
+/// report: the page cache hit ratio, with --probe-cache, how much of the files
+/// were holes, with --sparse, and whether O_NOATIME was honored, with --noatime.
+pub fn print_engine_report() {
+    let hits = CACHE_HITS.swap(0, Ordering::Relaxed);
+    let misses = CACHE_MISSES.swap(0, Ordering::Relaxed);
+    let unprobed = CACHE_UNPROBED.swap(0, Ordering::Relaxed);
+    if hits + misses > 0 {
+        report!(
+            "-- cache hits: {:.1}% of reads ({hits} from the page cache, {misses} from the device)",
+            100.0 * hits as f64 / (hits + misses) as f64,
+        );
+    }
+    if unprobed > 0 {
+        report!("-- cache hits: {unprobed} reads couldn't be probed (RWF_NOWAIT not supported)");
+    }
+    let mapped = SPARSE_MAPPED.swap(0, Ordering::Relaxed);
+    let apparent = SPARSE_APPARENT.swap(0, Ordering::Relaxed);
+    let sparse = SPARSE_FILES.swap(0, Ordering::Relaxed);
+    let holes = SPARSE_HOLES.swap(0, Ordering::Relaxed);
+    if mapped > 0 {
+        report!(
+            "-- sparse: {sparse} of {mapped} files have holes: {:.1} MB of data in {:.1} MB apparent size ({:.1}% holes)",
+            (apparent - holes) as f64 / 1e6,
+            apparent as f64 / 1e6,
+            100.0 * holes as f64 / apparent.max(1) as f64,
+        );
+    }
+    let set = NOATIME_SET.swap(0, Ordering::Relaxed);
+    let refused = NOATIME_REFUSED.swap(0, Ordering::Relaxed);
+    if refused > 0 {
+        report!(
+            "-- noatime: refused for {refused} of {} files (only their owner or CAP_FOWNER may set it), so reading them may update atime",
+            set + refused
+        );
+    } else if set > 0 {
+        report!("-- noatime: honored for all {set} files");
+    }
+}
+
+/// The data regions of the first `size` bytes of `f`, as (start, end)
+/// offsets, counting its holes for the report. Filesystems without
+/// SEEK_DATA support have no holes as far as this can tell.
+fn data_regions(f: &File, size: u64) -> Result<Vec<(u64, u64)>, io::Error> {
+    let fd = f.as_raw_fd();
+    let mut regions = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        // SAFETY: lseek only moves the file position of an open descriptor.
+        let start = unsafe { libc::lseek(fd, offset as i64, libc::SEEK_DATA) };
+        if start < 0 {
+            let err = io::Error::last_os_error();
+            match err.raw_os_error() {
+                // No more data after offset: the rest is a hole.
+                Some(libc::ENXIO) => break,
+                Some(libc::EINVAL) if regions.is_empty() => {
+                    regions.push((0, size));
+                    break;
+                }
+                _ => return Err(err),
+            }
+        }
+        let start = start as u64;
+        // SAFETY: as above.
+        let end = unsafe { libc::lseek(fd, start as i64, libc::SEEK_HOLE) };
+        if end < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let end = (end as u64).min(size);
+        if start >= end {
+            break;
+        }
+        regions.push((start, end));
+        offset = end;
+    }
+    // SAFETY: as above; reading starts from the beginning again.
+    if unsafe { libc::lseek(fd, 0, libc::SEEK_SET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let data = regions.iter().map(|(start, end)| end - start).sum::<u64>();
+    SPARSE_MAPPED.fetch_add(1, Ordering::Relaxed);
+    SPARSE_APPARENT.fetch_add(size, Ordering::Relaxed);
+    if data < size {
+        SPARSE_FILES.fetch_add(1, Ordering::Relaxed);
+        SPARSE_HOLES.fetch_add(size - data, Ordering::Relaxed);
+    }
+    Ok(regions)
+}
+
+/// Add O_NOATIME to the status flags of an open file.
+fn set_noatime(f: &File) -> Result<(), io::Error> {
+    let fd = f.as_raw_fd();
+    // SAFETY: fd is an open file; F_GETFL and F_SETFL take no pointers.
+    unsafe {
+        let flags = libc::fcntl(fd, libc::F_GETFL);
+        if flags < 0 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NOATIME) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// An engine that applies the per-file options that need only an open file
+/// (an fadvise hint, O_NOATIME) as soon as each file is opened, then reads it
+/// with the inner engine.
+struct AfterOpen {
+    inner: Box<dyn IoEngine>,
+    fadvise: Option<Fadvise>,
+    noatime: bool,
+}
+
+impl IoEngine for AfterOpen {
+    fn open(&self, path: &Path) -> Result<File, io::Error> {
+        let f = self.inner.open(path)?;
+        if self.noatime {
+            match set_noatime(&f) {
+                Ok(()) => NOATIME_SET.fetch_add(1, Ordering::Relaxed),
+                Err(err) => {
+                    debug!("error setting O_NOATIME: {}: {err}", path.to_string_lossy());
+                    NOATIME_REFUSED.fetch_add(1, Ordering::Relaxed)
+                }
+            };
+        }
+        if let Some(hint) = self.fadvise {
+            if let Err(err) = cache::advise(&f, hint) {
+                debug!(
+                    "error giving fadvise hint: {}: {err}",
+                    path.to_string_lossy()
+                );
+            }
+        }
+        Ok(f)
+    }
+
+    fn read_contents(
+        &mut self,
+        f: &mut File,
+        size: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+        hasher: Option<&mut Xxh3>,
+    ) -> Result<(), io::Error> {
+        self.inner.read_contents(f, size, pathstr, bytes, hasher)
+    }
+
+    fn read_head(
+        &mut self,
+        f: &mut File,
+        len: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+    ) -> Result<(), io::Error> {
+        self.inner.read_head(f, len, pathstr, bytes)
+    }
+}
+
+/// Open options for reading, with O_DIRECT if requested.
+fn open_for_read(path: &Path, direct: bool) -> Result<File, io::Error> {
+    let mut options = OpenOptions::new();
+    options.read(true);
+    if direct {
+        options.custom_flags(libc::O_DIRECT);
+    }
+    options.open(path)
+}
+
+/// Alignment of buffer addresses and transfer sizes required for O_DIRECT.
+/// 4096 covers the logical block size of practically all devices.
+pub const DIRECT_ALIGN: usize = 4096;
+
+/// Zero-initialized heap buffer aligned to [`DIRECT_ALIGN`], so the same
+/// buffers work whether or not files are opened with O_DIRECT.
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    len: usize,
+    /// Allocated size: `len` rounded up to a whole number of aligned blocks.
+    capacity: usize,
+}
+
+// The buffer is uniquely owned, like a Box<[u8]>.
+unsafe impl Send for AlignedBuf {}
+
+impl AlignedBuf {
+    pub fn new(len: usize) -> Self {
+        let capacity = len.max(1).next_multiple_of(DIRECT_ALIGN);
+        let layout = Self::layout(capacity);
+        // SAFETY: the layout has a non-zero size.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Self { ptr, len, capacity }
+    }
+
+    fn layout(len: usize) -> Layout {
+        Layout::from_size_align(len, DIRECT_ALIGN).expect("valid buffer layout")
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: ptr points to len initialized bytes owned by self.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: ptr points to len initialized bytes owned by self.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        // SAFETY: allocated in AlignedBuf::new with the same layout.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), Self::layout(self.capacity)) }
+    }
+}
@@ -0,0 +1,336 @@
+//! Minimal io_uring submission/completion ring, using raw syscalls.
+//!
+//! Only what the read engine needs is implemented: queueing `IORING_OP_READ`
+//! requests, submitting them, and reaping completions. The struct layouts
+//! mirror `<linux/io_uring.h>`.
+
+use std::{
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    ptr,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+const IORING_OFF_SQ_RING: libc::off_t = 0;
+const IORING_OFF_CQ_RING: libc::off_t = 0x800_0000;
+const IORING_OFF_SQES: libc::off_t = 0x1000_0000;
+const IORING_FEAT_SINGLE_MMAP: u32 = 1;
+const IORING_ENTER_GETEVENTS: u32 = 1;
+const IORING_OP_READ: u8 = 22;
+
+#[repr(C)]
+#[derive(Default)]
+struct SqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    flags: u32,
+    dropped: u32,
+    array: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct CqringOffsets {
+    head: u32,
+    tail: u32,
+    ring_mask: u32,
+    ring_entries: u32,
+    overflow: u32,
+    cqes: u32,
+    flags: u32,
+    resv1: u32,
+    user_addr: u64,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct UringParams {
+    sq_entries: u32,
+    cq_entries: u32,
+    flags: u32,
+    sq_thread_cpu: u32,
+    sq_thread_idle: u32,
+    features: u32,
+    wq_fd: u32,
+    resv: [u32; 3],
+    sq_off: SqringOffsets,
+    cq_off: CqringOffsets,
+}
+
+#[repr(C)]
+struct Sqe {
+    opcode: u8,
+    flags: u8,
+    ioprio: u16,
+    fd: i32,
+    off: u64,
+    addr: u64,
+    len: u32,
+    rw_flags: u32,
+    user_data: u64,
+    buf_index: u16,
+    personality: u16,
+    splice_fd_in: i32,
+    addr3: u64,
+    pad: u64,
+}
+
+#[repr(C)]
+struct Cqe {
+    user_data: u64,
+    res: i32,
+    flags: u32,
+}
+
+/// A memory-mapped region shared with the kernel.
+struct Mmap {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> io::Result<Self> {
+        // SAFETY: mapping a region of the io_uring fd as documented in io_uring_setup(2).
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self { ptr, len })
+    }
+
+    /// Pointer to the value at a byte offset into the region.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        // SAFETY: offsets come from the kernel and lie within the mapping.
+        unsafe { self.ptr.cast::<u8>().add(offset as usize).cast() }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: the region was mapped by Mmap::new and is unmapped once.
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// A read request's completion: the `user_data` it was queued with and the
+/// number of bytes read (or the error).
+pub struct Completion {
+    pub user_data: u64,
+    pub result: io::Result<usize>,
+}
+
+pub struct Uring {
+    // Field order matters: the mappings must be dropped before the fd is closed.
+    sq_ring: Mmap,
+    cq_ring: Option<Mmap>,
+    sqes: Mmap,
+    fd: OwnedFd,
+    sq_mask: u32,
+    sq_entries: u32,
+    sq_off: SqringOffsets,
+    cq_mask: u32,
+    cq_off: CqringOffsets,
+    /// Requests queued but not yet passed to io_uring_enter.
+    to_submit: u32,
+}
+
+// The ring is only accessed through &mut self, from one thread at a time.
+unsafe impl Send for Uring {}
+
+impl Uring {
+    pub fn new(entries: u32) -> io::Result<Self> {
+        let mut params = UringParams::default();
+        // SAFETY: params is a valid io_uring_params struct for the kernel to fill in.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_io_uring_setup,
+                entries.max(1),
+                &mut params as *mut UringParams,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: io_uring_setup returned a new file descriptor that we now own.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len =
+            params.cq_off.cqes as usize + params.cq_entries as usize * std::mem::size_of::<Cqe>();
+        let single_mmap = params.features & IORING_FEAT_SINGLE_MMAP != 0;
+        let sq_ring = Mmap::new(
+            fd.as_raw_fd(),
+            if single_mmap {
+                sq_len.max(cq_len)
+            } else {
+                sq_len
+            },
+            IORING_OFF_SQ_RING,
+        )?;
+        let cq_ring = if single_mmap {
+            None
+        } else {
+            Some(Mmap::new(fd.as_raw_fd(), cq_len, IORING_OFF_CQ_RING)?)
+        };
+        let sqes = Mmap::new(
+            fd.as_raw_fd(),
+            params.sq_entries as usize * std::mem::size_of::<Sqe>(),
+            IORING_OFF_SQES,
+        )?;
+
+        // SAFETY: the mask fields are within the mapped rings.
+        let sq_mask = unsafe { *sq_ring.at::<u32>(params.sq_off.ring_mask) };
+        let cq_mask = unsafe {
+            *cq_ring
+                .as_ref()
+                .unwrap_or(&sq_ring)
+                .at::<u32>(params.cq_off.ring_mask)
+        };
+
+        Ok(Self {
+            sq_ring,
+            cq_ring,
+            sqes,
+            fd,
+            sq_mask,
+            sq_entries: params.sq_entries,
+            sq_off: params.sq_off,
+            cq_mask,
+            cq_off: params.cq_off,
+            to_submit: 0,
+        })
+    }
+
+    fn cq(&self) -> &Mmap {
+        self.cq_ring.as_ref().unwrap_or(&self.sq_ring)
+    }
+
+    fn sq_atomic(&self, offset: u32) -> &AtomicU32 {
+        // SAFETY: head and tail are aligned u32 fields shared with the kernel.
+        unsafe { &*self.sq_ring.at::<AtomicU32>(offset) }
+    }
+
+    fn cq_atomic(&self, offset: u32) -> &AtomicU32 {
+        // SAFETY: head and tail are aligned u32 fields shared with the kernel.
+        unsafe { &*self.cq().at::<AtomicU32>(offset) }
+    }
+
+    /// Queue a read of `buf.len()` bytes at `offset` in `fd`. Returns false if
+    /// the submission queue is full.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must stay valid and unused until the matching completion has
+    /// been reaped with [`Uring::pop_completion`].
+    pub unsafe fn push_read(
+        &mut self,
+        fd: RawFd,
+        buf: &mut [u8],
+        offset: u64,
+        user_data: u64,
+    ) -> bool {
+        let head = self.sq_atomic(self.sq_off.head).load(Ordering::Acquire);
+        let tail = self.sq_atomic(self.sq_off.tail).load(Ordering::Relaxed);
+        if tail.wrapping_sub(head) >= self.sq_entries {
+            return false;
+        }
+        let index = tail & self.sq_mask;
+        let sqe = Sqe {
+            opcode: IORING_OP_READ,
+            flags: 0,
+            ioprio: 0,
+            fd,
+            off: offset,
+            addr: buf.as_mut_ptr() as u64,
+            len: buf.len() as u32,
+            rw_flags: 0,
+            user_data,
+            buf_index: 0,
+            personality: 0,
+            splice_fd_in: 0,
+            addr3: 0,
+            pad: 0,
+        };
+        // SAFETY: index is masked to the number of SQEs in the mapped array.
+        unsafe {
+            self.sqes.at::<Sqe>(0).add(index as usize).write(sqe);
+            *self
+                .sq_ring
+                .at::<u32>(self.sq_off.array)
+                .add(index as usize) = index;
+        }
+        self.sq_atomic(self.sq_off.tail)
+            .store(tail.wrapping_add(1), Ordering::Release);
+        self.to_submit += 1;
+        true
+    }
+
+    /// Submit queued requests and wait until at least `min_complete` completions are available.
+    pub fn submit_and_wait(&mut self, min_complete: u32) -> io::Result<()> {
+        loop {
+            // SAFETY: io_uring_enter with no signal mask.
+            let ret = unsafe {
+                libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    self.fd.as_raw_fd(),
+                    self.to_submit,
+                    min_complete,
+                    IORING_ENTER_GETEVENTS,
+                    ptr::null::<libc::sigset_t>(),
+                    0,
+                )
+            };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err);
+            }
+            self.to_submit -= (ret as u32).min(self.to_submit);
+            return Ok(());
+        }
+    }
+
+    pub fn pop_completion(&mut self) -> Option<Completion> {
+        let head = self.cq_atomic(self.cq_off.head).load(Ordering::Relaxed);
+        let tail = self.cq_atomic(self.cq_off.tail).load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        let index = head & self.cq_mask;
+        // SAFETY: index is masked to the number of CQEs in the mapped ring.
+        let cqe = unsafe {
+            self.cq()
+                .at::<Cqe>(self.cq_off.cqes)
+                .add(index as usize)
+                .read()
+        };
+        self.cq_atomic(self.cq_off.head)
+            .store(head.wrapping_add(1), Ordering::Release);
+        let result = if cqe.res < 0 {
+            Err(io::Error::from_raw_os_error(-cqe.res))
+        } else {
+            Ok(cqe.res as usize)
+        };
+        Some(Completion {
+            user_data: cqe.user_data,
+            result,
+        })
+    }
+}
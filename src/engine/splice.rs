@@ -0,0 +1,120 @@
+//! splice() of file data through a pipe into /dev/null, so it is read into the
+//! page cache without ever being copied to user space.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    ptr,
+};
+
+use tracing::debug;
+
+use crate::checksum::Xxh3;
+
+use super::{count_read, IoEngine};
+
+pub struct SpliceEngine {
+    chunk: usize,
+    pipe_read: OwnedFd,
+    pipe_write: OwnedFd,
+    dev_null: File,
+}
+
+impl SpliceEngine {
+    pub fn new(chunk: usize) -> Result<Self, io::Error> {
+        let mut fds = [0; 2];
+        // SAFETY: pipe2 fills in two new file descriptors on success.
+        if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the descriptors were just created and are owned by nothing else.
+        let (pipe_read, pipe_write) =
+            unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+        // Each splice moves at most a pipe's worth, so make the pipe as big as a chunk.
+        let chunk_size = libc::c_int::try_from(chunk).unwrap_or(libc::c_int::MAX);
+        // SAFETY: F_SETPIPE_SZ takes an int; failure just leaves the default size.
+        let pipe_size =
+            unsafe { libc::fcntl(pipe_write.as_raw_fd(), libc::F_SETPIPE_SZ, chunk_size) };
+        let chunk = if pipe_size > 0 {
+            pipe_size as usize
+        } else {
+            // SAFETY: F_GETPIPE_SZ has no arguments.
+            let pipe_size = unsafe { libc::fcntl(pipe_write.as_raw_fd(), libc::F_GETPIPE_SZ) };
+            debug!("pipe could not be resized to {chunk} bytes, using {pipe_size}");
+            usize::try_from(pipe_size).unwrap_or(65536)
+        };
+        Ok(Self {
+            chunk,
+            pipe_read,
+            pipe_write,
+            dev_null: OpenOptions::new().write(true).open("/dev/null")?,
+        })
+    }
+
+    /// Move `len` bytes from `from` to `to`, one of which must be a pipe,
+    /// returning the number moved (0 at the end of a file).
+    fn splice(from: i32, to: i32, len: usize) -> Result<usize, io::Error> {
+        loop {
+            // SAFETY: null offsets make the kernel use and update the file offsets.
+            let n = unsafe {
+                libc::splice(
+                    from,
+                    ptr::null_mut(),
+                    to,
+                    ptr::null_mut(),
+                    len,
+                    libc::SPLICE_F_MOVE,
+                )
+            };
+            if n >= 0 {
+                return Ok(n as usize);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+}
+
+impl IoEngine for SpliceEngine {
+    /// Splice the listed size of `f` into the pipe and from there into
+    /// /dev/null, a chunk at a time.
+    fn read_contents(
+        &mut self,
+        f: &mut File,
+        size: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+        hasher: Option<&mut Xxh3>,
+    ) -> Result<(), io::Error> {
+        if hasher.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "spliced data never reaches user space to be hashed",
+            ));
+        }
+        while *bytes < size {
+            let want = (size - *bytes).min(self.chunk as u64) as usize;
+            let n = Self::splice(f.as_raw_fd(), self.pipe_write.as_raw_fd(), want)?;
+            if n == 0 {
+                debug!(
+                    "file must have been truncated, size was {size} but only read {}: {pathstr}",
+                    *bytes
+                );
+                break;
+            }
+            let mut drained = 0;
+            while drained < n {
+                drained += Self::splice(
+                    self.pipe_read.as_raw_fd(),
+                    self.dev_null.as_raw_fd(),
+                    n - drained,
+                )?;
+            }
+            count_read(bytes, n as u64);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Blocking `read()` loop, optionally on files opened with O_DIRECT.
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    os::fd::AsRawFd,
+    path::Path,
+    sync::atomic::Ordering,
+};
+
+use tracing::{debug, trace};
+
+use crate::checksum::Xxh3;
+
+use super::{
+    count_read, data_regions, open_for_read, AlignedBuf, IoEngine, Sparse, CACHE_HITS,
+    CACHE_MISSES, CACHE_UNPROBED, DIRECT_ALIGN,
+};
+
+pub struct SyncEngine {
+    direct: bool,
+    /// Probe each read with RWF_NOWAIT to count page cache hits.
+    probe: bool,
+    sparse: Option<Sparse>,
+    buf: AlignedBuf,
+}
+
+impl SyncEngine {
+    pub fn new(buf_size: usize, direct: bool, probe: bool, sparse: Option<Sparse>) -> Self {
+        Self {
+            direct,
+            probe,
+            sparse,
+            buf: AlignedBuf::new(buf_size),
+        }
+    }
+
+    /// Read only the given data regions of `f`, seeking over the holes.
+    fn read_regions(
+        &mut self,
+        f: &mut File,
+        regions: &[(u64, u64)],
+        pathstr: &str,
+        bytes: &mut u64,
+    ) -> Result<(), io::Error> {
+        for &(start, end) in regions {
+            f.seek(SeekFrom::Start(start))?;
+            let mut offset = start;
+            while offset < end {
+                let mut want = (end - offset).min(self.buf.len() as u64) as usize;
+                if self.direct {
+                    // Data regions start on block boundaries, but may end mid-block at EOF.
+                    want = want.next_multiple_of(DIRECT_ALIGN).min(self.buf.len());
+                }
+                let n = self.read_chunk(f, want)?;
+                if n == 0 {
+                    debug!("file must have been truncated, got an empty read at {offset} of {end}: {pathstr}");
+                    return Ok(());
+                }
+                let n = (n as u64).min(end - offset);
+                count_read(bytes, n);
+                offset += n;
+            }
+            trace!("read data region {start}..{end} of: {pathstr}");
+        }
+        Ok(())
+    }
+
+    /// Read the next chunk of up to `len` bytes of `f` into the buffer. When
+    /// probing, the read is first tried without waiting for I/O: if any of the
+    /// data is cached the read is a hit, and otherwise it is made normally.
+    fn read_chunk(&mut self, f: &mut File, len: usize) -> Result<usize, io::Error> {
+        if !self.probe {
+            return f.read(&mut self.buf[..len]);
+        }
+        let iov = libc::iovec {
+            iov_base: self.buf.as_mut_ptr().cast(),
+            iov_len: len,
+        };
+        // SAFETY: iov describes len bytes of the buffer; offset -1 reads at,
+        // and advances, the file position, like read().
+        let n = unsafe { libc::preadv2(f.as_raw_fd(), &iov, 1, -1, libc::RWF_NOWAIT) };
+        if n > 0 {
+            CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+            return Ok(n as usize);
+        }
+        if n == 0 {
+            return Ok(0);
+        }
+        let err = io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EAGAIN) => CACHE_MISSES.fetch_add(1, Ordering::Relaxed),
+            Some(libc::EOPNOTSUPP) => CACHE_UNPROBED.fetch_add(1, Ordering::Relaxed),
+            _ => return Err(err),
+        };
+        f.read(&mut self.buf[..len])
+    }
+}
+
+impl IoEngine for SyncEngine {
+    fn open(&self, path: &Path) -> Result<File, io::Error> {
+        open_for_read(path, self.direct)
+    }
+
+    fn read_contents(
+        &mut self,
+        f: &mut File,
+        size: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+        mut hasher: Option<&mut Xxh3>,
+    ) -> Result<(), io::Error> {
+        if let Some(sparse) = self.sparse {
+            let regions = data_regions(f, size)?;
+            if sparse == Sparse::Skip {
+                return self.read_regions(f, &regions, pathstr, bytes);
+            }
+        }
+        let mb = size as f64 / 1e6;
+        loop {
+            let n = self.read_chunk(f, self.buf.len())?;
+            if n == 0 {
+                if *bytes != size {
+                    debug!("file must have been truncated, size was {size} but only read {} before getting empty read: {pathstr}", *bytes);
+                }
+                break;
+            }
+            count_read(bytes, n as u64);
+            if let Some(hasher) = hasher.as_deref_mut() {
+                hasher.update(&self.buf[..n]);
+            }
+            trace!(
+                "read chunk of {n} bytes ({:.1}% of {mb:.3} MB) from: {pathstr}",
+                100.0 * *bytes as f64 / size as f64,
+            );
+            if *bytes > size {
+                debug!("file must have been extended, size was {size} but we've read {}, stopping to avoid unbounded reading: {pathstr}", *bytes);
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_head(
+        &mut self,
+        f: &mut File,
+        len: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+    ) -> Result<(), io::Error> {
+        while *bytes < len {
+            let mut want = (len - *bytes).min(self.buf.len() as u64) as usize;
+            if self.direct {
+                // O_DIRECT needs whole blocks, so this may read a little past the head.
+                want = want.next_multiple_of(DIRECT_ALIGN).min(self.buf.len());
+            }
+            let n = self.read_chunk(f, want)?;
+            if n == 0 {
+                break;
+            }
+            count_read(bytes, n as u64);
+            trace!("read chunk of {n} bytes of the first {len} from: {pathstr}");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,140 @@
+//! io_uring reads, keeping several chunks of each file in flight at once.
+
+use std::{fs::File, io, os::fd::AsRawFd, path::Path};
+
+use tracing::debug;
+
+use crate::checksum::Xxh3;
+
+use super::{count_read, open_for_read, ring::Uring, AlignedBuf, IoEngine, DIRECT_ALIGN};
+
+/// An io_uring ring and the buffers for its in-flight reads.
+pub struct UringEngine {
+    ring: Uring,
+    buffers: Vec<AlignedBuf>,
+    buf_size: usize,
+    direct: bool,
+}
+
+impl UringEngine {
+    pub fn new(queue_depth: u32, buf_size: usize, direct: bool) -> Result<Self, io::Error> {
+        let queue_depth = queue_depth.max(1);
+        Ok(Self {
+            ring: Uring::new(queue_depth)?,
+            buffers: (0..queue_depth)
+                .map(|_| AlignedBuf::new(buf_size))
+                .collect(),
+            buf_size,
+            direct,
+        })
+    }
+}
+
+impl IoEngine for UringEngine {
+    fn open(&self, path: &Path) -> Result<File, io::Error> {
+        open_for_read(path, self.direct)
+    }
+
+    /// Read a file by keeping up to one chunk per buffer in flight, each at
+    /// its own offset. Only the listed size is read: unlike the sync engine,
+    /// reads are issued by offset so growth after listing is not observed.
+    fn read_contents(
+        &mut self,
+        f: &mut File,
+        size: u64,
+        pathstr: &str,
+        bytes: &mut u64,
+        hasher: Option<&mut Xxh3>,
+    ) -> Result<(), io::Error> {
+        if hasher.is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "checksums need the data in order",
+            ));
+        }
+        let fd = f.as_raw_fd();
+        let chunk = self.buf_size as u64;
+        let mut next_offset = 0;
+        let mut free = (0..self.buffers.len()).collect::<Vec<_>>();
+        // The offset and length of the read in flight in each buffer, and the
+        // buffers whose read came back short, to be resubmitted for the rest.
+        let mut reads = vec![(0, 0); self.buffers.len()];
+        let mut short = Vec::new();
+        let mut in_flight = 0;
+        let mut first_error = None;
+        let mut truncated = false;
+
+        loop {
+            while first_error.is_none() && !truncated {
+                let slot = match short.pop() {
+                    Some(slot) => slot,
+                    None if next_offset < size => {
+                        let Some(slot) = free.pop() else { break };
+                        let mut len = (size - next_offset).min(chunk) as usize;
+                        if self.direct {
+                            // O_DIRECT needs whole blocks, so the final read may ask for more than remains.
+                            len = len.next_multiple_of(DIRECT_ALIGN);
+                        }
+                        reads[slot] = (next_offset, len);
+                        next_offset += len as u64;
+                        slot
+                    }
+                    None => break,
+                };
+                let (offset, len) = reads[slot];
+                let buf = &mut self.buffers[slot][..len];
+                // SAFETY: the buffer is not touched again until its completion is reaped below.
+                if !unsafe { self.ring.push_read(fd, buf, offset, slot as u64) } {
+                    short.push(slot);
+                    break;
+                }
+                in_flight += 1;
+            }
+            if in_flight == 0 {
+                break;
+            }
+            // Buffers are in use by the kernel, so keep reaping after a read error.
+            self.ring.submit_and_wait(1)?;
+            while let Some(completion) = self.ring.pop_completion() {
+                in_flight -= 1;
+                let slot = completion.user_data as usize;
+                let (offset, len) = reads[slot];
+                match completion.result {
+                    Ok(0) => {
+                        truncated = true;
+                        free.push(slot);
+                    }
+                    Ok(n) => {
+                        count_read(bytes, n as u64);
+                        let wanted = (size.saturating_sub(offset) as usize).min(len);
+                        if n >= wanted {
+                            free.push(slot);
+                        } else if self.direct && !n.is_multiple_of(DIRECT_ALIGN) {
+                            // A direct read only stops inside a block at the end of the file.
+                            truncated = true;
+                            free.push(slot);
+                        } else {
+                            reads[slot] = (offset + n as u64, len - n);
+                            short.push(slot);
+                        }
+                    }
+                    Err(err) => {
+                        first_error.get_or_insert(err);
+                        free.push(slot);
+                    }
+                }
+            }
+        }
+
+        if let Some(err) = first_error {
+            return Err(err);
+        }
+        if *bytes != size {
+            debug!(
+                "file must have been truncated, size was {size} but only read {}: {pathstr}",
+                *bytes
+            );
+        }
+        Ok(())
+    }
+}
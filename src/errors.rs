@@ -0,0 +1,87 @@
+//! Read errors grouped by kind, so the files that failed and deflated the
+//! throughput show up in the summary rather than only in the debug log.
+
+use std::{collections::HashMap, io, path::PathBuf};
+
+use crate::output::report;
+
+/// How many paths to keep as examples of each kind of error.
+const EXAMPLES: usize = 3;
+
+#[derive(Debug, Default)]
+struct KindStats {
+    count: u64,
+    examples: Vec<PathBuf>,
+}
+
+/// The files that couldn't be read, by kind of error.
+#[derive(Debug, Default)]
+pub struct ErrorSummary {
+    kinds: HashMap<String, KindStats>,
+}
+
+impl ErrorSummary {
+    /// The error reading a single file.
+    pub fn one(err: &io::Error, path: PathBuf) -> Self {
+        let stats = KindStats {
+            count: 1,
+            examples: vec![path],
+        };
+        Self {
+            kinds: HashMap::from([(kind_name(err), stats)]),
+        }
+    }
+
+    pub fn combine(mut self, other: Self) -> Self {
+        if self.kinds.len() < other.kinds.len() {
+            return other.combine(self);
+        }
+        for (kind, other) in other.kinds {
+            let stats = self.kinds.entry(kind).or_default();
+            stats.count += other.count;
+            let room = EXAMPLES - stats.examples.len().min(EXAMPLES);
+            stats.examples.extend(other.examples.into_iter().take(room));
+        }
+        self
+    }
+
+    /// Print a line per kind of error, most common first, with some of the
+    /// files it happened to.
+    pub fn print(&self) {
+        if self.kinds.is_empty() {
+            return;
+        }
+        let total = self.kinds.values().map(|stats| stats.count).sum::<u64>();
+        let mut kinds = self.kinds.iter().collect::<Vec<_>>();
+        kinds.sort_by(|a, b| b.1.count.cmp(&a.1.count).then_with(|| a.0.cmp(b.0)));
+        report!("-- errors: {total} files couldn't be read:");
+        for (kind, stats) in kinds {
+            let mut examples = stats
+                .examples
+                .iter()
+                .map(|path| path.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(", ");
+            if stats.count > stats.examples.len() as u64 {
+                examples.push_str(", ...");
+            }
+            report!("   {:>9}  {kind}: {examples}", stats.count);
+        }
+    }
+}
+
+/// What to call an error: the system's description of its errno, which tells
+/// apart errors such as EIO and ESTALE that io::ErrorKind may not, or the
+/// kind of error if it didn't come from the system.
+fn kind_name(err: &io::Error) -> String {
+    match err.raw_os_error() {
+        Some(code) => {
+            let message = io::Error::from_raw_os_error(code).to_string();
+            match message.rfind(" (os error ") {
+                Some(suffix) => message[..suffix].to_string(),
+                None => message,
+            }
+        }
+        None => err.kind().to_string(),
+    }
+}
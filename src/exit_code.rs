@@ -0,0 +1,16 @@
+//! Exit statuses for automation to tell apart why a run failed. Any error
+//! that stops a run exits with 1, and clap exits with 2 for an invalid
+//! command line.
+
+/// The run finished, but some files couldn't be read (--fail-on-error).
+pub const READ_ERRORS: i32 = 3;
+
+/// The run was aborted by --max-errors.
+pub const TOO_MANY_ERRORS: i32 = 4;
+
+/// Throughput fell further below the baseline than --max-regression allows.
+pub const REGRESSION: i32 = 5;
+
+/// The run was stopped with Ctrl-C, after recording its partial results; the
+/// usual status of a process killed by SIGINT.
+pub const INTERRUPTED: i32 = 130;
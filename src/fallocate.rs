@@ -0,0 +1,156 @@
+//! Preallocation benchmark: create large files with fallocate() or
+//! ftruncate(), without writing any data.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io,
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::{Args, ValueEnum};
+use tracing::error;
+
+use crate::{
+    histogram::Histogram,
+    output::print_latency,
+    units::{format_size, parse_size},
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocMethod {
+    /// fallocate() with no flags: allocate blocks, reading back as zeros.
+    Fallocate,
+    /// fallocate() with FALLOC_FL_ZERO_RANGE, which some filesystems zero eagerly.
+    ZeroRange,
+    /// ftruncate() to the size, leaving a sparse file with no blocks allocated.
+    Ftruncate,
+}
+
+impl AllocMethod {
+    fn name(self) -> &'static str {
+        match self {
+            AllocMethod::Fallocate => "fallocate",
+            AllocMethod::ZeroRange => "zero-range",
+            AllocMethod::Ftruncate => "ftruncate",
+        }
+    }
+
+    fn apply(self, f: &File, len: u64) -> Result<(), io::Error> {
+        let len =
+            libc::off_t::try_from(len).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        let fd = f.as_raw_fd();
+        // SAFETY: fd is an open file, and these calls return -1 with errno on failure.
+        let result = unsafe {
+            match self {
+                AllocMethod::Fallocate => libc::fallocate(fd, 0, 0, len),
+                AllocMethod::ZeroRange => libc::fallocate(fd, libc::FALLOC_FL_ZERO_RANGE, 0, len),
+                AllocMethod::Ftruncate => libc::ftruncate(fd, len),
+            }
+        };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+/// Options for the `fallocate` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct FallocateArgs {
+    /// Existing directory to create the test files in; they are removed afterwards.
+    pub dir: PathBuf,
+    /// Number of files to create with each method.
+    #[arg(short = 'n', long, default_value_t = 10)]
+    pub files: u64,
+    /// Size of each file (e.g. 1G).
+    #[arg(short, long, default_value = "1G", value_parser = parse_size)]
+    pub size: u64,
+    /// Methods to compare, as a comma-separated list.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "fallocate,zero-range,ftruncate"
+    )]
+    pub method: Vec<AllocMethod>,
+}
+
+pub fn fallocate(options: &FallocateArgs) {
+    println!(
+        "-- creating {} files of {} in {:?} with each of: {}",
+        options.files,
+        format_size(options.size),
+        options.dir,
+        options
+            .method
+            .iter()
+            .map(|method| method.name())
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    for &method in &options.method {
+        let paths = (0..options.files)
+            .map(|i| options.dir.join(format!("iobench-{}-{i}", method.name())))
+            .collect::<Vec<_>>();
+        let mut latency = Histogram::default();
+        let t1 = Instant::now();
+        let mut result = Ok(());
+        for path in &paths {
+            let t_file = Instant::now();
+            result = preallocate(path, method, options.size);
+            if result.is_err() {
+                break;
+            }
+            latency.record(t_file.elapsed().as_nanos() as u64);
+        }
+        let dur_s = t1.elapsed().as_secs_f64();
+        // Only the files created are removed: the one that failed was removed
+        // already, or existed before.
+        let created = &paths[..latency.count() as usize];
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                remove_files(created);
+                error!(
+                    "{} exists already; remove it or use another directory",
+                    paths[created.len()].to_string_lossy()
+                );
+                std::process::exit(1);
+            }
+            Ok(()) => {
+                let created = latency.count();
+                println!(
+                    "-- {}: {:.0} files/s   {:.1} GB/s  ({created} files in {dur_s} s)",
+                    method.name(),
+                    created as f64 / dur_s,
+                    (created * options.size) as f64 / 1e9 / dur_s,
+                );
+                print_latency(method.name(), &latency);
+            }
+            Err(err) => println!("-- {}: failed: {err}", method.name()),
+        }
+        remove_files(created);
+    }
+}
+
+/// Create a file, which mustn't exist, and preallocate it to `len` bytes,
+/// removing it again if that fails.
+fn preallocate(path: &Path, method: AllocMethod, len: u64) -> Result<(), io::Error> {
+    let f = OpenOptions::new().write(true).create_new(true).open(path)?;
+    method.apply(&f, len).inspect_err(|_| {
+        let _ = fs::remove_file(path);
+    })
+}
+
+fn remove_files(paths: &[PathBuf]) {
+    for path in paths {
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => {
+                error!("error removing {}: {err}", path.to_string_lossy());
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! File lists saved by read-tree `--save-list` and loaded by `--load-list`,
+//! so a big tree is listed once and then read in many experiments, and the
+//! lists of paths read by read-list.
+//!
+//! A list is a text file with a path per line, after a header naming the
+//! directories that were listed. Paths are written as the bytes the
+//! filesystem gave, so they needn't be UTF-8, but they can't contain a line
+//! break.
+
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, BufRead, BufWriter, Write},
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use crate::walk::ListedFile;
+
+const HEADER: &[u8] = b"# iobench file list\n";
+const ROOT: &[u8] = b"# listed: ";
+
+/// The files listed under some directories.
+pub struct FileList {
+    pub dirs: Vec<PathBuf>,
+    pub files: Vec<ListedFile>,
+}
+
+/// Write the files listed under `dirs` to `path`, replacing it atomically.
+pub fn save(path: &Path, dirs: &[PathBuf], files: &[ListedFile]) -> Result<(), io::Error> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    out.write_all(HEADER)?;
+    for dir in dirs {
+        out.write_all(ROOT)?;
+        write_line(&mut out, dir)?;
+    }
+    for file in files {
+        write_line(&mut out, &file.path)?;
+    }
+    out.flush()?;
+    fs::rename(&tmp, path)
+}
+
+fn write_line(out: &mut impl Write, path: &Path) -> Result<(), io::Error> {
+    let bytes = path.as_os_str().as_bytes();
+    if bytes.contains(&b'\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("can't save a path with a line break in it: {path:?}"),
+        ));
+    }
+    out.write_all(bytes)?;
+    out.write_all(b"\n")
+}
+
+/// Read the paths in `input`, each ended by `end`, skipping empty ones.
+pub fn read_paths(input: impl BufRead, end: u8) -> Result<Vec<ListedFile>, io::Error> {
+    let mut files = Vec::new();
+    for line in input.split(end) {
+        let line = line?;
+        if !line.is_empty() {
+            files.push(ListedFile::named(PathBuf::from(OsStr::from_bytes(&line))));
+        }
+    }
+    Ok(files)
+}
+
+/// Read a list saved by `save`.
+pub fn load(path: &Path) -> Result<FileList, String> {
+    let contents = fs::read(path).map_err(|err| err.to_string())?;
+    let body = contents
+        .strip_prefix(HEADER)
+        .ok_or("not a file list saved by --save-list")?;
+    let mut dirs = Vec::new();
+    let mut files = Vec::new();
+    for line in body.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+        let line = Path::new(OsStr::from_bytes(line));
+        if files.is_empty() {
+            if let Some(dir) = line.as_os_str().as_bytes().strip_prefix(ROOT) {
+                dirs.push(PathBuf::from(OsStr::from_bytes(dir)));
+                continue;
+            }
+        }
+        let depth = dirs
+            .iter()
+            .find_map(|dir| line.strip_prefix(dir).ok())
+            .map(|relative| relative.components().count())
+            .ok_or_else(|| format!("{line:?} isn't under any of the directories listed"))?;
+        files.push(ListedFile {
+            path: line.to_path_buf(),
+            depth,
+        });
+    }
+    if dirs.is_empty() {
+        return Err("no directories listed".to_string());
+    }
+    Ok(FileList { dirs, files })
+}
@@ -0,0 +1,114 @@
+//! Sync latency benchmark: append small blocks to a file, syncing after each,
+//! as a database does when committing to its log.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::Instant,
+};
+
+use clap::{Args, ValueEnum};
+use tracing::error;
+
+use crate::{
+    histogram::Histogram,
+    output::print_latency,
+    rng::Rng,
+    units::{format_size, parse_size},
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SyncCall {
+    /// Flush data and all metadata.
+    #[default]
+    Fsync,
+    /// Flush data and only the metadata needed to read it back.
+    Fdatasync,
+}
+
+impl SyncCall {
+    pub fn name(self) -> &'static str {
+        match self {
+            SyncCall::Fsync => "fsync",
+            SyncCall::Fdatasync => "fdatasync",
+        }
+    }
+}
+
+/// Options for the `fsync` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct FsyncArgs {
+    /// Test file to create, which mustn't exist, so no file is overwritten; it
+    /// is removed afterwards unless --keep is given.
+    pub file: PathBuf,
+    /// Size of each write (e.g. 512, 4k).
+    #[arg(short, long, default_value = "4k", value_parser = parse_size)]
+    pub block_size: u64,
+    /// Number of write and sync pairs to perform.
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    pub ops: u64,
+    /// Which sync call to make after each write.
+    #[arg(long, value_enum, default_value_t = SyncCall::Fsync)]
+    pub sync: SyncCall,
+    /// Keep the test file instead of removing it.
+    #[arg(long)]
+    pub keep: bool,
+}
+
+pub fn fsync(options: &FsyncArgs) {
+    let path = &options.file;
+    println!(
+        "-- writing {} blocks of {} to {path:?}, calling {} after each",
+        options.ops,
+        format_size(options.block_size),
+        options.sync.name(),
+    );
+    let mut f = match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(f) => f,
+        Err(err) => {
+            error!("error creating {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    };
+    let mut data = vec![0; options.block_size.max(1) as usize];
+    Rng::from_time().fill_bytes(&mut data);
+
+    let mut write_latency = Histogram::default();
+    let mut sync_latency = Histogram::default();
+    let t1 = Instant::now();
+    for _ in 0..options.ops {
+        let t_write = Instant::now();
+        let result = f.write_all(&data).and_then(|()| {
+            let t_sync = Instant::now();
+            write_latency.record((t_sync - t_write).as_nanos() as u64);
+            let result = match options.sync {
+                SyncCall::Fsync => f.sync_all(),
+                SyncCall::Fdatasync => f.sync_data(),
+            };
+            sync_latency.record(t_sync.elapsed().as_nanos() as u64);
+            result
+        });
+        if let Err(err) = result {
+            error!("error writing {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- {}: {:.0} syncs/s  ({} syncs in {dur_s} s, avg {:.1} us)",
+        options.sync.name(),
+        options.ops as f64 / dur_s,
+        options.ops,
+        sync_latency.mean() / 1e3,
+    );
+    print_latency("write", &write_latency);
+    print_latency("sync", &sync_latency);
+
+    drop(f);
+    if !options.keep {
+        if let Err(err) = fs::remove_file(path) {
+            error!("error removing {}: {err}", path.to_string_lossy());
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Shell-style glob patterns for filtering the paths a walk yields.
+
+use regex::Regex;
+
+/// A glob such as `*.o`, `src/**/*.rs` or `target/**`.
+///
+/// `*` and `?` never match a `/`, `**` matches any number of whole path
+/// components, and `[...]` (or `[!...]` to negate) matches one character from a
+/// set. A pattern without a `/` is matched against the file name alone; one
+/// with a `/` is matched against the whole path relative to the walked
+/// directory.
+#[derive(Debug, Clone)]
+pub struct Glob {
+    regex: Regex,
+    match_path: bool,
+}
+
+impl Glob {
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let match_path = pattern.contains('/');
+        let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+        let chars = pattern.chars().collect::<Vec<_>>();
+        let mut re = String::from("^");
+        let mut i = 0;
+        while i < chars.len() {
+            match chars[i] {
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    i += 2;
+                    if chars.get(i) == Some(&'/') {
+                        re.push_str("(?:.*/)?");
+                        i += 1;
+                    } else {
+                        re.push_str(".*");
+                    }
+                    continue;
+                }
+                // A trailing `/**` also matches the directory itself, so it is pruned whole.
+                '/' if chars[i + 1..] == ['*', '*'] => {
+                    re.push_str("(?:/.*)?");
+                    break;
+                }
+                '*' => re.push_str("[^/]*"),
+                '?' => re.push_str("[^/]"),
+                '[' => {
+                    let Some(len) = chars[i + 1..].iter().skip(1).position(|&c| c == ']') else {
+                        return Err(format!("unclosed '[' in pattern: {pattern:?}"));
+                    };
+                    let class = &chars[i + 1..i + 2 + len];
+                    re.push('[');
+                    let class = match class.split_first() {
+                        Some(('!', rest)) => {
+                            re.push('^');
+                            rest
+                        }
+                        _ => class,
+                    };
+                    for &c in class {
+                        if matches!(c, '\\' | '[' | '&' | '~') {
+                            re.push('\\');
+                        }
+                        re.push(c);
+                    }
+                    re.push(']');
+                    i += len + 2;
+                }
+                c => re.push_str(&regex::escape(c.encode_utf8(&mut [0; 4]))),
+            }
+            i += 1;
+        }
+        re.push('$');
+        let regex = Regex::new(&re).map_err(|err| format!("invalid pattern {pattern:?}: {err}"))?;
+        Ok(Self { regex, match_path })
+    }
+
+    /// Whether an entry with this path (relative to the walk root) and file name matches.
+    pub fn matches(&self, relative_path: &str, file_name: &str) -> bool {
+        if self.match_path {
+            self.regex.is_match(relative_path)
+        } else {
+            self.regex.is_match(file_name)
+        }
+    }
+}
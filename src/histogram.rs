@@ -0,0 +1,128 @@
+//! Log-linear histogram for latency values, in the style of HdrHistogram.
+//!
+//! Values below 256 are counted exactly. Larger values are grouped into
+//! buckets that each cover 1/128th of a power of two, so a recorded value is
+//! never reported with more than 0.8% relative error. Buckets are allocated
+//! lazily, which keeps empty histograms free to create and merge.
+
+const SUB_BUCKET_BITS: u32 = 7;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+/// Values below this are stored in their own bucket.
+const EXACT_LIMIT: u64 = SUB_BUCKETS * 2;
+
+#[derive(Debug, Clone, Default)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    total: u64,
+    sum: u128,
+    min: u64,
+    max: u64,
+}
+
+fn bucket_index(value: u64) -> usize {
+    if value < EXACT_LIMIT {
+        return value as usize;
+    }
+    let shift = (64 - value.leading_zeros()) - (SUB_BUCKET_BITS + 1);
+    let top = value >> shift;
+    (EXACT_LIMIT + u64::from(shift - 1) * SUB_BUCKETS + (top - SUB_BUCKETS)) as usize
+}
+
+/// The highest value that falls into the bucket with the given index.
+fn bucket_high(index: usize) -> u64 {
+    let index = index as u64;
+    if index < EXACT_LIMIT {
+        return index;
+    }
+    let shift = (index - EXACT_LIMIT) / SUB_BUCKETS + 1;
+    let top = (index - EXACT_LIMIT) % SUB_BUCKETS + SUB_BUCKETS;
+    ((top + 1) << shift) - 1
+}
+
+impl Histogram {
+    pub fn record(&mut self, value: u64) {
+        let index = bucket_index(value);
+        if index >= self.counts.len() {
+            self.counts.resize(index + 1, 0);
+        }
+        self.counts[index] += 1;
+        if self.total == 0 || value < self.min {
+            self.min = value;
+        }
+        self.max = self.max.max(value);
+        self.total += 1;
+        self.sum += u128::from(value);
+    }
+
+    pub fn merge(&mut self, other: &Self) {
+        if other.total == 0 {
+            return;
+        }
+        if other.counts.len() > self.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other_count) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other_count;
+        }
+        if self.total == 0 || other.min < self.min {
+            self.min = other.min;
+        }
+        self.max = self.max.max(other.max);
+        self.total += other.total;
+        self.sum += other.sum;
+    }
+
+    pub fn count(&self) -> u64 {
+        self.total
+    }
+
+    pub fn min(&self) -> u64 {
+        self.min
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+        self.sum as f64 / self.total as f64
+    }
+
+    /// Number of values in each power-of-two range: element `i` counts the
+    /// values from 2^i to 2^(i+1) - 1, and the first also counts zeros.
+    pub fn power_of_two_counts(&self) -> Vec<u64> {
+        let mut counts = Vec::new();
+        for (index, &count) in self.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            // A bucket never straddles a power of two, so its top decides.
+            let bin = bucket_high(index).max(1).ilog2() as usize;
+            if bin >= counts.len() {
+                counts.resize(bin + 1, 0);
+            }
+            counts[bin] += count;
+        }
+        counts
+    }
+
+    /// Value at the given percentile (0-100). The result is the upper bound of
+    /// the bucket containing that rank, clamped to the recorded maximum.
+    pub fn percentile(&self, percentile: f64) -> u64 {
+        if self.total == 0 {
+            return 0;
+        }
+        let rank = ((percentile / 100.0) * self.total as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return bucket_high(index).min(self.max);
+            }
+        }
+        self.max
+    }
+}
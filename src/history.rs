@@ -0,0 +1,240 @@
+//! Past runs from the results database (`history`), with the trend of their
+//! throughput, so a target that is slowly getting slower shows up before it
+//! becomes a problem.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use tracing::error;
+
+use crate::{
+    results_db::{self, StoredRun},
+    stats::Summary,
+    tui::sparkline,
+    units::{format_latency, format_size},
+};
+
+/// Runs before each one that its throughput is checked against.
+const WINDOW: usize = 10;
+
+/// Fewest runs before one for it to be checked.
+const MIN_PREVIOUS: usize = 5;
+
+/// Smallest change from the runs before that is flagged, however steady they
+/// were, so the noise of a very steady target isn't.
+const MIN_ANOMALY_CHANGE: f64 = 0.05;
+
+/// Most columns in the trend sparkline.
+const TREND_WIDTH: usize = 60;
+
+/// Options for the `history` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct HistoryArgs {
+    /// Results database written by read-tree --db.
+    #[arg(long, value_name = "FILE")]
+    pub db: PathBuf,
+    /// Only show runs that read this path.
+    #[arg(long)]
+    pub path: Option<PathBuf>,
+    /// Only show runs on this host.
+    #[arg(long)]
+    pub host: Option<String>,
+    /// List at most this many of the latest runs of each configuration; the
+    /// trend is still of them all.
+    #[arg(long, default_value_t = 20)]
+    pub last: usize,
+    /// Flag runs whose throughput is this many standard deviations from that
+    /// of the runs before them.
+    #[arg(long, value_name = "STDDEVS", default_value_t = 3.0)]
+    pub anomaly: f64,
+}
+
+pub fn history(options: &HistoryArgs) {
+    if !options.db.exists() {
+        error!("no database at {}", options.db.to_string_lossy());
+        std::process::exit(1);
+    }
+    let runs = match results_db::load_runs(
+        &options.db,
+        options.path.as_deref(),
+        options.host.as_deref(),
+    ) {
+        Ok(runs) => runs,
+        Err(err) => {
+            error!(
+                "error reading database {}: {err}",
+                options.db.to_string_lossy()
+            );
+            std::process::exit(1);
+        }
+    };
+    if runs.is_empty() {
+        println!("-- no runs found");
+        return;
+    }
+
+    // Each target and configuration run, in the order first run.
+    let mut configs = Vec::new();
+    for run in &runs {
+        let config = config(run);
+        if !configs.contains(&config) {
+            configs.push(config);
+        }
+    }
+    for key in configs {
+        let runs = runs
+            .iter()
+            .filter(|run| config(run) == key)
+            .collect::<Vec<_>>();
+        let (paths, host, engine, threads, buf_size) = key;
+        let (first, last) = (
+            date(&runs[0].timestamp),
+            date(&runs[runs.len() - 1].timestamp),
+        );
+        println!(
+            "-- {paths} on {host}: {threads} threads, {} buffer, {engine} engine: {}",
+            format_size(buf_size as u64),
+            match runs.len() {
+                1 => format!("1 run on {first}"),
+                count => format!("{count} runs from {first} to {last}"),
+            }
+        );
+        let anomalies = anomalies(&runs, options.anomaly);
+        let hidden = runs.len().saturating_sub(options.last);
+        if hidden > 0 {
+            println!("   ({hidden} earlier runs not listed)");
+        }
+        for (run, anomaly) in runs.iter().zip(&anomalies).skip(hidden) {
+            let errors = if run.errors > 0 {
+                format!("  ({} errors)", run.errors)
+            } else {
+                String::new()
+            };
+            let line = format!(
+                "   {}  {:>10} MB/s  {:>9} files/s  p99 {:>9}  {:>9} files{errors}  {}",
+                minute(&run.timestamp),
+                run.mb_per_s.map_or("-".to_string(), |v| format!("{v:.1}")),
+                run.files_per_s
+                    .map_or("-".to_string(), |v| format!("{v:.0}")),
+                run.p99_latency_ns
+                    .map_or("-".to_string(), |ns| format_latency(ns as u64)),
+                run.files,
+                anomaly.as_deref().unwrap_or_default(),
+            );
+            println!("{}", line.trim_end());
+        }
+        print_trend(&runs);
+    }
+}
+
+/// What runs are grouped by: paths, host, engine, threads and buffer size.
+type Config = (String, String, String, i64, i64);
+
+fn config(run: &StoredRun) -> Config {
+    (
+        run.paths.clone(),
+        run.host.clone(),
+        run.engine.clone(),
+        run.threads,
+        run.buf_size,
+    )
+}
+
+/// For each run, a note if its throughput is at least `stddevs` standard
+/// deviations from the mean of the runs before it.
+fn anomalies(runs: &[&StoredRun], stddevs: f64) -> Vec<Option<String>> {
+    (0..runs.len())
+        .map(|i| {
+            let value = runs[i].mb_per_s?;
+            let previous = runs[i.saturating_sub(WINDOW)..i]
+                .iter()
+                .filter_map(|run| run.mb_per_s)
+                .collect::<Vec<_>>();
+            if previous.len() < MIN_PREVIOUS {
+                return None;
+            }
+            let summary = Summary::of(&previous);
+            let deviation = value - summary.mean;
+            let change = deviation / summary.mean;
+            if summary.stddev == 0.0
+                || deviation.abs() < stddevs * summary.stddev
+                || change.abs() < MIN_ANOMALY_CHANGE
+            {
+                return None;
+            }
+            Some(format!(
+                "<- {:+.0}%, {:.1} stddevs {} the {} runs before",
+                100.0 * change,
+                deviation.abs() / summary.stddev,
+                if deviation < 0.0 { "below" } else { "above" },
+                previous.len(),
+            ))
+        })
+        .collect()
+}
+
+/// Print the throughput of the runs as a sparkline, with neighbouring runs
+/// averaged to fit and scaled from the slowest to the fastest so a gradual
+/// change shows, and its change over time as fitted by least squares, as a
+/// rate per 30 days.
+fn print_trend(runs: &[&StoredRun]) {
+    let points = runs
+        .iter()
+        .filter_map(|run| Some((run.day, run.mb_per_s?)))
+        .collect::<Vec<_>>();
+    if points.len() < 2 {
+        return;
+    }
+    let values = points.iter().map(|&(_, value)| value).collect::<Vec<_>>();
+    let mean = Summary::of(&values).mean;
+    let days = points.iter().map(|&(day, _)| day).collect::<Vec<_>>();
+    let mean_day = Summary::of(&days).mean;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for &(day, value) in &points {
+        covariance += (day - mean_day) * (value - mean);
+        variance += (day - mean_day).powi(2);
+    }
+    let span = days[days.len() - 1] - days[0];
+    let slope = if span >= 1.0 {
+        let per_month = 30.0 * covariance / variance;
+        format!(
+            ", {per_month:+.1} MB/s per 30 days ({:+.1}%)",
+            100.0 * per_month / mean
+        )
+    } else {
+        String::new()
+    };
+    let per_column = values.len().div_ceil(TREND_WIDTH);
+    let columns = values
+        .chunks(per_column)
+        .map(|chunk| chunk.iter().sum::<f64>() / chunk.len() as f64)
+        .collect::<Vec<_>>();
+    let summary = Summary::of(&columns);
+    // The slowest gets the lowest block rather than none.
+    let floor = if summary.max > summary.min {
+        summary.min - (summary.max - summary.min) / 7.0
+    } else {
+        0.0
+    };
+    let columns = columns
+        .iter()
+        .map(|value| value - floor)
+        .collect::<Vec<_>>();
+    println!(
+        "   trend: {}  mean {mean:.1} MB/s{slope}",
+        sparkline(&columns)
+    );
+}
+
+/// The date of an RFC 3339 timestamp.
+fn date(timestamp: &str) -> &str {
+    timestamp.get(..10).unwrap_or(timestamp)
+}
+
+/// An RFC 3339 timestamp to the minute, with a space for the `T`.
+fn minute(timestamp: &str) -> String {
+    timestamp
+        .get(..16)
+        .unwrap_or(timestamp)
+        .replacen('T', " ", 1)
+}
@@ -0,0 +1,193 @@
+//! HTML report of a read-tree run (`--report`), with the charts drawn as
+//! inline SVG so the file needs nothing else to be viewed or passed around.
+
+use std::{fmt::Write as _, path::Path};
+
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    chart::{self, escape, BOTTOM, COLORS, HEIGHT, LEFT, PLOT_HEIGHT, PLOT_WIDTH, TOP},
+    histogram::Histogram,
+    output::RunRecord,
+    units::{format_latency, format_size},
+};
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 4px 10px; text-align: right; }
+th { background: #f4f4f4; }
+svg { display: block; margin-bottom: 2em; }
+.note { color: #666; }";
+
+/// Write the report for the runs of a read-tree invocation to `path`.
+pub fn write_report(path: &Path, title: &str, records: &[RunRecord]) -> Result<(), std::io::Error> {
+    std::fs::write(path, render(title, records)?)
+}
+
+fn render(title: &str, records: &[RunRecord]) -> Result<String, std::io::Error> {
+    let generated = OffsetDateTime::now_utc()
+        .format(&Rfc3339)
+        .map_err(std::io::Error::other)?;
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>iobench {}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n<h1>iobench {}</h1>\n<p class=\"note\">Generated {generated}</p>\n",
+        escape(title),
+        escape(title),
+    );
+
+    html.push_str("<h2>Runs</h2>\n");
+    html.push_str(&runs_table(records));
+
+    html.push_str("<h2>Throughput over time</h2>\n");
+    html.push_str(&throughput_chart(records));
+
+    // With several runs, the distributions are of all of them together.
+    let mut latency = Histogram::default();
+    let mut sizes = Histogram::default();
+    for record in records {
+        latency.merge(&record.latency);
+        sizes.merge(&record.sizes);
+    }
+    let all = if records.len() > 1 { ", all runs" } else { "" };
+    let _ = writeln!(html, "<h2>Latency per file{all}</h2>");
+    html.push_str(&histogram_chart(&latency, "time to open and read", |bin| {
+        format_latency(1 << bin)
+    }));
+    let _ = writeln!(html, "<h2>File sizes{all}</h2>");
+    html.push_str(&histogram_chart(&sizes, "bytes read", |bin| {
+        format_size(1 << bin)
+    }));
+
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+fn runs_table(records: &[RunRecord]) -> String {
+    let mut table = String::from(
+        "<table>\n<tr><th>run</th><th>started</th><th>threads</th><th>buffer</th><th>files</th><th>MB</th><th>seconds</th><th>MB/s</th><th>files/s</th><th>p50 latency</th><th>p99 latency</th></tr>\n",
+    );
+    for (run, record) in records.iter().enumerate() {
+        let started = record.timestamp.format(&Rfc3339).unwrap_or_default();
+        let _ = writeln!(
+            table,
+            "<tr><td>{}</td><td>{started}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}</td><td>{:.3}</td><td>{:.0}</td><td>{:.0}</td><td>{}</td><td>{}</td></tr>",
+            run + 1,
+            record.threads,
+            format_size(record.buf_size),
+            record.files,
+            record.bytes as f64 / 1e6,
+            record.duration_s,
+            record.mb_per_s(),
+            record.files_per_s(),
+            format_latency(record.latency.percentile(50.0)),
+            format_latency(record.latency.percentile(99.0)),
+        );
+    }
+    table.push_str("</table>\n");
+    table
+}
+
+/// A line per run of the MB/s read in each second.
+fn throughput_chart(records: &[RunRecord]) -> String {
+    let seconds = records
+        .iter()
+        .map(|record| record.series.len())
+        .max()
+        .unwrap_or(0);
+    if seconds == 0 {
+        return "<p class=\"note\">No run lasted a whole second.</p>\n".to_string();
+    }
+    let peak = records
+        .iter()
+        .flat_map(|record| &record.series)
+        .map(|sample| sample.bytes as f64 / 1e6)
+        .fold(0.0, f64::max);
+    let mut svg = String::new();
+    let y_max = chart::axes(&mut svg, peak, "MB/s");
+    let x = |second: f64| LEFT + PLOT_WIDTH * second / seconds as f64;
+    let y = |mb_per_s: f64| TOP + PLOT_HEIGHT * (1.0 - mb_per_s / y_max);
+
+    let step = chart::tick_step(seconds as f64, 10) as usize;
+    for tick in (0..=seconds).step_by(step) {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{tick}</text>",
+            x(tick as f64),
+            HEIGHT - BOTTOM + 18.0,
+        );
+    }
+    chart::x_title(&mut svg, "seconds");
+
+    for (run, record) in records.iter().enumerate() {
+        let color = COLORS[run % COLORS.len()];
+        // Each second's value is drawn at the end of that second.
+        let points = record
+            .series
+            .iter()
+            .enumerate()
+            .map(|(second, sample)| {
+                format!(
+                    "{:.1},{:.1}",
+                    x(second as f64 + 1.0),
+                    y(sample.bytes as f64 / 1e6)
+                )
+            })
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"2\" points=\"{}\"><title>run {}: {} threads, {}</title></polyline>",
+            points.join(" "),
+            run + 1,
+            record.threads,
+            format_size(record.buf_size),
+        );
+        if records.len() > 1 {
+            chart::legend(&mut svg, run, &format!("run {}", run + 1));
+        }
+    }
+    chart::finish(svg)
+}
+
+/// A bar per power-of-two range of the histogram's values, labelled with the
+/// start of the range.
+fn histogram_chart(
+    histogram: &Histogram,
+    x_title: &str,
+    label: impl Fn(usize) -> String,
+) -> String {
+    let counts = histogram.power_of_two_counts();
+    let Some(first) = counts.iter().position(|&count| count > 0) else {
+        return "<p class=\"note\">No files were read.</p>\n".to_string();
+    };
+    let bins = &counts[first..];
+    let peak = bins.iter().copied().max().unwrap_or(0);
+    let mut svg = String::new();
+    let y_max = chart::axes(&mut svg, peak as f64, "files");
+    let bar_w = PLOT_WIDTH / bins.len() as f64;
+    for (i, &count) in bins.iter().enumerate() {
+        let bin = first + i;
+        let height = PLOT_HEIGHT * count as f64 / y_max;
+        let x = LEFT + bar_w * i as f64;
+        let _ = writeln!(
+            svg,
+            "<rect class=\"bar\" x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{height:.1}\"><title>{} to {}: {count} files</title></rect>",
+            x + 1.0,
+            TOP + PLOT_HEIGHT - height,
+            (bar_w - 2.0).max(1.0),
+            label(bin),
+            label(bin + 1),
+        );
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"end\" transform=\"rotate(-35 {:.1} {:.1})\">{}</text>",
+            x + bar_w / 2.0,
+            HEIGHT - BOTTOM + 14.0,
+            x + bar_w / 2.0,
+            HEIGHT - BOTTOM + 14.0,
+            label(bin),
+        );
+    }
+    chart::x_title(&mut svg, x_title);
+    chart::finish(svg)
+}
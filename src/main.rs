@@ -1,18 +1,85 @@
 //! Disk I/O benchmark test. Measure the read performance of filesystem access.
 //! Multithreaded with configurable concurrency.
 
-use std::{env, io::Read, os::unix::fs::MetadataExt, path::PathBuf, time::Instant};
+use std::env;
 
+use cache_report::CacheReportArgs;
 use clap::{Parser, Subcommand};
-use jwalk::{
-    rayon::{
-        self,
-        iter::{IntoParallelRefIterator, ParallelIterator},
-    },
-    DirEntry, WalkDir,
-};
-use tracing::{debug, trace};
+use cleanup::CleanupArgs;
+use compare::CompareArgs;
+use copy_tree::CopyTreeArgs;
+use fallocate::FallocateArgs;
+use fsync::FsyncArgs;
+use history::HistoryArgs;
+use list_tree::ListTreeArgs;
+use mixed::MixedArgs;
+use open_close::OpenCloseArgs;
+use plot::PlotArgs;
+use rand_read::RandReadArgs;
+use rand_write::RandWriteArgs;
+use raw_read::RawReadArgs;
+use read_list::ReadListArgs;
+use read_tree::ReadTreeArgs;
+use scrub::ScrubArgs;
+use stat_tree::StatTreeArgs;
 use tracing_subscriber::{layer::SubscriberExt as _, util::SubscriberInitExt as _, EnvFilter};
+use verify::VerifyArgs;
+use wal::WalArgs;
+use write_tree::WriteTreeArgs;
+
+mod baseline;
+mod breakdown;
+mod cache;
+mod cache_report;
+mod chart;
+mod checkpoint;
+mod checksum;
+mod cleanup;
+mod compare;
+mod config;
+mod copy_tree;
+mod engine;
+mod errors;
+mod exit_code;
+mod fallocate;
+mod file_list;
+mod fsync;
+mod glob;
+mod histogram;
+mod history;
+mod html_report;
+mod json;
+mod list_tree;
+mod manifest;
+mod metrics;
+mod mixed;
+mod open_close;
+mod otlp;
+mod output;
+mod parquet;
+mod plot;
+mod progress;
+mod rand_read;
+mod rand_write;
+mod raw_read;
+mod read_list;
+mod read_tree;
+mod results_db;
+mod rng;
+mod scrub;
+mod signals;
+mod sqlite;
+mod stat_tree;
+mod stats;
+mod statsd;
+mod toml;
+mod trace;
+mod tui;
+mod units;
+mod verify;
+mod wal;
+mod walk;
+mod write_tree;
 
 /// Disk I/O benchmark performance test
 #[derive(Parser, Debug, Clone)]
@@ -22,163 +89,84 @@ struct Cli {
     command: CliCommand,
 }
 
+// Parsed once at startup, so the size of the largest variant doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Subcommand, Debug, Clone)]
 enum CliCommand {
     /// Read a filesystem directory tree recursively.
-    ReadTree {
-        /// The directory to read instead of CWD.
-        #[arg(short, long)]
-        dir: Option<String>,
-        /// Number of concurrent threads to use.
-        #[arg(short = 'j', long, default_value_t = 16)]
-        threads: u32,
-
-        /// Filesystem paths to read (alternative to -d/--dir DIR)
-        paths: Vec<String>,
-    },
+    #[command(alias = "sweep-threads")]
+    ReadTree(ReadTreeArgs),
+    /// Read the files named on stdin or in a file, one per line.
+    ReadList(ReadListArgs),
+    /// Create a directory tree of files to measure write performance, or as a
+    /// reproducible dataset for the read benchmarks.
+    #[command(visible_alias = "create-tree")]
+    WriteTree(WriteTreeArgs),
+    /// Read random blocks from within large files to measure IOPS and latency.
+    RandRead(RandReadArgs),
+    /// Write random blocks within preallocated files to measure write IOPS and latency.
+    RandWrite(RandWriteArgs),
+    /// Read a block device directly, sequentially or at random, without a filesystem.
+    RawRead(RawReadArgs),
+    /// Stat every entry in a directory tree, without opening files.
+    StatTree(StatTreeArgs),
+    /// List every entry in a directory tree with readdir() only.
+    ListTree(ListTreeArgs),
+    /// Open and immediately close every file in a directory tree.
+    OpenClose(OpenCloseArgs),
+    /// Append small blocks to a file, syncing after each, to measure sync latency.
+    Fsync(FsyncArgs),
+    /// Append records to a log, syncing after every group, to measure commit latency.
+    Wal(WalArgs),
+    /// Create large files with fallocate() or ftruncate() to measure preallocation speed.
+    Fallocate(FallocateArgs),
+    /// Delete a tree created by write-tree, measuring unlink throughput.
+    Cleanup(CleanupArgs),
+    /// Write files with a seeded pattern and read them back to check their integrity.
+    Verify(VerifyArgs),
+    /// Re-read a tree and compare it against a manifest saved by read-tree --checksum.
+    Scrub(ScrubArgs),
+    /// Interleave reads of existing files with writes of new ones at a given ratio.
+    Mixed(MixedArgs),
+    /// Copy every file of a directory tree to a new tree, in parallel.
+    CopyTree(CopyTreeArgs),
+    /// Report how much of each file in a tree is resident in the page cache.
+    CacheReport(CacheReportArgs),
+    /// Draw charts of saved read-tree results (--output json) as SVG.
+    Plot(PlotArgs),
+    /// Compare two saved read-tree results files (--output json), metric by metric.
+    Compare(CompareArgs),
+    /// List the past runs recorded in a results database (read-tree --db), with their trends.
+    History(HistoryArgs),
 }
 
 fn main() {
     init_logging();
-    let options = Cli::parse();
+    let options = config::parse::<Cli>();
 
     match options.command {
-        CliCommand::ReadTree {
-            dir,
-            paths,
-            threads,
-        } => {
-            let mut paths = paths.clone();
-            if let Some(d) = dir {
-                paths.push(d);
-            }
-            let mut paths = paths
-                .into_iter()
-                .map(PathBuf::from)
-                .collect::<Vec<PathBuf>>();
-            if paths.is_empty() {
-                paths.push(std::env::current_dir().unwrap());
-            }
-            read_tree(paths, threads);
-        }
-    }
-}
-
-fn read_tree(dirs: Vec<PathBuf>, threads: u32) {
-    println!("-- reading {dirs:?} using {threads} threads");
-    let t1 = Instant::now();
-    let mut all_files = Vec::new();
-    for dir in dirs {
-        let files = WalkDir::new(dir)
-            .parallelism(jwalk::Parallelism::RayonNewPool(threads as usize))
-            .skip_hidden(false)
-            .sort(true)
-            .into_iter()
-            .filter_map(|result| result.ok().filter(|entry| entry.file_type.is_file()))
-            .collect::<Vec<_>>();
-        all_files.extend(files);
+        CliCommand::ReadTree(args) => read_tree::read_tree(&args),
+        CliCommand::ReadList(args) => read_list::read_list(&args),
+        CliCommand::WriteTree(args) => write_tree::write_tree(&args),
+        CliCommand::RandRead(args) => rand_read::rand_read(&args),
+        CliCommand::RandWrite(args) => rand_write::rand_write(&args),
+        CliCommand::RawRead(args) => raw_read::raw_read(&args),
+        CliCommand::StatTree(args) => stat_tree::stat_tree(&args),
+        CliCommand::ListTree(args) => list_tree::list_tree(&args),
+        CliCommand::OpenClose(args) => open_close::open_close(&args),
+        CliCommand::Fsync(args) => fsync::fsync(&args),
+        CliCommand::Wal(args) => wal::wal(&args),
+        CliCommand::Fallocate(args) => fallocate::fallocate(&args),
+        CliCommand::Cleanup(args) => cleanup::cleanup(&args),
+        CliCommand::Verify(args) => verify::verify(&args),
+        CliCommand::Scrub(args) => scrub::scrub(&args),
+        CliCommand::Mixed(args) => mixed::mixed(&args),
+        CliCommand::CopyTree(args) => copy_tree::copy_tree(&args),
+        CliCommand::CacheReport(args) => cache_report::cache_report(&args),
+        CliCommand::Plot(args) => plot::plot(&args),
+        CliCommand::Compare(args) => compare::compare(&args),
+        CliCommand::History(args) => history::history(&args),
     }
-    let t2 = Instant::now();
-    let dur_s = (t2 - t1).as_secs_f64();
-    println!(
-        "-- list: {:.0} files/s  ({} files in {} s)",
-        all_files.len() as f64 / dur_s,
-        all_files.len(),
-        dur_s,
-    );
-
-    let pool = rayon::ThreadPoolBuilder::new()
-        .num_threads(threads as usize)
-        .build()
-        .expect("thread pool");
-
-    let t1 = Instant::now();
-
-    let all_stats = pool.install(|| {
-        all_files
-            .par_iter()
-            .map(read_file)
-            .reduce(ReadFilesStats::default, |a, b| a.combine(&b))
-    });
-
-    let t2 = Instant::now();
-    let dur_s = (t2 - t1).as_secs_f64();
-    let total_size_mb = all_stats.bytes as f64 / 1_000_000.0;
-    println!(
-        "-- read: {:.0} MB/s   {:.0} files/s  ({} MB in {} s)",
-        total_size_mb / dur_s,
-        all_files.len() as f64 / dur_s,
-        total_size_mb,
-        dur_s,
-    );
-}
-
-#[derive(Default)]
-struct ReadFilesStats {
-    bytes: u64,
-    file_count: u64,
-}
-
-impl ReadFilesStats {
-    fn combine(&self, other: &Self) -> Self {
-        Self {
-            bytes: self.bytes + other.bytes,
-            file_count: self.file_count + other.file_count,
-        }
-    }
-}
-
-fn read_file(entry: &DirEntry<((), ())>) -> ReadFilesStats {
-    let mut stats = ReadFilesStats::default();
-    let path = entry.path();
-
-    match do_read_file(entry, &mut stats) {
-        Ok(()) => {
-            trace!("done reading file {}", path.to_string_lossy());
-        }
-        Err(err) => {
-            debug!("error reading file {}: {err}", path.to_string_lossy());
-        }
-    }
-
-    stats
-}
-
-const BUF_SIZE: usize = 65536;
-
-fn do_read_file(
-    entry: &DirEntry<((), ())>,
-    stats: &mut ReadFilesStats,
-) -> Result<(), std::io::Error> {
-    let path = entry.path();
-    let pathstr = path.to_string_lossy();
-    trace!("open file: {}", pathstr);
-    let mut f = std::fs::File::open(&path)?;
-    stats.file_count += 1;
-    let size = entry.metadata()?.size();
-    let mb = size as f64 / 1e6;
-    trace!("begin reading file: {}", pathstr);
-    let mut buf = [0; BUF_SIZE];
-    loop {
-        let n = f.read(&mut buf[..])?;
-        if n == 0 {
-            if stats.bytes != size {
-                debug!("file must have been truncated, size was {size} but only read {} before getting empty read: {pathstr}", stats.bytes);
-            }
-            break;
-        }
-        stats.bytes += n as u64;
-        trace!(
-            "read chunk of {n} bytes ({:.1}% of {mb:.3} MB) from: {pathstr}",
-            100.0 * stats.bytes as f64 / size as f64,
-        );
-        if stats.bytes > size {
-            debug!("file must have been extended, size was {size} but we've read {}, stopping to avoid unbounded reading: {pathstr}", stats.bytes);
-            break;
-        }
-    }
-
-    Ok(())
 }
 
 const DEFAULT_LOGGING_DIRECTIVES: &str = "info,iobench=debug";
@@ -0,0 +1,106 @@
+//! Checksum manifests: the path, size and hash of every file read, saved by
+//! `read-tree --checksum` for `scrub` to check later.
+//!
+//! The format is one line per file, `HASH  SIZE  PATH`, with the hash in hex,
+//! sorted by path. Backslashes and newlines in paths are escaped as `\\` and
+//! `\n`. Lines starting with `#` are comments.
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+
+use crate::checksum::ChecksumKind;
+
+/// Default manifest file, in the current directory.
+pub const DEFAULT_MANIFEST: &str = "iobench.manifest";
+
+/// Start of the header line, which is followed by the checksum kind.
+const HEADER: &str = "# iobench manifest: ";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileChecksum {
+    pub path: PathBuf,
+    pub size: u64,
+    pub hash: u64,
+}
+
+pub fn write_manifest(
+    path: &Path,
+    kind: ChecksumKind,
+    checksums: &mut [FileChecksum],
+) -> io::Result<()> {
+    checksums.sort_by(|a, b| a.path.cmp(&b.path));
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "{HEADER}{} hash, size, path", kind.name())?;
+    for checksum in checksums.iter() {
+        let path = checksum
+            .path
+            .to_string_lossy()
+            .replace('\\', "\\\\")
+            .replace('\n', "\\n");
+        writeln!(out, "{:016x}  {}  {path}", checksum.hash, checksum.size)?;
+    }
+    out.flush()
+}
+
+/// Read a manifest written by [`write_manifest`], returning the kind of
+/// checksum it holds and its entries in file order.
+pub fn read_manifest(path: &Path) -> io::Result<(ChecksumKind, Vec<FileChecksum>)> {
+    let invalid = |line_no: usize, msg: &str| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("line {line_no}: {msg}"))
+    };
+    let mut kind = None;
+    let mut checksums = Vec::new();
+    for (i, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        let line_no = i + 1;
+        if let Some(header) = line.strip_prefix(HEADER) {
+            let name = header.split_whitespace().next().unwrap_or_default();
+            kind = Some(
+                ChecksumKind::from_str(name, true)
+                    .map_err(|_| invalid(line_no, &format!("unknown checksum {name:?}")))?,
+            );
+            continue;
+        }
+        if line.starts_with('#') || line.is_empty() {
+            continue;
+        }
+        let mut fields = line.splitn(3, "  ");
+        let (Some(hash), Some(size), Some(path)) = (fields.next(), fields.next(), fields.next())
+        else {
+            return Err(invalid(line_no, "expected HASH  SIZE  PATH"));
+        };
+        checksums.push(FileChecksum {
+            path: PathBuf::from(unescape(path)),
+            size: size
+                .parse()
+                .map_err(|_| invalid(line_no, &format!("invalid size {size:?}")))?,
+            hash: u64::from_str_radix(hash, 16)
+                .map_err(|_| invalid(line_no, &format!("invalid hash {hash:?}")))?,
+        });
+    }
+    let kind = kind.ok_or_else(|| invalid(1, "missing iobench manifest header"))?;
+    Ok((kind, checksums))
+}
+
+/// Undo the escaping of backslashes and newlines in a manifest path.
+fn unescape(path: &str) -> String {
+    let mut out = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
@@ -0,0 +1,236 @@
+//! Results in the OpenMetrics text format: written to a file by
+//! `--metrics-out` for node_exporter's textfile collector, and served over
+//! HTTP by `--listen` while a long run goes on.
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Duration,
+};
+
+use tracing::debug;
+
+use crate::output::RunRecord;
+
+/// Totals over every measured pass so far, for --listen.
+static BYTES_READ: AtomicU64 = AtomicU64::new(0);
+static FILES_READ: AtomicU64 = AtomicU64::new(0);
+static READ_ERRORS: AtomicU64 = AtomicU64::new(0);
+/// The metric families of the runs finished so far, for --listen.
+static RESULTS: Mutex<String> = Mutex::new(String::new());
+
+/// Percentiles of the latency given as summary quantiles.
+const QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 0.999];
+
+/// Count a file read in a measured pass.
+pub fn count_file(bytes: u64, failed: bool) {
+    BYTES_READ.fetch_add(bytes, Ordering::Relaxed);
+    FILES_READ.fetch_add(1, Ordering::Relaxed);
+    if failed {
+        READ_ERRORS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Make the results of the runs finished so far available to scrapes.
+pub fn publish(records: &[RunRecord]) {
+    *RESULTS.lock().unwrap() = result_families(records);
+}
+
+/// Write the results of every run to `path`, replacing it atomically so a
+/// collector never reads a half-written file.
+pub fn write_metrics(path: &Path, records: &[RunRecord]) -> Result<(), io::Error> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::write(&tmp, result_families(records) + "# EOF\n")?;
+    std::fs::rename(&tmp, path)
+}
+
+/// Serve the live totals and finished results at `/metrics` from a
+/// background thread, returning the address listened on.
+pub fn serve(addr: SocketAddr) -> Result<SocketAddr, io::Error> {
+    let listener = TcpListener::bind(addr)?;
+    let local = listener.local_addr()?;
+    std::thread::Builder::new()
+        .name("metrics".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(answer);
+                if let Err(err) = result {
+                    debug!("error answering metrics request: {err}");
+                }
+            }
+        })?;
+    Ok(local)
+}
+
+fn answer(mut stream: TcpStream) -> Result<(), io::Error> {
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+    // Skip the headers; nothing in them matters here.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+    let (status, content_type, body) = if path == "/metrics" || path.starts_with("/metrics?") {
+        let body = live_families() + &RESULTS.lock().unwrap() + "# EOF\n";
+        (
+            "200 OK",
+            "application/openmetrics-text; version=1.0.0; charset=utf-8",
+            body,
+        )
+    } else {
+        ("404 Not Found", "text/plain", "not found\n".to_string())
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )?;
+    stream.flush()
+}
+
+fn live_families() -> String {
+    let mut out = String::new();
+    for (name, help, value) in [
+        (
+            "iobench_live_read_bytes",
+            "Bytes read so far in the measured passes.",
+            &BYTES_READ,
+        ),
+        (
+            "iobench_live_read_files",
+            "Files read so far in the measured passes.",
+            &FILES_READ,
+        ),
+        (
+            "iobench_live_read_errors",
+            "Files that couldn't be read so far in the measured passes.",
+            &READ_ERRORS,
+        ),
+    ] {
+        let _ = write!(
+            out,
+            "# TYPE {name} counter\n# HELP {name} {help}\n{name}_total {}\n",
+            value.load(Ordering::Relaxed)
+        );
+    }
+    out
+}
+
+/// Name, help text and value of a gauge with a sample per run.
+type Gauge = (&'static str, &'static str, fn(&RunRecord) -> f64);
+
+/// A gauge per run for each result, and a summary of each run's latency.
+fn result_families(records: &[RunRecord]) -> String {
+    let gauges: [Gauge; 7] = [
+        (
+            "iobench_read_throughput_bytes_per_second",
+            "Mean read throughput of the run.",
+            |r| r.bytes as f64 / r.duration_s,
+        ),
+        (
+            "iobench_read_files_per_second",
+            "Files read per second in the run.",
+            RunRecord::files_per_s,
+        ),
+        ("iobench_read_bytes", "Bytes read in the run.", |r| {
+            r.bytes as f64
+        }),
+        ("iobench_read_files", "Files read in the run.", |r| {
+            r.files as f64
+        }),
+        (
+            "iobench_read_errors",
+            "Files that couldn't be read in the run.",
+            |r| r.errors as f64,
+        ),
+        (
+            "iobench_read_duration_seconds",
+            "Length of the run's read phase.",
+            |r| r.duration_s,
+        ),
+        (
+            "iobench_read_start_timestamp_seconds",
+            "When the run started, in seconds since the epoch.",
+            |r| r.timestamp.unix_timestamp_nanos() as f64 / 1e9,
+        ),
+    ];
+    let mut out = String::new();
+    for (name, help, value) in gauges {
+        let _ = writeln!(out, "# TYPE {name} gauge\n# HELP {name} {help}");
+        for (run, record) in records.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{name}{{{}}} {}",
+                labels(run, record),
+                number(value(record))
+            );
+        }
+    }
+    let name = "iobench_read_latency_seconds";
+    let _ = writeln!(
+        out,
+        "# TYPE {name} summary\n# HELP {name} Time to open and read each file."
+    );
+    for (run, record) in records.iter().enumerate() {
+        let labels = labels(run, record);
+        let latency = &record.latency;
+        for quantile in QUANTILES {
+            let _ = writeln!(
+                out,
+                "{name}{{{labels},quantile=\"{quantile}\"}} {}",
+                number(latency.percentile(quantile * 100.0) as f64 / 1e9)
+            );
+        }
+        let _ = writeln!(
+            out,
+            "{name}_sum{{{labels}}} {}\n{name}_count{{{labels}}} {}",
+            number(latency.mean() * latency.count() as f64 / 1e9),
+            latency.count()
+        );
+    }
+    out
+}
+
+fn labels(run: usize, record: &RunRecord) -> String {
+    let paths = record
+        .paths
+        .iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(
+        "run=\"{}\",threads=\"{}\",buf_size=\"{}\",paths=\"{}\"",
+        run + 1,
+        record.threads,
+        record.buf_size,
+        escape_label(&paths),
+    )
+}
+
+/// Escape a label value: backslashes, quotes and line breaks.
+fn escape_label(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Format a sample value, which may be NaN or infinite.
+fn number(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
@@ -0,0 +1,282 @@
+//! Mixed workload: reads of existing files interleaved with writes of new
+//! ones, at a given ratio.
+
+use std::{
+    fs::{self, File},
+    io::{self, Write},
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use clap::Args;
+use jwalk::DirEntry;
+use tracing::{debug, error};
+
+use crate::{
+    engine::{print_engine_report, EngineArgs},
+    histogram::Histogram,
+    output::print_latency,
+    rng::Rng,
+    units::parse_duration,
+    walk::{paths_or_cwd, walk_files, WalkArgs},
+    write_tree::SizeDistribution,
+};
+
+/// Options for the `mixed` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct MixedArgs {
+    /// Ratio of reads to writes, e.g. 70/30 (or just the read percentage, 70).
+    #[arg(long, value_name = "R/W", default_value = "70/30", value_parser = parse_rwmix)]
+    pub rwmix: f64,
+    /// Number of concurrent threads to use, for both listing and the workload.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+    /// Total number of operations (file reads plus file writes) to perform.
+    #[arg(short = 'n', long, default_value_t = 10_000)]
+    pub ops: u64,
+    /// Stop after this long (e.g. 60s), even if not all operations were done.
+    #[arg(long, value_parser = parse_duration)]
+    pub duration: Option<Duration>,
+    /// Size of each written file: fixed (e.g. 64k), a uniform range (e.g.
+    /// 4k..1M) or log-normal (e.g. lognormal:16k:2).
+    #[arg(short = 's', long, default_value = "64k", value_parser = SizeDistribution::parse)]
+    pub write_size: SizeDistribution,
+    /// Directory to write new files in; it must not exist yet, and is removed
+    /// afterwards unless --keep is given [default: a new directory in the
+    /// first path].
+    #[arg(long, value_name = "DIR")]
+    pub write_dir: Option<PathBuf>,
+    /// Call fsync on each file after writing it.
+    #[arg(long)]
+    pub fsync: bool,
+    /// Keep the written files instead of removing them.
+    #[arg(long)]
+    pub keep: bool,
+    /// Seed for the choice of operations, files and written sizes.
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    #[command(flatten)]
+    pub engine: EngineArgs,
+
+    /// Directories whose files are read [default: CWD].
+    pub paths: Vec<PathBuf>,
+}
+
+/// Parse `R/W` (e.g. `70/30`) or a read percentage (e.g. `70`) into the
+/// fraction of operations that are reads.
+fn parse_rwmix(s: &str) -> Result<f64, String> {
+    let parse = |part: &str| {
+        part.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite() && *value >= 0.0)
+            .ok_or_else(|| format!("invalid read/write mix: {s:?}"))
+    };
+    let (reads, writes) = match s.split_once('/') {
+        Some((reads, writes)) => (parse(reads)?, parse(writes)?),
+        None => {
+            let reads = parse(s)?;
+            (reads, 100.0 - reads)
+        }
+    };
+    if reads + writes <= 0.0 || writes < 0.0 {
+        return Err(format!("invalid read/write mix: {s:?}"));
+    }
+    Ok(reads / (reads + writes))
+}
+
+#[derive(Default)]
+struct MixedStats {
+    read_bytes: u64,
+    write_bytes: u64,
+    /// Time to open and read each successfully read file, in nanoseconds.
+    read_latency: Histogram,
+    /// Time to create, write (and sync) each successfully written file, in nanoseconds.
+    write_latency: Histogram,
+    errors: u64,
+}
+
+impl MixedStats {
+    fn combine(mut self, other: Self) -> Self {
+        self.read_bytes += other.read_bytes;
+        self.write_bytes += other.write_bytes;
+        self.read_latency.merge(&other.read_latency);
+        self.write_latency.merge(&other.write_latency);
+        self.errors += other.errors;
+        self
+    }
+}
+
+pub fn mixed(options: &MixedArgs) {
+    if let Err(err) = options.engine.check_available() {
+        error!("{} is not available: {err}", options.engine);
+        std::process::exit(1);
+    }
+    let dirs = paths_or_cwd(&options.paths);
+    let threads = options.threads.max(1);
+    let reads_pct = 100.0 * options.rwmix;
+    println!(
+        "-- {reads_pct:.0}% reads of files in {dirs:?}, {:.0}% writes of new files, using {threads} threads ({})",
+        100.0 - reads_pct,
+        options.engine,
+    );
+
+    let t1 = Instant::now();
+    let mut files = Vec::new();
+    for dir in &dirs {
+        files.extend(walk_files(dir, threads, &options.walk));
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- list: {:.0} files/s  ({} files in {dur_s} s)",
+        files.len() as f64 / dur_s,
+        files.len(),
+    );
+    if files.is_empty() && options.rwmix > 0.0 {
+        println!("-- mixed: no files to read");
+        return;
+    }
+
+    let write_dir = options
+        .write_dir
+        .clone()
+        .unwrap_or_else(|| dirs[0].join(format!(".iobench-mixed-{}", std::process::id())));
+    if let Err(err) = fs::create_dir(&write_dir) {
+        error!("error creating {}: {err}", write_dir.to_string_lossy());
+        std::process::exit(1);
+    }
+
+    let seed = options.seed.unwrap_or_else(|| Rng::from_time().next_u64());
+    let t1 = Instant::now();
+    let deadline = options.duration.map(|duration| t1 + duration);
+    let stats = std::thread::scope(|scope| {
+        let workers = (0..u64::from(threads))
+            .map(|worker| {
+                let ops = options.ops / u64::from(threads)
+                    + u64::from(worker < options.ops % u64::from(threads));
+                let files = &files;
+                let write_dir = &write_dir;
+                scope.spawn(move || {
+                    let mut rng = Rng::new(seed.wrapping_add(worker));
+                    let mut stats = MixedStats::default();
+                    for op in 0..ops {
+                        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                            break;
+                        }
+                        if rng.next_f64() < options.rwmix {
+                            let entry =
+                                &files[rng.range_inclusive(0, files.len() as u64 - 1) as usize];
+                            read_one(entry, &options.engine, &mut stats);
+                        } else {
+                            let path = write_dir.join(format!("w{worker}-{op}"));
+                            let size = options.write_size.sample(&mut rng);
+                            write_one(&path, size, options, &mut rng, &mut stats);
+                        }
+                    }
+                    stats
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("mixed worker panicked"))
+            .fold(MixedStats::default(), MixedStats::combine)
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+
+    let reads = stats.read_latency.count();
+    let writes = stats.write_latency.count();
+    println!(
+        "-- mixed: {:.0} ops/s   {:.0} MB/s  ({} ops in {dur_s} s)",
+        (reads + writes) as f64 / dur_s,
+        (stats.read_bytes + stats.write_bytes) as f64 / 1e6 / dur_s,
+        reads + writes,
+    );
+    for (phase, count, bytes) in [
+        ("read", reads, stats.read_bytes),
+        ("write", writes, stats.write_bytes),
+    ] {
+        println!(
+            "-- {phase}: {:.0} MB/s   {:.0} files/s  ({count} files, {} MB)",
+            bytes as f64 / 1e6 / dur_s,
+            count as f64 / dur_s,
+            bytes as f64 / 1e6,
+        );
+    }
+    print_engine_report();
+    print_latency("read", &stats.read_latency);
+    print_latency("write", &stats.write_latency);
+    if stats.errors > 0 {
+        println!("-- errors: {}", stats.errors);
+    }
+
+    if !options.keep {
+        if let Err(err) = fs::remove_dir_all(&write_dir) {
+            error!("error removing {}: {err}", write_dir.to_string_lossy());
+        }
+    }
+}
+
+fn read_one(entry: &DirEntry<((), ())>, engine: &EngineArgs, stats: &mut MixedStats) {
+    let path = entry.path();
+    let pathstr = path.to_string_lossy();
+    let head = engine.head;
+    let t1 = Instant::now();
+    let mut bytes = 0;
+    let result = engine.with_thread_engine(|engine| {
+        let mut f = engine.open(&path)?;
+        if let Some(len) = head {
+            engine.read_head(&mut f, len, &pathstr, &mut bytes)?;
+        } else {
+            let size = entry.metadata()?.size();
+            engine.read_contents(&mut f, size, &pathstr, &mut bytes, None)?;
+        }
+        Ok(f)
+    });
+    match result {
+        Ok(f) => {
+            engine.finish_file(&f, &pathstr);
+            stats.read_latency.record(t1.elapsed().as_nanos() as u64);
+            stats.read_bytes += bytes;
+        }
+        Err(err) => {
+            stats.errors += 1;
+            debug!("error reading file {pathstr}: {err}");
+        }
+    }
+}
+
+fn write_one(path: &Path, size: u64, options: &MixedArgs, rng: &mut Rng, stats: &mut MixedStats) {
+    let t1 = Instant::now();
+    match write_file(path, size, options, rng) {
+        Ok(()) => {
+            stats.write_latency.record(t1.elapsed().as_nanos() as u64);
+            stats.write_bytes += size;
+        }
+        Err(err) => {
+            stats.errors += 1;
+            debug!("error writing file {}: {err}", path.to_string_lossy());
+        }
+    }
+}
+
+fn write_file(path: &Path, size: u64, options: &MixedArgs, rng: &mut Rng) -> Result<(), io::Error> {
+    let mut f = File::create(path)?;
+    let mut buf = vec![0; options.engine.effective_buf_size()];
+    let mut remaining = size;
+    while remaining > 0 {
+        let n = remaining.min(buf.len() as u64) as usize;
+        rng.fill_bytes(&mut buf[..n]);
+        f.write_all(&buf[..n])?;
+        remaining -= n as u64;
+    }
+    if options.fsync {
+        f.sync_all()?;
+    }
+    Ok(())
+}
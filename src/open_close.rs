@@ -0,0 +1,104 @@
+//! Open benchmark: list a directory tree, then open and immediately close
+//! every file in it, without reading anything.
+
+use std::{fs::File, path::PathBuf, time::Instant};
+
+use clap::Args;
+use jwalk::{
+    rayon::iter::{IntoParallelRefIterator, ParallelIterator},
+    DirEntry,
+};
+use tracing::debug;
+
+use crate::{
+    histogram::Histogram,
+    output::print_latency,
+    read_tree::build_pool,
+    walk::{paths_or_cwd, walk_files, WalkArgs},
+};
+
+/// Options for the `open-close` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct OpenCloseArgs {
+    /// Number of concurrent threads to use, for both listing and opening.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    /// Directories to walk [default: CWD].
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct OpenStats {
+    opens: u64,
+    errors: u64,
+    /// Time to open and close each file, in nanoseconds.
+    latency: Histogram,
+}
+
+impl OpenStats {
+    fn combine(mut self, other: Self) -> Self {
+        self.opens += other.opens;
+        self.errors += other.errors;
+        self.latency.merge(&other.latency);
+        self
+    }
+}
+
+pub fn open_close(options: &OpenCloseArgs) {
+    let dirs = paths_or_cwd(&options.paths);
+    let threads = options.threads.max(1);
+    println!("-- opening files in {dirs:?} using {threads} threads");
+
+    let t1 = Instant::now();
+    let mut files = Vec::new();
+    for dir in &dirs {
+        files.extend(walk_files(dir, threads, &options.walk));
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- list: {:.0} files/s  ({} files in {dur_s} s)",
+        files.len() as f64 / dur_s,
+        files.len(),
+    );
+
+    let pool = build_pool(threads);
+    let t1 = Instant::now();
+    let stats = pool.install(|| {
+        files
+            .par_iter()
+            .map(open_file)
+            .reduce(OpenStats::default, OpenStats::combine)
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- open: {:.0} opens/s  ({} files in {dur_s} s)",
+        stats.opens as f64 / dur_s,
+        stats.opens,
+    );
+    if stats.errors > 0 {
+        println!("-- {} files could not be opened", stats.errors);
+    }
+    print_latency("open+close", &stats.latency);
+}
+
+fn open_file(entry: &DirEntry<((), ())>) -> OpenStats {
+    let mut stats = OpenStats::default();
+    let path = entry.path();
+    let t1 = Instant::now();
+    match File::open(&path) {
+        Ok(f) => {
+            drop(f);
+            stats.latency.record(t1.elapsed().as_nanos() as u64);
+            stats.opens = 1;
+        }
+        Err(err) => {
+            debug!("error opening {}: {err}", path.to_string_lossy());
+            stats.errors = 1;
+        }
+    }
+    stats
+}
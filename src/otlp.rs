@@ -0,0 +1,219 @@
+//! Export of a run as OpenTelemetry spans (`--otlp-endpoint`), so it shows up
+//! in Jaeger or Tempo next to the application traces it is compared with.
+//!
+//! Spans are sent in one request at the end of the run, with OTLP/HTTP and
+//! JSON encoding. Only plain `http://` endpoints are supported; run a
+//! collector next to iobench to forward them over TLS.
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Write},
+    net::TcpStream,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{output::json_string, rng::Rng};
+
+/// How long to wait for the collector to connect and answer.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// An OTLP/HTTP traces endpoint, e.g. `http://localhost:4318`.
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    /// Host and port, as given.
+    authority: String,
+    path: String,
+}
+
+impl Endpoint {
+    /// Parse an endpoint URL; `/v1/traces` is appended unless it's already there.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        let rest = s
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("invalid endpoint {s:?}: only http:// URLs are supported"))?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        if authority.is_empty() {
+            return Err(format!("invalid endpoint {s:?}: no host"));
+        }
+        let path = if path.ends_with("/v1/traces") {
+            path.to_string()
+        } else {
+            format!("{}/v1/traces", path.trim_end_matches('/'))
+        };
+        Ok(Self {
+            authority: authority.to_string(),
+            path,
+        })
+    }
+
+    /// Host and port to connect to, with the HTTP port if none is given.
+    fn address(&self) -> String {
+        // The port follows the last colon, unless that's inside an IPv6 address.
+        match self.authority.rfind(':') {
+            Some(colon) if !self.authority[colon..].contains(']') => self.authority.clone(),
+            _ => format!("{}:80", self.authority),
+        }
+    }
+}
+
+impl std::fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "http://{}{}", self.authority, self.path)
+    }
+}
+
+pub enum Attribute {
+    Int(i64),
+    Double(f64),
+    Str(String),
+}
+
+pub struct Span {
+    pub id: u64,
+    pub parent: Option<u64>,
+    pub name: String,
+    pub start: Instant,
+    pub end: Instant,
+    pub attributes: Vec<(&'static str, Attribute)>,
+    /// Whether the span is marked as an error.
+    pub failed: bool,
+}
+
+/// The spans of one trace, collected during the run.
+pub struct SpanRecorder {
+    rng: Rng,
+    trace_id: [u64; 2],
+    /// A moment in both clocks, to convert `Instant`s to wall-clock time.
+    anchor: (Instant, SystemTime),
+    spans: Vec<Span>,
+}
+
+impl SpanRecorder {
+    pub fn new() -> Self {
+        let mut rng = Rng::from_time();
+        let trace_id = [rng.next_u64(), rng.next_u64()];
+        Self {
+            rng,
+            trace_id,
+            anchor: (Instant::now(), SystemTime::now()),
+            spans: Vec::new(),
+        }
+    }
+
+    /// Choose the id for a new span.
+    pub fn new_id(&mut self) -> u64 {
+        // Zero is an invalid span id.
+        self.rng.next_u64().max(1)
+    }
+
+    pub fn add(&mut self, span: Span) {
+        self.spans.push(span);
+    }
+
+    fn wall_time(&self, instant: Instant) -> SystemTime {
+        let (anchor, wall) = self.anchor;
+        if instant >= anchor {
+            wall + (instant - anchor)
+        } else {
+            wall - (anchor - instant)
+        }
+    }
+
+    /// Send the spans to the collector, returning how many were sent.
+    pub fn export(&self, endpoint: &Endpoint) -> Result<usize, io::Error> {
+        post(endpoint, &self.to_json())?;
+        Ok(self.spans.len())
+    }
+
+    /// The spans as an OTLP `ExportTraceServiceRequest` in JSON.
+    fn to_json(&self) -> String {
+        let trace_id = format!("{:016x}{:016x}", self.trace_id[0], self.trace_id[1]);
+        let unix_nanos = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos())
+        };
+        let spans = self
+            .spans
+            .iter()
+            .map(|span| {
+                let mut json = format!(
+                    "{{\"traceId\":\"{trace_id}\",\"spanId\":\"{:016x}\"",
+                    span.id
+                );
+                if let Some(parent) = span.parent {
+                    let _ = write!(json, ",\"parentSpanId\":\"{parent:016x}\"");
+                }
+                let _ = write!(
+                    json,
+                    ",\"name\":{},\"kind\":1,\"startTimeUnixNano\":\"{}\",\"endTimeUnixNano\":\"{}\",\"attributes\":{},\"status\":{{\"code\":{}}}}}",
+                    json_string(&span.name),
+                    unix_nanos(self.wall_time(span.start)),
+                    unix_nanos(self.wall_time(span.end)),
+                    attributes_json(&span.attributes),
+                    if span.failed { 2 } else { 0 },
+                );
+                json
+            })
+            .collect::<Vec<_>>();
+        let resource = attributes_json(&[
+            ("service.name", Attribute::Str("iobench".to_string())),
+            ("host.name", Attribute::Str(hostname())),
+        ]);
+        format!(
+            "{{\"resourceSpans\":[{{\"resource\":{{\"attributes\":{resource}}},\"scopeSpans\":[{{\"scope\":{{\"name\":\"iobench\",\"version\":\"{}\"}},\"spans\":[{}]}}]}}]}}",
+            env!("CARGO_PKG_VERSION"),
+            spans.join(","),
+        )
+    }
+}
+
+fn attributes_json(attributes: &[(&str, Attribute)]) -> String {
+    let attributes = attributes
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Attribute::Int(n) => format!("{{\"intValue\":\"{n}\"}}"),
+                Attribute::Double(x) if x.is_finite() => format!("{{\"doubleValue\":{x}}}"),
+                Attribute::Double(_) => "{}".to_string(),
+                Attribute::Str(s) => format!("{{\"stringValue\":{}}}", json_string(s)),
+            };
+            format!("{{\"key\":{},\"value\":{value}}}", json_string(key))
+        })
+        .collect::<Vec<_>>();
+    format!("[{}]", attributes.join(","))
+}
+
+/// POST a JSON body to the endpoint and check that it was accepted.
+fn post(endpoint: &Endpoint, body: &str) -> Result<(), io::Error> {
+    let mut stream = TcpStream::connect(endpoint.address())?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        endpoint.path,
+        endpoint.authority,
+        body.len(),
+    )?;
+    stream.write_all(body.as_bytes())?;
+    stream.flush()?;
+    let mut status = String::new();
+    BufReader::new(stream).read_line(&mut status)?;
+    let status = status.trim_end();
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(io::Error::other(format!("collector answered {status:?}"))),
+    }
+}
+
+/// The name of this host, for the resource the spans come from.
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: gethostname writes at most buf.len() bytes into buf.
+    let ret = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if ret != 0 {
+        return "unknown".to_string();
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
@@ -0,0 +1,567 @@
+//! Machine-readable result records written in addition to the summary lines.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, BufWriter, Write},
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use clap::{Args, ValueEnum};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use crate::{
+    engine::Engine,
+    histogram::Histogram,
+    json::{self, Value},
+    otlp::hostname,
+    trace::PassTrace,
+    units::{format_latency, format_size},
+};
+
+/// Set by --quiet, leaving only the machine-readable record on stdout.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Print a human-readable summary line, unless --quiet is in effect.
+macro_rules! report {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            println!($($arg)*);
+        }
+    };
+}
+pub(crate) use report;
+
+/// Format of the results record for a run.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human-readable summary lines only.
+    #[default]
+    Text,
+    /// Append one row per run to a CSV file.
+    Csv,
+    /// Append one JSON object per run, one per line, to a file.
+    Json,
+    /// Print a GitHub-flavored Markdown table with a row per run, or write it
+    /// to --output-file.
+    Markdown,
+    /// Append one InfluxDB line protocol point per run to a file.
+    Influx,
+}
+
+/// Options controlling where and how results are recorded.
+#[derive(Args, Debug, Clone)]
+pub struct OutputArgs {
+    /// Results output format.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+    /// File to write results to [csv default: iobench.csv, json default:
+    /// iobench.jsonl, influx default: iobench.lp, markdown default: stdout, or
+    /// iobench.md with --quiet].
+    #[arg(long)]
+    pub output_file: Option<PathBuf>,
+    /// Print no summary lines, only a single JSON object with the results of
+    /// every run on stdout when done.
+    #[arg(short, long)]
+    pub quiet: bool,
+    /// Also write a self-contained HTML report with charts of throughput over
+    /// time, latency and file sizes to PATH.
+    #[arg(long, value_name = "PATH")]
+    pub report: Option<PathBuf>,
+    /// Also write the results in OpenMetrics text format to PATH, e.g. for
+    /// node_exporter's textfile collector.
+    #[arg(long, value_name = "PATH")]
+    pub metrics_out: Option<PathBuf>,
+    /// Serve live totals and the results of finished runs in OpenMetrics
+    /// format at http://ADDR/metrics while running (e.g. 0.0.0.0:9464).
+    #[arg(long, value_name = "ADDR")]
+    pub listen: Option<SocketAddr>,
+    /// Send throughput, files/s, p99 latency and error count of each run to
+    /// the statsd daemon at HOST:PORT over UDP when done.
+    #[arg(long, value_name = "HOST:PORT")]
+    pub statsd: Option<String>,
+    /// Also record every run, with its parameters and the host it ran on, in
+    /// this SQLite database, creating it if needed. Uses the system's
+    /// libsqlite3.
+    #[arg(long, value_name = "PATH")]
+    pub db: Option<PathBuf>,
+}
+
+/// Summary of one benchmark run.
+#[derive(Debug, Clone)]
+pub struct RunRecord {
+    pub timestamp: OffsetDateTime,
+    pub paths: Vec<PathBuf>,
+    pub threads: u32,
+    /// Read buffer size in bytes.
+    pub buf_size: u64,
+    pub engine: Engine,
+    pub files: u64,
+    pub bytes: u64,
+    pub duration_s: f64,
+    /// Files that couldn't be read.
+    pub errors: u64,
+    /// What was read in each whole second of the run, as files finished.
+    pub series: Vec<Sample>,
+    /// Time to open and read each file, in nanoseconds.
+    pub latency: Histogram,
+    /// Bytes read from each file.
+    pub sizes: Histogram,
+}
+
+/// Bytes and files read in one second of a run.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sample {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+impl RunRecord {
+    pub fn mb_per_s(&self) -> f64 {
+        self.bytes as f64 / 1_000_000.0 / self.duration_s
+    }
+
+    pub fn files_per_s(&self) -> f64 {
+        self.files as f64 / self.duration_s
+    }
+}
+
+/// Percentiles of the latency saved in JSON records, enough to draw a CDF.
+const SAVED_PERCENTILES: [f64; 18] = [
+    0.0, 1.0, 5.0, 10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0, 95.0, 99.0, 99.5, 99.9,
+    99.99, 100.0,
+];
+
+const CSV_HEADER: &str = "timestamp,paths,threads,files,bytes,mb_per_s,files_per_s,series_mb_per_s";
+
+/// Write the records of all runs in the requested format. Text output is
+/// printed by the benchmark itself, so there is nothing more to do for it here.
+pub fn write_records(options: &OutputArgs, records: &[RunRecord]) -> Result<(), std::io::Error> {
+    if options.output == OutputFormat::Markdown {
+        let table = markdown_table(records);
+        return match &options.output_file {
+            Some(path) => std::fs::write(path, table),
+            None if options.quiet => std::fs::write("iobench.md", table),
+            None => {
+                println!();
+                print!("{table}");
+                Ok(())
+            }
+        };
+    }
+    records
+        .iter()
+        .try_for_each(|record| write_record(options, record))
+}
+
+/// Write one run record in a format with a line per run.
+fn write_record(options: &OutputArgs, record: &RunRecord) -> Result<(), std::io::Error> {
+    match options.output {
+        OutputFormat::Text | OutputFormat::Markdown => Ok(()),
+        OutputFormat::Csv => {
+            let path = options
+                .output_file
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("iobench.csv"));
+            append_csv(&path, record)
+        }
+        OutputFormat::Json => {
+            let path = options
+                .output_file
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("iobench.jsonl"));
+            let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(f, "{}", json_record(record)?)
+        }
+        OutputFormat::Influx => {
+            let path = options
+                .output_file
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("iobench.lp"));
+            let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(f, "{}", influx_line(record, &hostname()))
+        }
+    }
+}
+
+/// A run record as an InfluxDB line protocol point in the `iobench_read`
+/// measurement, timestamped in nanoseconds with the start of the run.
+fn influx_line(record: &RunRecord, host: &str) -> String {
+    let paths = record
+        .paths
+        .iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(",");
+    let mut fields = vec![
+        format!("bytes={}i", record.bytes),
+        format!("files={}i", record.files),
+        format!("errors={}i", record.errors),
+    ];
+    // Line protocol has no way to write infinities or NaN, so leave those out.
+    for (key, value) in [
+        ("duration_s", record.duration_s),
+        ("mb_per_s", record.mb_per_s()),
+        ("files_per_s", record.files_per_s()),
+    ] {
+        if value.is_finite() {
+            fields.push(format!("{key}={value}"));
+        }
+    }
+    if record.latency.count() > 0 {
+        fields.push(format!(
+            "p50_latency_ns={}i",
+            record.latency.percentile(50.0)
+        ));
+        fields.push(format!(
+            "p99_latency_ns={}i",
+            record.latency.percentile(99.0)
+        ));
+    }
+    format!(
+        "iobench_read,host={},path={},threads={},engine={},buf_size={} {} {}",
+        influx_tag(host),
+        influx_tag(&paths),
+        record.threads,
+        record.engine.name(),
+        record.buf_size,
+        fields.join(","),
+        record.timestamp.unix_timestamp_nanos(),
+    )
+}
+
+/// Escape a tag value for line protocol: commas, equals signs and spaces.
+/// Line breaks can't be escaped, so they are replaced with spaces.
+fn influx_tag(s: &str) -> String {
+    s.replace(['\n', '\r'], " ")
+        .replace(',', "\\,")
+        .replace('=', "\\=")
+        .replace(' ', "\\ ")
+}
+
+/// A Markdown table of the runs, with a row per run.
+fn markdown_table(records: &[RunRecord]) -> String {
+    let mut table = String::from(
+        "| run | paths | threads | buffer | files | MB | seconds | MB/s | files/s |\n\
+         |----:|-------|--------:|-------:|------:|---:|--------:|-----:|--------:|\n",
+    );
+    for (run, record) in records.iter().enumerate() {
+        let paths = record
+            .paths
+            .iter()
+            .map(|p| format!("`{}`", p.to_string_lossy()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        table.push_str(&format!(
+            "| {} | {} | {} | {} | {} | {:.1} | {:.3} | {:.0} | {:.0} |\n",
+            run + 1,
+            paths.replace('|', "\\|"),
+            record.threads,
+            format_size(record.buf_size),
+            record.files,
+            record.bytes as f64 / 1e6,
+            record.duration_s,
+            record.mb_per_s(),
+            record.files_per_s(),
+        ));
+    }
+    table
+}
+
+/// Print the records of every run as one JSON object on stdout, for --quiet.
+pub fn print_json_summary(records: &[RunRecord]) -> Result<(), std::io::Error> {
+    let runs = records
+        .iter()
+        .map(json_record)
+        .collect::<Result<Vec<_>, _>>()?;
+    println!("{{\"runs\":[{}]}}", runs.join(","));
+    Ok(())
+}
+
+/// A run record as a single-line JSON object.
+fn json_record(record: &RunRecord) -> Result<String, std::io::Error> {
+    let timestamp = record
+        .timestamp
+        .format(&Rfc3339)
+        .map_err(std::io::Error::other)?;
+    let paths = record
+        .paths
+        .iter()
+        .map(|p| json_string(&p.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(",");
+    let series = record
+        .series
+        .iter()
+        .map(|sample| format!("{{\"bytes\":{},\"files\":{}}}", sample.bytes, sample.files))
+        .collect::<Vec<_>>()
+        .join(",");
+    let latency = if record.latency.count() > 0 {
+        SAVED_PERCENTILES
+            .iter()
+            .map(|&p| format!("[{p},{}]", record.latency.percentile(p)))
+            .collect::<Vec<_>>()
+            .join(",")
+    } else {
+        String::new()
+    };
+    Ok(format!(
+        "{{\"timestamp\":{},\"paths\":[{paths}],\"engine\":{},\"threads\":{},\"buf_size\":{},\"files\":{},\"bytes\":{},\"errors\":{},\"duration_s\":{},\"mb_per_s\":{},\"files_per_s\":{},\"series\":[{series}],\"latency_ns\":[{latency}]}}",
+        json_string(&timestamp),
+        json_string(record.engine.name()),
+        record.threads,
+        record.buf_size,
+        record.files,
+        record.bytes,
+        record.errors,
+        json_number(record.duration_s),
+        json_number(record.mb_per_s()),
+        json_number(record.files_per_s()),
+    ))
+}
+
+/// A run record read back from a JSON results file.
+#[derive(Debug, Clone)]
+pub struct SavedRun {
+    /// The engine's name; sync for records saved before it was.
+    pub engine: String,
+    pub threads: u32,
+    pub buf_size: u64,
+    pub files: u64,
+    pub bytes: u64,
+    pub errors: u64,
+    pub duration_s: f64,
+    /// Latency in nanoseconds at each saved percentile, lowest first; empty if
+    /// the record has none.
+    pub latency: Vec<(f64, u64)>,
+}
+
+impl SavedRun {
+    pub fn mb_per_s(&self) -> f64 {
+        self.bytes as f64 / 1_000_000.0 / self.duration_s
+    }
+
+    fn from_json(value: &Value) -> Result<Self, String> {
+        let number = |key: &str| {
+            value
+                .get(key)
+                .and_then(Value::as_f64)
+                .ok_or_else(|| format!("missing or invalid {key:?}"))
+        };
+        let array = |key: &str| value.get(key).and_then(Value::as_array).unwrap_or(&[]);
+        let latency = array("latency_ns")
+            .iter()
+            .filter_map(|pair| match pair.as_array()? {
+                [p, ns] => Some((p.as_f64()?, ns.as_f64()? as u64)),
+                _ => None,
+            })
+            .collect();
+        Ok(Self {
+            engine: value
+                .get("engine")
+                .and_then(Value::as_str)
+                .unwrap_or(Engine::default().name())
+                .to_string(),
+            threads: number("threads")? as u32,
+            buf_size: number("buf_size")? as u64,
+            files: number("files")? as u64,
+            bytes: number("bytes")? as u64,
+            errors: value.get("errors").and_then(Value::as_f64).unwrap_or(0.0) as u64,
+            duration_s: number("duration_s")?,
+            latency,
+        })
+    }
+}
+
+/// Read the run records in a file written by `--output json`, one object per
+/// line, or by `--quiet`, with the records in a `runs` array.
+pub fn load_records(path: &Path) -> Result<Vec<SavedRun>, std::io::Error> {
+    let invalid = |line_no: usize, msg: &str| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("line {line_no}: {msg}"),
+        )
+    };
+    let mut runs = Vec::new();
+    for (i, line) in std::fs::read_to_string(path)?.lines().enumerate() {
+        let line_no = i + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let value = json::parse(line).map_err(|err| invalid(line_no, &err))?;
+        let records = match value.get("runs").and_then(Value::as_array) {
+            Some(records) => records,
+            None => std::slice::from_ref(&value),
+        };
+        for record in records {
+            runs.push(SavedRun::from_json(record).map_err(|err| invalid(line_no, &err))?);
+        }
+    }
+    Ok(runs)
+}
+
+/// Quote and escape a string for JSON.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Format a number for JSON, which has no infinities or NaN.
+pub fn json_number(value: f64) -> String {
+    if value.is_finite() {
+        value.to_string()
+    } else {
+        "null".to_string()
+    }
+}
+
+fn append_csv(path: &Path, record: &RunRecord) -> Result<(), std::io::Error> {
+    let mut f = OpenOptions::new()
+        .read(true)
+        .create(true)
+        .append(true)
+        .open(path)?;
+    if f.metadata()?.len() == 0 {
+        writeln!(f, "{CSV_HEADER}")?;
+    } else {
+        check_csv_header(path, &f)?;
+    }
+    let timestamp = record
+        .timestamp
+        .format(&Rfc3339)
+        .map_err(std::io::Error::other)?;
+    let paths = record
+        .paths
+        .iter()
+        .map(|p| p.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(";");
+    let series = record
+        .series
+        .iter()
+        .map(|sample| format!("{:.1}", sample.bytes as f64 / 1e6))
+        .collect::<Vec<_>>()
+        .join(";");
+    writeln!(
+        f,
+        "{},{},{},{},{},{:.3},{:.3},{}",
+        timestamp,
+        csv_field(&paths),
+        record.threads,
+        record.files,
+        record.bytes,
+        record.mb_per_s(),
+        record.files_per_s(),
+        series,
+    )
+}
+
+/// Check that the CSV file with records is in the columns written now, since
+/// rows appended under an older header would be misread.
+fn check_csv_header(path: &Path, f: &File) -> Result<(), std::io::Error> {
+    let mut header = String::new();
+    BufReader::new(f).read_line(&mut header)?;
+    if header.trim_end() == CSV_HEADER {
+        return Ok(());
+    }
+    Err(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!(
+            "{} has other columns than iobench writes now; use a new file",
+            path.to_string_lossy()
+        ),
+    ))
+}
+
+/// Check before a run that its records can be appended to the CSV file, if
+/// they are to be.
+pub fn check_csv(options: &OutputArgs) -> Result<(), std::io::Error> {
+    if options.output != OutputFormat::Csv {
+        return Ok(());
+    }
+    let path = options
+        .output_file
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("iobench.csv"));
+    match File::open(&path) {
+        Ok(f) if f.metadata()?.len() > 0 => check_csv_header(&path, &f),
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+const FILE_CSV_HEADER: &str = "run,path,thread,size,open_ns,read_ns,mb_per_s,failed";
+
+/// Write a row for every file read in each pass to `path`, returning the
+/// number of rows written. MB/s is over the time to open and read the file.
+pub fn write_file_csv(path: &Path, passes: &[PassTrace]) -> Result<usize, std::io::Error> {
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "{FILE_CSV_HEADER}")?;
+    let mut rows = 0;
+    for (run, pass) in passes.iter().enumerate() {
+        for event in &pass.events {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{:.3},{}",
+                run + 1,
+                csv_field(&event.path.to_string_lossy()),
+                event.thread,
+                event.bytes,
+                event.open_ns,
+                event.duration_ns.saturating_sub(event.open_ns),
+                event.bytes as f64 * 1e3 / event.duration_ns.max(1) as f64,
+                event.failed,
+            )?;
+            rows += 1;
+        }
+    }
+    out.flush()?;
+    Ok(rows)
+}
+
+/// Quote a CSV field if it contains separators, quotes or line breaks.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Print the percentiles of per-item latencies for a phase.
+pub fn print_latency(phase: &str, latency: &Histogram) {
+    if latency.count() == 0 {
+        return;
+    }
+    report!(
+        "-- {phase} latency: p50 {}   p90 {}   p99 {}   p99.9 {}   max {}",
+        format_latency(latency.percentile(50.0)),
+        format_latency(latency.percentile(90.0)),
+        format_latency(latency.percentile(99.0)),
+        format_latency(latency.percentile(99.9)),
+        format_latency(latency.max()),
+    );
+}
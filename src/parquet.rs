@@ -0,0 +1,344 @@
+//! Per-file records of the measured passes (`--records-out`) as a Parquet
+//! file, for loading into pandas or Polars.
+//!
+//! Only what these records need is implemented: required columns, PLAIN
+//! encoding, no compression, and a data page per column in each row group.
+//! The file metadata is written with the Thrift compact protocol.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+    time::Instant,
+};
+
+use crate::trace::PassTrace;
+
+const MAGIC: &[u8] = b"PAR1";
+
+/// Most rows in a row group; each pass starts a new one too.
+const ROW_GROUP_ROWS: usize = 1 << 20;
+
+/// Parquet physical types.
+#[derive(Clone, Copy)]
+enum Type {
+    Boolean = 0,
+    Int32 = 1,
+    Int64 = 2,
+    ByteArray = 6,
+}
+
+/// The columns written, in order.
+const COLUMNS: [(&str, Type); 8] = [
+    ("run", Type::Int32),
+    ("path", Type::ByteArray),
+    ("thread", Type::Int32),
+    ("start_ns", Type::Int64),
+    ("size", Type::Int64),
+    ("open_ns", Type::Int64),
+    ("read_ns", Type::Int64),
+    ("failed", Type::Boolean),
+];
+
+/// One file read, as the row written for it.
+struct Row<'a> {
+    run: i32,
+    path: &'a str,
+    thread: i32,
+    start_ns: i64,
+    size: i64,
+    open_ns: i64,
+    read_ns: i64,
+    failed: bool,
+}
+
+/// Write a row for every file read in each pass to `path`, with start times
+/// counted from the first file started, returning the number of rows written.
+pub fn write_records(path: &Path, passes: &[PassTrace]) -> Result<usize, std::io::Error> {
+    let epoch = passes
+        .iter()
+        .flat_map(|pass| pass.events.iter().map(|event| event.start))
+        .min()
+        .unwrap_or_else(Instant::now);
+    let paths = passes
+        .iter()
+        .map(|pass| {
+            pass.events
+                .iter()
+                .map(|event| event.path.to_string_lossy())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut out = BufWriter::new(File::create(path)?);
+    out.write_all(MAGIC)?;
+    let mut offset = MAGIC.len() as i64;
+    let mut row_groups = Vec::new();
+    let mut total_rows = 0;
+    for (run, (pass, paths)) in passes.iter().zip(&paths).enumerate() {
+        let rows = pass
+            .events
+            .iter()
+            .zip(paths)
+            .map(|(event, path)| Row {
+                run: run as i32 + 1,
+                path,
+                thread: event.thread as i32,
+                start_ns: (event.start - epoch).as_nanos() as i64,
+                size: event.bytes as i64,
+                open_ns: event.open_ns as i64,
+                read_ns: event.duration_ns.saturating_sub(event.open_ns) as i64,
+                failed: event.failed,
+            })
+            .collect::<Vec<_>>();
+        for group in rows.chunks(ROW_GROUP_ROWS) {
+            let mut chunks = Vec::new();
+            for (column, &(name, kind)) in COLUMNS.iter().enumerate() {
+                let data = encode_column(column, group);
+                let header = page_header(data.len(), group.len());
+                out.write_all(&header)?;
+                out.write_all(&data)?;
+                chunks.push(ColumnChunk {
+                    name,
+                    kind,
+                    rows: group.len(),
+                    offset,
+                    size: (header.len() + data.len()) as i64,
+                });
+                offset += (header.len() + data.len()) as i64;
+            }
+            total_rows += group.len();
+            row_groups.push(chunks);
+        }
+    }
+    let footer = file_metadata(&row_groups, total_rows);
+    out.write_all(&footer)?;
+    out.write_all(&(footer.len() as u32).to_le_bytes())?;
+    out.write_all(MAGIC)?;
+    out.flush()?;
+    Ok(total_rows)
+}
+
+/// The values of one column of a row group, PLAIN encoded.
+fn encode_column(column: usize, rows: &[Row]) -> Vec<u8> {
+    let mut data = Vec::new();
+    match column {
+        0 => rows
+            .iter()
+            .for_each(|row| data.extend(row.run.to_le_bytes())),
+        1 => rows.iter().for_each(|row| {
+            data.extend((row.path.len() as u32).to_le_bytes());
+            data.extend(row.path.as_bytes());
+        }),
+        2 => rows
+            .iter()
+            .for_each(|row| data.extend(row.thread.to_le_bytes())),
+        3 => rows
+            .iter()
+            .for_each(|row| data.extend(row.start_ns.to_le_bytes())),
+        4 => rows
+            .iter()
+            .for_each(|row| data.extend(row.size.to_le_bytes())),
+        5 => rows
+            .iter()
+            .for_each(|row| data.extend(row.open_ns.to_le_bytes())),
+        6 => rows
+            .iter()
+            .for_each(|row| data.extend(row.read_ns.to_le_bytes())),
+        // Booleans are packed eight to a byte, the first in the lowest bit.
+        _ => {
+            data.resize(rows.len().div_ceil(8), 0);
+            for (i, row) in rows.iter().enumerate() {
+                data[i / 8] |= u8::from(row.failed) << (i % 8);
+            }
+        }
+    }
+    data
+}
+
+/// Where a column chunk was written, for the file metadata.
+struct ColumnChunk {
+    name: &'static str,
+    kind: Type,
+    rows: usize,
+    /// Offset of its data page header in the file.
+    offset: i64,
+    /// Size of the page with its header.
+    size: i64,
+}
+
+/// Header of an uncompressed DATA_PAGE with `values` PLAIN encoded values.
+/// The columns are all required and not nested, so there are no levels.
+fn page_header(size: usize, values: usize) -> Vec<u8> {
+    let mut w = Compact::default();
+    w.i32(1, 0); // type: DATA_PAGE
+    w.i32(2, size as i32); // uncompressed_page_size
+    w.i32(3, size as i32); // compressed_page_size
+    w.begin_struct(5); // data_page_header
+    w.i32(1, values as i32); // num_values
+    w.i32(2, 0); // encoding: PLAIN
+    w.i32(3, 3); // definition_level_encoding: RLE
+    w.i32(4, 3); // repetition_level_encoding: RLE
+    w.end_struct();
+    w.stop();
+    w.out
+}
+
+fn file_metadata(row_groups: &[Vec<ColumnChunk>], rows: usize) -> Vec<u8> {
+    let mut w = Compact::default();
+    w.i32(1, 1); // version
+    w.begin_list(2, COLUMNS.len() + 1); // schema
+    w.begin_element();
+    w.binary(4, b"schema"); // name
+    w.i32(5, COLUMNS.len() as i32); // num_children
+    w.end_element();
+    for (name, kind) in COLUMNS {
+        w.begin_element();
+        w.i32(1, kind as i32); // type
+        w.i32(3, 0); // repetition_type: REQUIRED
+        w.binary(4, name.as_bytes()); // name
+        if matches!(kind, Type::ByteArray) {
+            w.i32(6, 0); // converted_type: UTF8
+            w.begin_struct(10); // logicalType
+            w.begin_struct(1); // STRING
+            w.end_struct();
+            w.end_struct();
+        }
+        w.end_element();
+    }
+    w.i64(3, rows as i64); // num_rows
+    w.begin_list(4, row_groups.len()); // row_groups
+    for chunks in row_groups {
+        w.begin_element();
+        w.begin_list(1, chunks.len()); // columns
+        for chunk in chunks {
+            w.begin_element();
+            w.i64(2, chunk.offset); // file_offset
+            w.begin_struct(3); // meta_data
+            w.i32(1, chunk.kind as i32); // type
+            w.begin_list_of(2, 1, Compact::I32); // encodings
+            w.zigzag(0); // PLAIN
+            w.begin_list_of(3, 1, Compact::BINARY); // path_in_schema
+            w.raw_binary(chunk.name.as_bytes());
+            w.i32(4, 0); // codec: UNCOMPRESSED
+            w.i64(5, chunk.rows as i64); // num_values
+            w.i64(6, chunk.size); // total_uncompressed_size
+            w.i64(7, chunk.size); // total_compressed_size
+            w.i64(9, chunk.offset); // data_page_offset
+            w.end_struct();
+            w.end_element();
+        }
+        let size = chunks.iter().map(|chunk| chunk.size).sum::<i64>();
+        w.i64(2, size); // total_byte_size
+        w.i64(3, chunks.first().map_or(0, |chunk| chunk.rows) as i64); // num_rows
+        w.end_element();
+    }
+    w.binary(
+        6,
+        format!("iobench version {}", env!("CARGO_PKG_VERSION")).as_bytes(),
+    ); // created_by
+    w.stop();
+    w.out
+}
+
+/// Writer for the Thrift compact protocol, keeping track of the last field id
+/// of each struct being written since field ids are written as deltas.
+#[derive(Default)]
+struct Compact {
+    out: Vec<u8>,
+    last_field: i16,
+    outer_fields: Vec<i16>,
+}
+
+impl Compact {
+    const I32: u8 = 5;
+    const I64: u8 = 6;
+    const BINARY: u8 = 8;
+    const LIST: u8 = 9;
+    const STRUCT: u8 = 12;
+
+    fn varint(&mut self, mut n: u64) {
+        while n >= 0x80 {
+            self.out.push(n as u8 | 0x80);
+            n >>= 7;
+        }
+        self.out.push(n as u8);
+    }
+
+    fn zigzag(&mut self, n: i64) {
+        self.varint(((n << 1) ^ (n >> 63)) as u64);
+    }
+
+    fn field(&mut self, id: i16, kind: u8) {
+        let delta = id - self.last_field;
+        if (1..=15).contains(&delta) {
+            self.out.push((delta as u8) << 4 | kind);
+        } else {
+            self.out.push(kind);
+            self.zigzag(i64::from(id));
+        }
+        self.last_field = id;
+    }
+
+    fn i32(&mut self, id: i16, n: i32) {
+        self.field(id, Self::I32);
+        self.zigzag(i64::from(n));
+    }
+
+    fn i64(&mut self, id: i16, n: i64) {
+        self.field(id, Self::I64);
+        self.zigzag(n);
+    }
+
+    fn binary(&mut self, id: i16, bytes: &[u8]) {
+        self.field(id, Self::BINARY);
+        self.raw_binary(bytes);
+    }
+
+    /// A binary value without a field header, as a list element.
+    fn raw_binary(&mut self, bytes: &[u8]) {
+        self.varint(bytes.len() as u64);
+        self.out.extend(bytes);
+    }
+
+    fn begin_struct(&mut self, id: i16) {
+        self.field(id, Self::STRUCT);
+        self.begin_element();
+    }
+
+    fn end_struct(&mut self) {
+        self.stop();
+        self.last_field = self.outer_fields.pop().unwrap_or(0);
+    }
+
+    /// Start a struct that is an element of a list.
+    fn begin_element(&mut self) {
+        self.outer_fields.push(self.last_field);
+        self.last_field = 0;
+    }
+
+    fn end_element(&mut self) {
+        self.end_struct();
+    }
+
+    /// Start a list field of `len` structs; each is written between
+    /// `begin_element` and `end_element`.
+    fn begin_list(&mut self, id: i16, len: usize) {
+        self.begin_list_of(id, len, Self::STRUCT);
+    }
+
+    fn begin_list_of(&mut self, id: i16, len: usize, kind: u8) {
+        self.field(id, Self::LIST);
+        if len < 15 {
+            self.out.push((len as u8) << 4 | kind);
+        } else {
+            self.out.push(0xf0 | kind);
+            self.varint(len as u64);
+        }
+    }
+
+    /// End the current struct's fields.
+    fn stop(&mut self) {
+        self.out.push(0);
+    }
+}
@@ -0,0 +1,257 @@
+//! Charts of saved results: throughput against thread count, and latency
+//! CDFs, drawn as SVG from the JSON records of `read-tree --output json`.
+
+use std::{fmt::Write as _, path::PathBuf};
+
+use clap::{Args, ValueEnum};
+use tracing::error;
+
+use crate::{
+    chart::{self, COLORS, HEIGHT, LEFT, PLOT_HEIGHT, PLOT_WIDTH, TOP},
+    output::{load_records, SavedRun},
+    units::{format_latency, format_size},
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ChartKind {
+    /// Mean MB/s for each thread count, a line per file and buffer size.
+    #[default]
+    Throughput,
+    /// Cumulative distribution of per-file latency, a curve per run.
+    Latency,
+}
+
+impl ChartKind {
+    fn name(self) -> &'static str {
+        match self {
+            ChartKind::Throughput => "throughput",
+            ChartKind::Latency => "latency",
+        }
+    }
+}
+
+/// Options for the `plot` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct PlotArgs {
+    /// Chart to draw.
+    #[arg(long, value_enum, default_value_t = ChartKind::Throughput)]
+    pub chart: ChartKind,
+    /// SVG file to write the chart to.
+    #[arg(short, long, value_name = "PATH", default_value = "iobench.svg")]
+    pub output: PathBuf,
+    /// Result files written by read-tree --output json (or the output of --quiet).
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+}
+
+/// The runs loaded from one results file, and the name it is shown by.
+struct Source {
+    name: String,
+    runs: Vec<SavedRun>,
+}
+
+pub fn plot(options: &PlotArgs) {
+    if options
+        .output
+        .extension()
+        .is_some_and(|ext| !ext.eq_ignore_ascii_case("svg"))
+    {
+        error!(
+            "charts can only be written as SVG, not {}; convert the SVG with e.g. rsvg-convert for other formats",
+            options.output.to_string_lossy()
+        );
+        std::process::exit(1);
+    }
+    let mut sources = Vec::new();
+    for path in &options.files {
+        match load_records(path) {
+            Ok(runs) => sources.push(Source {
+                name: path
+                    .file_name()
+                    .map_or_else(|| path.to_string_lossy(), |name| name.to_string_lossy())
+                    .into_owned(),
+                runs,
+            }),
+            Err(err) => {
+                error!("error reading results {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    }
+    let runs = sources
+        .iter()
+        .map(|source| source.runs.len())
+        .sum::<usize>();
+    if runs == 0 {
+        error!("no runs found in {:?}", options.files);
+        std::process::exit(1);
+    }
+
+    let svg = match options.chart {
+        ChartKind::Throughput => throughput_chart(&sources),
+        ChartKind::Latency => latency_chart(&sources).unwrap_or_else(|| {
+            error!("the results have no latency percentiles to plot");
+            std::process::exit(1);
+        }),
+    };
+    if let Err(err) = std::fs::write(&options.output, svg) {
+        error!("error writing {}: {err}", options.output.to_string_lossy());
+        std::process::exit(1);
+    }
+    println!(
+        "-- plot: {} chart of {runs} runs from {} files written to {:?}",
+        options.chart.name(),
+        sources.len(),
+        options.output,
+    );
+}
+
+/// Mean MB/s against thread count, with the thread counts evenly spaced.
+fn throughput_chart(sources: &[Source]) -> String {
+    let mut thread_counts = sources
+        .iter()
+        .flat_map(|source| source.runs.iter().map(|run| run.threads))
+        .collect::<Vec<_>>();
+    thread_counts.sort_unstable();
+    thread_counts.dedup();
+
+    // A line for each buffer size in each file, with a point per thread count.
+    let mut lines = Vec::new();
+    for source in sources {
+        let mut buf_sizes = source
+            .runs
+            .iter()
+            .map(|run| run.buf_size)
+            .collect::<Vec<_>>();
+        buf_sizes.sort_unstable();
+        buf_sizes.dedup();
+        for &buf_size in &buf_sizes {
+            let points = thread_counts
+                .iter()
+                .enumerate()
+                .filter_map(|(i, &threads)| {
+                    let values = source
+                        .runs
+                        .iter()
+                        .filter(|run| run.threads == threads && run.buf_size == buf_size)
+                        .map(SavedRun::mb_per_s)
+                        .collect::<Vec<_>>();
+                    (!values.is_empty())
+                        .then(|| (i, values.iter().sum::<f64>() / values.len() as f64))
+                })
+                .collect::<Vec<_>>();
+            let label = if buf_sizes.len() > 1 {
+                format!("{} ({} buffer)", source.name, format_size(buf_size))
+            } else {
+                source.name.clone()
+            };
+            lines.push((label, points));
+        }
+    }
+
+    let peak = lines
+        .iter()
+        .flat_map(|(_, points)| points.iter().map(|&(_, mb_per_s)| mb_per_s))
+        .fold(0.0, f64::max);
+    let mut svg = String::new();
+    let y_max = chart::axes(&mut svg, peak, "MB/s");
+    let x = |i: usize| LEFT + PLOT_WIDTH * (i as f64 + 0.5) / thread_counts.len() as f64;
+    let y = |mb_per_s: f64| TOP + PLOT_HEIGHT * (1.0 - mb_per_s / y_max);
+    for (i, threads) in thread_counts.iter().enumerate() {
+        let _ = writeln!(
+            svg,
+            "<text x=\"{:.1}\" y=\"{:.1}\" text-anchor=\"middle\">{threads}</text>",
+            x(i),
+            HEIGHT - chart::BOTTOM + 18.0,
+        );
+    }
+    chart::x_title(&mut svg, "threads");
+    for (line, (label, points)) in lines.iter().enumerate() {
+        let color = COLORS[line % COLORS.len()];
+        let coords = points
+            .iter()
+            .map(|&(i, mb_per_s)| format!("{:.1},{:.1}", x(i), y(mb_per_s)))
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"2\" points=\"{}\"/>",
+            coords.join(" "),
+        );
+        for &(i, mb_per_s) in points {
+            let _ = writeln!(
+                svg,
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3.5\" fill=\"{color}\"><title>{}: {} threads, {mb_per_s:.0} MB/s</title></circle>",
+                x(i),
+                y(mb_per_s),
+                chart::escape(label),
+                thread_counts[i],
+            );
+        }
+        chart::legend(&mut svg, line, label);
+    }
+    chart::finish(svg)
+}
+
+/// Percentile against latency on a log scale, or None if no run has latencies.
+fn latency_chart(sources: &[Source]) -> Option<String> {
+    let curves = sources
+        .iter()
+        .flat_map(|source| {
+            source.runs.iter().enumerate().map(move |(run, saved)| {
+                let label = if sources.len() > 1 {
+                    format!(
+                        "{} run {} ({} threads)",
+                        source.name,
+                        run + 1,
+                        saved.threads
+                    )
+                } else {
+                    format!("run {} ({} threads)", run + 1, saved.threads)
+                };
+                (label, &saved.latency)
+            })
+        })
+        .filter(|(_, latency)| !latency.is_empty())
+        .collect::<Vec<_>>();
+    let all = curves.iter().flat_map(|(_, latency)| latency.iter());
+    let lowest = all.clone().map(|&(_, ns)| ns.max(1)).min()?;
+    let highest = all.map(|&(_, ns)| ns.max(1)).max()?;
+    // Whole decades of nanoseconds, at least one.
+    let low_decade = (lowest as f64).log10().floor() as i32;
+    let high_decade = ((highest as f64).log10().ceil() as i32).max(low_decade + 1);
+
+    let mut svg = String::new();
+    chart::axes(&mut svg, 100.0, "percentile");
+    let x = |ns: u64| {
+        let decades = (ns.max(1) as f64).log10() - f64::from(low_decade);
+        LEFT + PLOT_WIDTH * decades / f64::from(high_decade - low_decade)
+    };
+    let y = |percentile: f64| TOP + PLOT_HEIGHT * (1.0 - percentile / 100.0);
+    for decade in low_decade..=high_decade {
+        let ns = 10u64.pow(decade as u32);
+        let _ = writeln!(
+            svg,
+            "<line class=\"grid\" x1=\"{0:.1}\" y1=\"{TOP}\" x2=\"{0:.1}\" y2=\"{1:.1}\"/>\n<text x=\"{0:.1}\" y=\"{2:.1}\" text-anchor=\"middle\">{3}</text>",
+            x(ns),
+            TOP + PLOT_HEIGHT,
+            HEIGHT - chart::BOTTOM + 18.0,
+            format_latency(ns),
+        );
+    }
+    chart::x_title(&mut svg, "time to open and read a file");
+    for (curve, (label, latency)) in curves.iter().enumerate() {
+        let color = COLORS[curve % COLORS.len()];
+        let coords = latency
+            .iter()
+            .map(|&(percentile, ns)| format!("{:.1},{:.1}", x(ns), y(percentile)))
+            .collect::<Vec<_>>();
+        let _ = writeln!(
+            svg,
+            "<polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"2\" points=\"{}\"><title>{}</title></polyline>",
+            coords.join(" "),
+            chart::escape(label),
+        );
+        chart::legend(&mut svg, curve, label);
+    }
+    Some(chart::finish(svg))
+}
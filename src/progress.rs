@@ -0,0 +1,109 @@
+//! Progress line on stderr for long runs, redrawn in place a few times a
+//! second, so there is feedback before the summary at the end.
+
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// How often the line is redrawn at most.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(200);
+/// Width of the bar, in characters.
+const BAR_WIDTH: usize = 30;
+
+pub struct ProgressBar {
+    /// What is being counted, e.g. "files" or "files listed".
+    noun: &'static str,
+    /// Number of items expected, if known, for the bar and the ETA.
+    total: Option<u64>,
+    start: Instant,
+    /// Time and byte count at the last redraw, for the current rate.
+    last: Option<(Instant, u64)>,
+    /// Throughput between the last two redraws, in MB/s.
+    rate: f64,
+}
+
+impl ProgressBar {
+    pub fn new(noun: &'static str, total: Option<u64>) -> Self {
+        Self {
+            noun,
+            total,
+            start: Instant::now(),
+            last: None,
+            rate: 0.0,
+        }
+    }
+
+    /// Redraw the line for `done` items and, if they are counted, `bytes`,
+    /// unless it was redrawn very recently.
+    pub fn update(&mut self, done: u64, bytes: Option<u64>) {
+        let now = Instant::now();
+        if let Some((last_time, last_bytes)) = self.last {
+            let interval = now - last_time;
+            if interval < REDRAW_INTERVAL {
+                return;
+            }
+            let bytes = bytes.unwrap_or(0);
+            self.rate = bytes.saturating_sub(last_bytes) as f64 / 1e6 / interval.as_secs_f64();
+        }
+        self.last = Some((now, bytes.unwrap_or(0)));
+
+        let mut line = String::new();
+        match self.total {
+            Some(total) => {
+                let fraction = if total > 0 {
+                    (done as f64 / total as f64).min(1.0)
+                } else {
+                    1.0
+                };
+                let filled = (fraction * BAR_WIDTH as f64) as usize;
+                line.push_str(&format!(
+                    "[{}{}] {done}/{total} {}",
+                    "=".repeat(filled),
+                    " ".repeat(BAR_WIDTH - filled),
+                    self.noun,
+                ));
+            }
+            None => line.push_str(&format!("{done} {}", self.noun)),
+        }
+        if let Some(bytes) = bytes {
+            line.push_str(&format!(
+                "   {:.1} MB   {:.0} MB/s",
+                bytes as f64 / 1e6,
+                self.rate
+            ));
+        } else {
+            let per_s = done as f64 / (now - self.start).as_secs_f64();
+            line.push_str(&format!("   {per_s:.0}/s"));
+        }
+        if let Some(total) = self.total.filter(|_| done > 0) {
+            let elapsed = (now - self.start).as_secs_f64();
+            let remaining = elapsed * total.saturating_sub(done) as f64 / done as f64;
+            line.push_str(&format!("   ETA {}", format_eta(remaining)));
+        }
+        let mut stderr = io::stderr().lock();
+        let _ = write!(stderr, "\r\x1b[K{line}");
+        let _ = stderr.flush();
+    }
+
+    /// Erase the line, before printing something else or when done.
+    pub fn clear(&self) {
+        if self.last.is_some() {
+            let mut stderr = io::stderr().lock();
+            let _ = write!(stderr, "\r\x1b[K");
+            let _ = stderr.flush();
+        }
+    }
+}
+
+/// Format a number of seconds remaining as e.g. 1h02m, 3m05s or 12s.
+fn format_eta(secs: f64) -> String {
+    let secs = secs.round() as u64;
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{secs}s")
+    }
+}
@@ -0,0 +1,179 @@
+//! Random read benchmark: block-sized reads at random offsets within large files.
+
+use std::{
+    fs::File,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::Args;
+use tracing::debug;
+
+use crate::{
+    histogram::Histogram,
+    rng::Rng,
+    units::{format_latency, parse_size},
+};
+
+/// Options for the `rand-read` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct RandReadArgs {
+    /// Files to read from.
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+    /// Size of each read (e.g. 4k, 1M).
+    #[arg(short, long, default_value = "4k", value_parser = parse_size)]
+    pub block_size: u64,
+    /// Number of reads kept in flight concurrently (one thread each).
+    #[arg(short = 'q', long, default_value_t = 16)]
+    pub io_depth: u32,
+    /// Total number of reads to perform.
+    #[arg(short = 'n', long, default_value_t = 100_000)]
+    pub ops: u64,
+    /// Seed for the random offsets.
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LatencyStats {
+    pub bytes: u64,
+    pub errors: u64,
+    /// Latency of each successful operation, in nanoseconds.
+    pub latency: Histogram,
+}
+
+impl LatencyStats {
+    pub fn combine(mut self, other: Self) -> Self {
+        self.bytes += other.bytes;
+        self.errors += other.errors;
+        self.latency.merge(&other.latency);
+        self
+    }
+}
+
+struct Target {
+    file: File,
+    path: PathBuf,
+    /// Number of whole blocks in the file.
+    blocks: u64,
+}
+
+pub fn rand_read(options: &RandReadArgs) {
+    let block_size = options.block_size.max(1);
+    let targets = options
+        .files
+        .iter()
+        .filter_map(|path| match open_target(path, block_size) {
+            Ok(target) => Some(target),
+            Err(err) => {
+                debug!("error opening file {}: {err}", path.to_string_lossy());
+                None
+            }
+        })
+        .filter(|target| {
+            if target.blocks == 0 {
+                debug!(
+                    "skipping file smaller than block size: {}",
+                    target.path.to_string_lossy()
+                );
+            }
+            target.blocks > 0
+        })
+        .collect::<Vec<_>>();
+    if targets.is_empty() {
+        println!("-- rand-read: no usable files (each must be at least one block)");
+        return;
+    }
+
+    let io_depth = u64::from(options.io_depth.max(1));
+    println!(
+        "-- random reads of {block_size} bytes from {} files with io depth {io_depth}",
+        targets.len(),
+    );
+
+    let seed = options.seed.unwrap_or_else(|| Rng::from_time().next_u64());
+    let t1 = Instant::now();
+    let stats = std::thread::scope(|scope| {
+        let workers = (0..io_depth)
+            .map(|worker| {
+                let ops = options.ops / io_depth + u64::from(worker < options.ops % io_depth);
+                let targets = &targets;
+                scope.spawn(move || {
+                    let mut rng = Rng::new(seed.wrapping_add(worker));
+                    read_blocks(targets, block_size, ops, &mut rng)
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("rand-read worker panicked"))
+            .fold(LatencyStats::default(), LatencyStats::combine)
+    });
+    let t2 = Instant::now();
+    print_iops("rand-read", "reads", &stats, (t2 - t1).as_secs_f64());
+}
+
+/// Print the IOPS, throughput and latency of a random I/O run that took `dur_s` seconds.
+pub fn print_iops(name: &str, ops_noun: &str, stats: &LatencyStats, dur_s: f64) {
+    let total_size_mb = stats.bytes as f64 / 1_000_000.0;
+    let ops = stats.latency.count();
+    println!(
+        "-- {name}: {:.0} IOPS   {:.0} MB/s  ({} {ops_noun}, {} MB in {} s)",
+        ops as f64 / dur_s,
+        total_size_mb / dur_s,
+        ops,
+        total_size_mb,
+        dur_s,
+    );
+    if ops > 0 {
+        let latency = &stats.latency;
+        println!(
+            "-- latency: avg {}   min {}   p50 {}   p99 {}   p99.9 {}   max {}",
+            format_latency(latency.mean() as u64),
+            format_latency(latency.min()),
+            format_latency(latency.percentile(50.0)),
+            format_latency(latency.percentile(99.0)),
+            format_latency(latency.percentile(99.9)),
+            format_latency(latency.max()),
+        );
+    }
+    if stats.errors > 0 {
+        println!("-- errors: {}", stats.errors);
+    }
+}
+
+fn open_target(path: &Path, block_size: u64) -> Result<Target, std::io::Error> {
+    let file = File::open(path)?;
+    let size = file.metadata()?.len();
+    Ok(Target {
+        file,
+        path: path.to_path_buf(),
+        blocks: size / block_size,
+    })
+}
+
+fn read_blocks(targets: &[Target], block_size: u64, ops: u64, rng: &mut Rng) -> LatencyStats {
+    let mut stats = LatencyStats::default();
+    let mut buf = vec![0; block_size as usize];
+    for _ in 0..ops {
+        let target = &targets[rng.range_inclusive(0, targets.len() as u64 - 1) as usize];
+        let offset = rng.range_inclusive(0, target.blocks - 1) * block_size;
+        let t1 = Instant::now();
+        match target.file.read_at(&mut buf, offset) {
+            Ok(n) => {
+                stats.latency.record(t1.elapsed().as_nanos() as u64);
+                stats.bytes += n as u64;
+            }
+            Err(err) => {
+                stats.errors += 1;
+                debug!(
+                    "error reading {} at offset {offset}: {err}",
+                    target.path.to_string_lossy()
+                );
+            }
+        }
+    }
+    stats
+}
@@ -0,0 +1,212 @@
+//! Random write benchmark: block-sized writes at random offsets within
+//! preallocated files.
+
+use std::{
+    fs::{File, OpenOptions},
+    io,
+    os::{
+        fd::AsRawFd,
+        unix::fs::{FileExt, OpenOptionsExt},
+    },
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::{Args, ValueEnum};
+use tracing::{debug, error};
+
+use crate::{
+    rand_read::{print_iops, LatencyStats},
+    rng::Rng,
+    units::{format_size, parse_size},
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteSync {
+    /// Leave it to the kernel to write back the data.
+    #[default]
+    None,
+    /// Call fsync after each write.
+    Fsync,
+    /// Call fdatasync after each write.
+    Fdatasync,
+    /// Open the files with O_DSYNC, so each write returns once it is stable.
+    Dsync,
+}
+
+impl WriteSync {
+    fn name(self) -> &'static str {
+        match self {
+            WriteSync::None => "none",
+            WriteSync::Fsync => "fsync",
+            WriteSync::Fdatasync => "fdatasync",
+            WriteSync::Dsync => "O_DSYNC",
+        }
+    }
+}
+
+/// Options for the `rand-write` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct RandWriteArgs {
+    /// Files to write to, created and preallocated to --file-size; existing
+    /// files are only written to with --overwrite.
+    #[arg(required = true)]
+    pub files: Vec<PathBuf>,
+    /// Size to preallocate files to (e.g. 1G).
+    #[arg(long, default_value = "1G", value_parser = parse_size)]
+    pub file_size: u64,
+    /// Size of each write (e.g. 4k, 1M).
+    #[arg(short, long, default_value = "4k", value_parser = parse_size)]
+    pub block_size: u64,
+    /// Number of writes kept in flight concurrently (one thread each).
+    #[arg(short = 'q', long, default_value_t = 16)]
+    pub io_depth: u32,
+    /// Total number of writes to perform.
+    #[arg(short = 'n', long, default_value_t = 100_000)]
+    pub ops: u64,
+    /// How each write is made durable.
+    #[arg(long, value_enum, default_value_t = WriteSync::None)]
+    pub sync: WriteSync,
+    /// Seed for the random offsets and data.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Write into files that exist already, extending those shorter than
+    /// --file-size, which destroys their contents.
+    #[arg(long)]
+    pub overwrite: bool,
+}
+
+struct Target {
+    file: File,
+    path: PathBuf,
+    /// Number of whole blocks in the file.
+    blocks: u64,
+}
+
+pub fn rand_write(options: &RandWriteArgs) {
+    let block_size = options.block_size.max(1);
+    let mut targets = Vec::new();
+    for path in &options.files {
+        match open_target(path, options, block_size) {
+            Ok(target) => targets.push(target),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                error!(
+                    "{} exists; give --overwrite to write random blocks over its contents",
+                    path.to_string_lossy()
+                );
+                std::process::exit(1);
+            }
+            Err(err) => {
+                error!("error preparing {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    }
+    if targets.iter().any(|target| target.blocks == 0) {
+        error!("--file-size must be at least one block");
+        std::process::exit(1);
+    }
+
+    let io_depth = u64::from(options.io_depth.max(1));
+    println!(
+        "-- random writes of {block_size} bytes to {} files of at least {} with io depth {io_depth} (sync: {})",
+        targets.len(),
+        format_size(options.file_size),
+        options.sync.name(),
+    );
+
+    let seed = options.seed.unwrap_or_else(|| Rng::from_time().next_u64());
+    let t1 = Instant::now();
+    let stats = std::thread::scope(|scope| {
+        let workers = (0..io_depth)
+            .map(|worker| {
+                let ops = options.ops / io_depth + u64::from(worker < options.ops % io_depth);
+                let targets = &targets;
+                scope.spawn(move || {
+                    let mut rng = Rng::new(seed.wrapping_add(worker));
+                    write_blocks(targets, block_size, ops, options.sync, &mut rng)
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("rand-write worker panicked"))
+            .fold(LatencyStats::default(), LatencyStats::combine)
+    });
+    let t2 = Instant::now();
+    print_iops("rand-write", "writes", &stats, (t2 - t1).as_secs_f64());
+}
+
+/// Open a file for writing, preallocating it to the requested size first.
+fn open_target(path: &Path, options: &RandWriteArgs, block_size: u64) -> Result<Target, io::Error> {
+    let mut open_options = OpenOptions::new();
+    if options.overwrite {
+        open_options.write(true).create(true).truncate(false);
+    } else {
+        open_options.write(true).create_new(true);
+    }
+    if options.sync == WriteSync::Dsync {
+        open_options.custom_flags(libc::O_DSYNC);
+    }
+    let file = open_options.open(path)?;
+    let mut size = file.metadata()?.len();
+    if size < options.file_size {
+        debug!(
+            "preallocating {} to {}",
+            path.to_string_lossy(),
+            format_size(options.file_size)
+        );
+        let len = libc::off_t::try_from(options.file_size)
+            .map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+        // SAFETY: fd is an open file; posix_fallocate returns an error number.
+        let err = unsafe { libc::posix_fallocate(file.as_raw_fd(), 0, len) };
+        if err != 0 {
+            return Err(io::Error::from_raw_os_error(err));
+        }
+        size = options.file_size;
+    }
+    Ok(Target {
+        file,
+        path: path.to_path_buf(),
+        blocks: size / block_size,
+    })
+}
+
+fn write_blocks(
+    targets: &[Target],
+    block_size: u64,
+    ops: u64,
+    sync: WriteSync,
+    rng: &mut Rng,
+) -> LatencyStats {
+    let mut stats = LatencyStats::default();
+    let mut buf = vec![0; block_size as usize];
+    rng.fill_bytes(&mut buf);
+    for _ in 0..ops {
+        let target = &targets[rng.range_inclusive(0, targets.len() as u64 - 1) as usize];
+        let offset = rng.range_inclusive(0, target.blocks - 1) * block_size;
+        let t1 = Instant::now();
+        let result = target
+            .file
+            .write_all_at(&buf, offset)
+            .and_then(|()| match sync {
+                WriteSync::Fsync => target.file.sync_all(),
+                WriteSync::Fdatasync => target.file.sync_data(),
+                WriteSync::None | WriteSync::Dsync => Ok(()),
+            });
+        match result {
+            Ok(()) => {
+                stats.latency.record(t1.elapsed().as_nanos() as u64);
+                stats.bytes += block_size;
+            }
+            Err(err) => {
+                stats.errors += 1;
+                debug!(
+                    "error writing {} at offset {offset}: {err}",
+                    target.path.to_string_lossy()
+                );
+            }
+        }
+    }
+    stats
+}
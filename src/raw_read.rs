@@ -0,0 +1,175 @@
+//! Raw device benchmark: read a block device directly, with O_DIRECT, to
+//! measure what the device delivers without any filesystem in the way.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Seek, SeekFrom},
+    os::unix::fs::{FileExt, OpenOptionsExt},
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use clap::{Args, ValueEnum};
+use tracing::{debug, error};
+
+use crate::{
+    engine::{AlignedBuf, DIRECT_ALIGN},
+    rand_read::{print_iops, LatencyStats},
+    rng::Rng,
+    units::{format_size, parse_duration, parse_size},
+};
+
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccessPattern {
+    /// Consecutive blocks; with several threads, each reads its own region.
+    #[default]
+    Seq,
+    /// Blocks at random offsets across the device.
+    Rand,
+}
+
+/// Options for the `raw-read` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct RawReadArgs {
+    /// Block device (or file) to read, e.g. /dev/nvme0n1. Nothing is written to it.
+    pub device: PathBuf,
+    /// Whether to read sequentially or at random offsets.
+    #[arg(long, value_enum, default_value_t = AccessPattern::Seq)]
+    pub pattern: AccessPattern,
+    /// Size of each read (e.g. 4k, 1M); a multiple of 4k unless --buffered is given.
+    #[arg(short, long, default_value = "1M", value_parser = parse_size)]
+    pub block_size: u64,
+    /// Number of reads kept in flight concurrently (one thread each).
+    #[arg(short = 'q', long, default_value_t = 1)]
+    pub io_depth: u32,
+    /// Read only the first SIZE bytes of the device [default: all of it].
+    #[arg(long, value_parser = parse_size)]
+    pub size: Option<u64>,
+    /// Number of random reads to perform.
+    #[arg(short = 'n', long, default_value_t = 100_000)]
+    pub ops: u64,
+    /// Stop after this long (e.g. 30s), even if not everything was read.
+    #[arg(long, value_parser = parse_duration)]
+    pub duration: Option<Duration>,
+    /// Read through the page cache instead of with O_DIRECT.
+    #[arg(long)]
+    pub buffered: bool,
+    /// Seed for the random offsets.
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+pub fn raw_read(options: &RawReadArgs) {
+    let path = &options.device;
+    let block_size = options.block_size.max(1);
+    if !options.buffered && !block_size.is_multiple_of(DIRECT_ALIGN as u64) {
+        error!("--block-size must be a multiple of {DIRECT_ALIGN} with O_DIRECT");
+        std::process::exit(1);
+    }
+    let (device, size) = match open_device(options) {
+        Ok(opened) => opened,
+        Err(err) => {
+            error!("error opening {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    };
+    let size = options.size.map_or(size, |limit| limit.min(size));
+    let blocks = size / block_size;
+    if blocks == 0 {
+        error!(
+            "{} is smaller than one block ({})",
+            path.to_string_lossy(),
+            format_size(block_size)
+        );
+        std::process::exit(1);
+    }
+
+    let io_depth = u64::from(options.io_depth.max(1));
+    let pattern = match options.pattern {
+        AccessPattern::Seq => "sequential",
+        AccessPattern::Rand => "random",
+    };
+    println!(
+        "-- {pattern} reads of {} from {path:?} ({} {}) with io depth {io_depth}{}",
+        format_size(block_size),
+        if options.size.is_some() {
+            "first"
+        } else {
+            "all"
+        },
+        format_size(blocks * block_size),
+        if options.buffered { "" } else { ", O_DIRECT" },
+    );
+
+    let seed = options.seed.unwrap_or_else(|| Rng::from_time().next_u64());
+    let t1 = Instant::now();
+    let deadline = options.duration.map(|duration| t1 + duration);
+    let stats = std::thread::scope(|scope| {
+        let workers = (0..io_depth)
+            .map(|worker| {
+                let device = &device;
+                scope.spawn(move || {
+                    let mut buf = AlignedBuf::new(block_size as usize);
+                    let mut stats = LatencyStats::default();
+                    let mut next_block: Box<dyn FnMut() -> Option<u64>> = match options.pattern {
+                        AccessPattern::Seq => {
+                            // Worker w reads the w-th of io_depth equal regions.
+                            let mut block = blocks * worker / io_depth;
+                            let end = blocks * (worker + 1) / io_depth;
+                            Box::new(move || {
+                                block += 1;
+                                (block <= end).then_some(block - 1)
+                            })
+                        }
+                        AccessPattern::Rand => {
+                            let mut rng = Rng::new(seed.wrapping_add(worker));
+                            let mut ops =
+                                options.ops / io_depth + u64::from(worker < options.ops % io_depth);
+                            Box::new(move || {
+                                ops = ops.checked_sub(1)?;
+                                Some(rng.range_inclusive(0, blocks - 1))
+                            })
+                        }
+                    };
+                    while let Some(block) = next_block() {
+                        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                            break;
+                        }
+                        let offset = block * block_size;
+                        let t1 = Instant::now();
+                        match device.read_at(&mut buf, offset) {
+                            Ok(n) => {
+                                stats.latency.record(t1.elapsed().as_nanos() as u64);
+                                stats.bytes += n as u64;
+                            }
+                            Err(err) => {
+                                stats.errors += 1;
+                                debug!("error reading at offset {offset}: {err}");
+                            }
+                        }
+                    }
+                    stats
+                })
+            })
+            .collect::<Vec<_>>();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("raw-read worker panicked"))
+            .fold(LatencyStats::default(), LatencyStats::combine)
+    });
+    let t2 = Instant::now();
+    print_iops("raw-read", "reads", &stats, (t2 - t1).as_secs_f64());
+}
+
+/// Open the device read-only and find its size.
+fn open_device(options: &RawReadArgs) -> Result<(File, u64), io::Error> {
+    let mut open_options = OpenOptions::new();
+    open_options.read(true);
+    if !options.buffered {
+        open_options.custom_flags(libc::O_DIRECT);
+    }
+    let mut device = open_options.open(&options.device)?;
+    // The metadata of a block device has no size, but seeking to its end finds it.
+    let size = device.seek(SeekFrom::End(0))?;
+    Ok((device, size))
+}
@@ -0,0 +1,55 @@
+//! Read benchmark over a given set of files: read-tree without the listing,
+//! reading the paths named on stdin or in a file, such as the output of
+//! `find`.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use tracing::error;
+
+use crate::read_tree::{read_tree, ReadTreeArgs};
+
+/// Options for the `read-list` subcommand: those of `read-tree`, with the
+/// files to read taken from the list instead of a walk.
+#[derive(Args, Debug, Clone)]
+pub struct ReadListArgs {
+    /// Read the paths from FILE instead of stdin, one per line.
+    #[arg(long, value_name = "FILE")]
+    pub files_from: Option<PathBuf>,
+    /// The paths are ended by NUL bytes instead of line breaks, as written by
+    /// `find -print0`, so any path can be given.
+    #[arg(short = '0', long)]
+    pub null: bool,
+
+    #[command(flatten)]
+    pub read: ReadTreeArgs,
+}
+
+pub fn read_list(options: &ReadListArgs) {
+    let read = &options.read;
+    let walking = [
+        (
+            !read.paths.is_empty() || read.dir.is_some(),
+            "paths to read",
+        ),
+        (read.load_list.is_some(), "--load-list"),
+        (read.save_list.is_some(), "--save-list"),
+        (read.pipeline, "--pipeline"),
+        (read.walk.filters_given(), "the filtering options"),
+    ];
+    if let Some((_, what)) = walking.iter().find(|(given, _)| *given) {
+        error!("read-list reads the files named in its list, so {what} can't be given");
+        std::process::exit(1);
+    }
+    let mut read = read.clone();
+    read.files_from = Some(
+        options
+            .files_from
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("-")),
+    );
+    read.null_delimited = options.null;
+    // A path in the list names the file to read, even if it's a symlink.
+    read.walk.follow_symlinks = true;
+    read_tree(&read);
+}
@@ -0,0 +1,2726 @@
+//! Read benchmark: list a directory tree, then read every file in it.
+
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    fs::{File, Metadata},
+    io,
+    os::unix::fs::MetadataExt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc,
+    },
+    time::{Duration, Instant},
+};
+
+use clap::{Args, ValueEnum};
+use jwalk::rayon::{
+    self,
+    iter::{IntoParallelRefIterator, ParallelIterator},
+};
+use time::OffsetDateTime;
+use tracing::{debug, error, trace};
+
+use crate::{
+    baseline,
+    breakdown::{GroupBy, GroupStats, Groups},
+    cache::{self, drop_caches, readahead, CacheStat},
+    checkpoint::{Checkpoint, Progress},
+    checksum::Xxh3,
+    engine::{print_engine_report, take_first_byte, EngineArgs},
+    errors::ErrorSummary,
+    exit_code,
+    file_list::{self, FileList},
+    histogram::Histogram,
+    html_report,
+    manifest::{write_manifest, FileChecksum, DEFAULT_MANIFEST},
+    metrics,
+    otlp::{Attribute, Endpoint, Span, SpanRecorder},
+    output::{self, print_latency, report, OutputArgs, OutputFormat, RunRecord, Sample},
+    parquet,
+    progress::ProgressBar,
+    results_db,
+    rng::Rng,
+    signals,
+    stats::Summary,
+    statsd,
+    trace::{write_trace, FileEvent, PassTrace},
+    tui::{sparkline, Activity, Dashboard, Totals},
+    units::{format_latency, format_size, parse_duration, parse_percent, parse_size},
+    walk::{print_walk_report, walk_files, ListedFile, WalkArgs},
+};
+
+/// Options for the `read-tree` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct ReadTreeArgs {
+    /// The directory to read instead of CWD.
+    #[arg(short, long)]
+    pub dir: Option<String>,
+    /// Number of concurrent threads to use. A comma-separated list (e.g.
+    /// 1,2,4,8) reruns the read phase for each count and prints a scaling table.
+    #[arg(
+        short = 'j',
+        long,
+        visible_alias = "read-threads",
+        value_delimiter = ',',
+        default_value = "16"
+    )]
+    pub threads: Vec<u32>,
+    /// Number of threads for directory traversal [default: the largest -j value].
+    #[arg(long)]
+    pub list_threads: Option<u32>,
+
+    /// Stop the read phase after this long (e.g. 60s, 10m), even if not all files were read.
+    #[arg(long, value_parser = parse_duration)]
+    pub duration: Option<Duration>,
+    /// Stop starting new files once this many bytes (e.g. 100G) have been read.
+    #[arg(long, value_parser = parse_size)]
+    pub max_bytes: Option<u64>,
+    /// Stop starting new files once this many have been read.
+    #[arg(long, value_name = "N")]
+    pub max_files: Option<u64>,
+    /// Abort the run once this many files couldn't be read in a pass,
+    /// reporting what was read so far and exiting with status 4. At least 1;
+    /// --fail-on-error fails a run with any errors after it has finished.
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_errors: Option<u64>,
+    /// Retry a file this many times after a transient error (EAGAIN, ESTALE
+    /// or ETIMEDOUT) before counting it as failed.
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub retries: u32,
+    /// Wait this long before the first retry of a file, doubling it for each
+    /// further retry.
+    #[arg(long, value_parser = parse_duration, default_value = "100ms")]
+    pub retry_delay: Duration,
+    /// Give up on a file whose open or read hasn't finished after this long
+    /// (e.g. 30s), counting it as timed out. The thread stuck in it is left
+    /// behind and another one takes over its reads.
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration)]
+    pub file_timeout: Option<Duration>,
+    /// Read a random subset of this percentage of the listed files (e.g. 10%).
+    #[arg(long, value_parser = parse_percent, conflicts_with = "sample_files")]
+    pub sample: Option<f64>,
+    /// Read a random subset of this many of the listed files.
+    #[arg(long, value_name = "N", conflicts_with = "pipeline")]
+    pub sample_files: Option<u64>,
+    /// Order in which files are handed to the readers.
+    #[arg(long, value_enum, default_value_t = ReadOrder::Path, conflicts_with = "pipeline")]
+    pub order: ReadOrder,
+    /// Seed for the random choices in --sample and --order shuffle, so a run
+    /// can be repeated exactly.
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// With --max-files, also stop listing once that many files have been
+    /// found, instead of listing the whole tree.
+    #[arg(long, requires = "max_files")]
+    pub limit_listing: bool,
+    /// Read files for this long before measuring; those files are excluded from the results.
+    #[arg(long, value_parser = parse_duration)]
+    pub warmup: Option<Duration>,
+    /// Read this many files before measuring; those files are excluded from the results.
+    #[arg(long)]
+    pub warmup_files: Option<u64>,
+    /// Rerun the read phase for each buffer size: a comma-separated list
+    /// (4k,64k,1M) or a range doubling from MIN to MAX (4k..1M).
+    #[arg(long, value_parser = SizeSweep::parse)]
+    pub sweep_buf: Option<SizeSweep>,
+    /// Number of times to repeat the read phase over the same file list.
+    #[arg(long, default_value_t = 1)]
+    pub runs: u32,
+    /// Run the whole benchmark again every INTERVAL (e.g. 10m) until
+    /// interrupted, listing the tree again each time. Each round's results
+    /// are added to the output file, database and statsd as it finishes.
+    #[arg(long, value_name = "INTERVAL", value_parser = parse_duration, conflicts_with_all = ["checkpoint", "resume", "quiet"])]
+    pub watch: Option<Duration>,
+    /// Read files as they are listed instead of listing the whole tree first.
+    /// Only a single pass is possible, as the file list is never stored.
+    #[arg(long, conflicts_with_all = ["warmup", "warmup_files", "runs", "sweep_buf"])]
+    pub pipeline: bool,
+    /// Save the list of files found to FILE, so later runs can read the same
+    /// files with --load-list instead of listing the tree again.
+    #[arg(long, value_name = "FILE", conflicts_with = "pipeline")]
+    pub save_list: Option<PathBuf>,
+    /// Read the files in a list saved by --save-list instead of listing the
+    /// tree. Sampling and --order still apply to them.
+    #[arg(
+        long,
+        value_name = "FILE",
+        conflicts_with_all = [
+            "pipeline", "save_list", "paths", "dir", "include", "exclude", "min_size",
+            "max_size", "max_depth", "one_file_system", "dedup_hardlinks", "limit_listing",
+        ]
+    )]
+    pub load_list: Option<PathBuf>,
+    /// For read-list: the file to read the paths of the files from, or `-`
+    /// for stdin.
+    #[arg(skip)]
+    pub files_from: Option<PathBuf>,
+    /// For read-list: the paths there are ended by NULs, not line breaks.
+    #[arg(skip)]
+    pub null_delimited: bool,
+    /// Save the progress of the read pass to this file every
+    /// --checkpoint-interval, so an interrupted run can be continued with
+    /// --resume.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pipeline", "warmup", "warmup_files", "prefetch", "sweep_buf", "runs"])]
+    pub checkpoint: Option<PathBuf>,
+    #[arg(long, value_parser = parse_duration, default_value = "60s")]
+    pub checkpoint_interval: Duration,
+    /// Continue the run saved in this checkpoint: list and order the same
+    /// tree again, skip the files it had read, and count them in the results.
+    /// Checkpoints go on being saved to it.
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["pipeline", "warmup", "warmup_files", "prefetch", "sweep_buf", "runs", "seed"])]
+    pub resume: Option<PathBuf>,
+    /// Drop the page cache (via /proc/sys/vm/drop_caches, which needs root)
+    /// before each measured pass, so every pass reads from cold.
+    #[arg(long)]
+    pub drop_caches: bool,
+    /// Where --checksum saves the checksums of the files read in the first
+    /// measured pass [default: iobench.manifest].
+    #[arg(long, value_name = "PATH", requires = "checksum")]
+    pub manifest: Option<PathBuf>,
+    /// Issue readahead() for the next N files (default 64) from a separate
+    /// thread while the readers work, and compare each measured pass with a
+    /// pass without it.
+    #[arg(
+        long,
+        value_name = "N",
+        num_args = 0..=1,
+        default_missing_value = "64",
+        conflicts_with = "pipeline"
+    )]
+    pub prefetch: Option<usize>,
+    /// Report the page cache state of the listed files (cached, dirty and
+    /// evicted pages) before and after each measured pass, with cachestat()
+    /// (Linux 6.5 and later).
+    #[arg(long, conflicts_with = "pipeline")]
+    pub cachestat: bool,
+    /// Print the throughput of every second of the read phase as it goes.
+    #[arg(long)]
+    pub live: bool,
+    /// Show a progress line on stderr while listing and reading (files done,
+    /// bytes, current MB/s and ETA).
+    #[arg(long)]
+    pub progress: bool,
+    /// Show a live dashboard during the read phase: throughput graph, what
+    /// each thread is reading, errors and the slowest files so far.
+    #[arg(long, conflicts_with_all = ["progress", "live"])]
+    pub tui: bool,
+    /// Write a timeline of every file read in the measured passes (thread,
+    /// start, duration and size) to PATH, in Chrome trace format for
+    /// chrome://tracing or Perfetto.
+    #[arg(long, value_name = "PATH")]
+    pub trace_out: Option<PathBuf>,
+    /// Write a row for every file read in the measured passes (path, size,
+    /// time to open and to read, thread and whether it failed) to PATH, in
+    /// Parquet format.
+    #[arg(long, value_name = "PATH")]
+    pub records_out: Option<PathBuf>,
+    /// Write a CSV row for every file read in the measured passes (size, time
+    /// to open and to read, and MB/s) to PATH.
+    #[arg(long, value_name = "PATH")]
+    pub per_file_csv: Option<PathBuf>,
+    /// Before reading, stat the listed files and print a histogram of their
+    /// sizes: the files and bytes in each power-of-two size range.
+    #[arg(long, conflicts_with = "pipeline")]
+    pub size_histogram: bool,
+    /// Before reading, stat the listed files and print their total apparent
+    /// size next to the space allocated for them on disk (st_blocks), which
+    /// compression and sparse files make smaller.
+    #[arg(long, conflicts_with = "pipeline")]
+    pub disk_usage: bool,
+    /// After each measured pass, list the N files that took longest to read
+    /// and the N read at the lowest MB/s.
+    #[arg(long, value_name = "N")]
+    pub slowest: Option<usize>,
+    /// After each measured pass, break the results down by the directory the
+    /// files are in, DEPTH levels below the listed paths (dir or dir:DEPTH,
+    /// default depth 1), by file extension (ext), or by file size class: under
+    /// 4k, to 64k, 1M, 100M and over (size).
+    #[arg(long, value_name = "GROUPING", value_parser = GroupBy::parse)]
+    pub group_by: Option<GroupBy>,
+    /// After each measured pass, print what each reader thread read and how
+    /// much of the pass it spent busy and idle.
+    #[arg(long)]
+    pub per_thread: bool,
+    /// Exit with status 3 if any file couldn't be read in a measured pass, for
+    /// using iobench as a health check.
+    #[arg(long)]
+    pub fail_on_error: bool,
+    /// Compare the throughput of each configuration run with the runs of it
+    /// saved in this results file (--output json, or --quiet's output).
+    #[arg(long, value_name = "FILE")]
+    pub baseline: Option<PathBuf>,
+    /// With --baseline, exit with status 5 if the throughput of any
+    /// configuration fell by more than this (e.g. 10%) from the baseline.
+    #[arg(long, value_parser = parse_percent, requires = "baseline")]
+    pub max_regression: Option<f64>,
+    /// Send the run as OpenTelemetry spans (the whole run, the listing and
+    /// each measured pass) to this OTLP/HTTP endpoint when done, e.g.
+    /// http://localhost:4318.
+    #[arg(long, value_name = "URL", value_parser = Endpoint::parse)]
+    pub otlp_endpoint: Option<Endpoint>,
+    /// With --otlp-endpoint, also send a span for each file that took at
+    /// least this long to read (e.g. 100ms).
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, requires = "otlp_endpoint")]
+    pub otlp_file_spans: Option<Duration>,
+
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    #[command(flatten)]
+    pub engine: EngineArgs,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    /// Filesystem paths to read (alternative to -d/--dir DIR)
+    pub paths: Vec<String>,
+}
+
+impl ReadTreeArgs {
+    /// The paths to read: positional paths plus `--dir`, or CWD if neither is given.
+    fn target_paths(&self) -> Vec<PathBuf> {
+        let mut paths = self.paths.clone();
+        if let Some(d) = &self.dir {
+            paths.push(d.clone());
+        }
+        let mut paths = paths
+            .into_iter()
+            .map(PathBuf::from)
+            .collect::<Vec<PathBuf>>();
+        if paths.is_empty() {
+            paths.push(std::env::current_dir().unwrap());
+        }
+        paths
+    }
+
+    /// The most files to list, if --limit-listing applies --max-files to the walk.
+    fn listing_limit(&self) -> Option<u64> {
+        self.max_files.filter(|_| self.limit_listing)
+    }
+
+    /// Where to save checkpoints of the read pass, if anywhere.
+    fn checkpoint_path(&self) -> Option<&Path> {
+        self.checkpoint.as_deref().or(self.resume.as_deref())
+    }
+
+    /// Whether a pass of the run was stopped by --max-errors.
+    fn error_limit_reached(&self, records: &[RunRecord]) -> bool {
+        self.max_errors
+            .is_some_and(|max| records.iter().any(|record| record.errors >= max))
+    }
+
+    /// Whether every file read is kept, for --trace-out, --records-out or
+    /// --per-file-csv.
+    fn keeps_file_events(&self) -> bool {
+        self.trace_out.is_some() || self.records_out.is_some() || self.per_file_csv.is_some()
+    }
+
+    /// The shortest read, in nanoseconds, to record as a file event, if one of
+    /// the per-file outputs or --otlp-file-spans wants them.
+    fn trace_threshold(&self) -> Option<u64> {
+        if self.keeps_file_events() {
+            return Some(0);
+        }
+        self.otlp_file_spans
+            .map(|duration| duration.as_nanos() as u64)
+    }
+}
+
+pub fn read_tree(options: &ReadTreeArgs) {
+    let dirs = options.target_paths();
+    if options.pipeline && options.threads.len() > 1 {
+        error!("--pipeline reads the tree once, so it can't be combined with a thread sweep");
+        std::process::exit(1);
+    }
+    if let Err(err) = options.engine.check_available() {
+        error!("{} is not available: {err}", options.engine);
+        std::process::exit(1);
+    }
+    if options.checkpoint_path().is_some() && options.threads.len() > 1 {
+        error!("a checkpoint is of a single pass, so it can't be combined with a thread sweep");
+        std::process::exit(1);
+    }
+    if options.prefetch.is_some() && options.engine.direct {
+        error!("--prefetch fills the page cache, which --direct bypasses");
+        std::process::exit(1);
+    }
+    output::set_quiet(options.output.quiet);
+    signals::catch_status_request();
+    signals::catch_pause_toggle();
+    if let Some(addr) = options.output.listen {
+        match metrics::serve(addr) {
+            Ok(addr) => report!("-- metrics: serving at http://{addr}/metrics"),
+            Err(err) => {
+                error!("error listening on {addr}: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+    // Find out now if the database can't be used, rather than after the run.
+    if let Some(path) = &options.output.db {
+        if let Err(err) = results_db::open(path) {
+            error!("error opening database {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+    if let Err(err) = output::check_csv(&options.output) {
+        error!("error writing results: {err}");
+        std::process::exit(1);
+    }
+    let baseline = options
+        .baseline
+        .as_deref()
+        .map(|path| match output::load_records(path) {
+            Ok(runs) => runs,
+            Err(err) => {
+                error!("error reading baseline {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        });
+    let loaded = match &options.files_from {
+        Some(path) => Some(read_paths(path, options.null_delimited)),
+        None => options.load_list.as_deref().map(load_list),
+    };
+    let dirs = match &loaded {
+        Some(list) => list.dirs.clone(),
+        None => dirs,
+    };
+    let loaded = loaded.map(|list| list.files);
+    let mut records = Vec::new();
+    let mut rounds = 0;
+    let interrupted = loop {
+        let started = Instant::now();
+        rounds += 1;
+        if options.watch.is_some() {
+            report!("-- watch: round {rounds}");
+        }
+        let round = run_read_tree(options, dirs.clone(), loaded.clone());
+        let first = records.len();
+        records.extend(round);
+        record_results(options, &dirs, &records, first);
+        if signals::interrupted() || options.error_limit_reached(&records) {
+            break signals::interrupted();
+        }
+        let Some(interval) = options.watch else {
+            break false;
+        };
+        let next = started + interval;
+        if next > Instant::now() {
+            report!(
+                "-- watch: next round in {:.0} s",
+                (next - Instant::now()).as_secs_f64()
+            );
+        } else {
+            report!("-- watch: the round took longer than --watch, so the next starts now");
+        }
+        if !wait_until(next) {
+            report!("-- watch: stopped after {rounds} rounds");
+            break false;
+        }
+    };
+    if options.output.quiet {
+        if let Err(err) = output::print_json_summary(&records) {
+            error!("error writing results: {err}");
+            std::process::exit(1);
+        }
+    }
+    if interrupted {
+        error!("interrupted: the results cover only the files read before Ctrl-C");
+        std::process::exit(exit_code::INTERRUPTED);
+    }
+    if options.error_limit_reached(&records) {
+        error!(
+            "aborted after {} files couldn't be read",
+            options.max_errors.unwrap_or_default()
+        );
+        std::process::exit(exit_code::TOO_MANY_ERRORS);
+    }
+    let regression = baseline.map(|baseline| baseline::compare(&baseline, &records));
+    let errors = records.iter().map(|record| record.errors).sum::<u64>();
+    if options.fail_on_error && errors > 0 {
+        error!("{errors} file reads failed");
+        std::process::exit(exit_code::READ_ERRORS);
+    }
+    if let Some(max) = options.max_regression {
+        match regression.flatten() {
+            Some(fall) if fall > max => {
+                error!(
+                    "throughput regressed by {:.1}% from the baseline, more than the {:.1}% allowed",
+                    100.0 * fall,
+                    100.0 * max
+                );
+                std::process::exit(exit_code::REGRESSION);
+            }
+            Some(_) => {}
+            None => {
+                error!("the baseline has no runs of the configurations run to compare with");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Write the results of a round of runs, `all[first..]`, to the outputs asked
+/// for. Those that hold the latest results rather than adding to what was
+/// there get the round's; a Markdown table gets every run so far.
+fn record_results(options: &ReadTreeArgs, dirs: &[PathBuf], all: &[RunRecord], first: usize) {
+    let records = &all[first..];
+    let written = if options.output.output == OutputFormat::Markdown {
+        all
+    } else {
+        records
+    };
+    if let Err(err) = output::write_records(&options.output, written) {
+        error!("error writing results: {err}");
+        std::process::exit(1);
+    }
+    if let Some(path) = &options.output.report {
+        let title = format!("read-tree {dirs:?} ({})", options.engine);
+        match html_report::write_report(path, &title, records) {
+            Ok(()) => report!("-- report: written to {path:?}"),
+            Err(err) => {
+                error!("error writing report {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &options.output.metrics_out {
+        if let Err(err) = metrics::write_metrics(path, records) {
+            error!("error writing metrics {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+    if let Some(path) = &options.output.db {
+        match results_db::record_runs(path, records) {
+            Ok(()) => report!("-- db: {} runs recorded in {path:?}", records.len()),
+            Err(err) => {
+                error!("error recording runs in {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(addr) = &options.output.statsd {
+        // Nothing is lost if statsd is unreachable: the results are already recorded.
+        match statsd::send(addr, records) {
+            Ok(sent) => debug!("sent {sent} runs to statsd at {addr}"),
+            Err(err) => error!("error sending metrics to statsd at {addr}: {err}"),
+        }
+    }
+}
+
+/// Wait for the next round of --watch, returning false if interrupted first.
+fn wait_until(next: Instant) -> bool {
+    while !signals::interrupted() {
+        let now = Instant::now();
+        if now >= next {
+            return true;
+        }
+        std::thread::sleep((next - now).min(Duration::from_millis(100)));
+    }
+    false
+}
+
+/// Orders for `--order`.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReadOrder {
+    /// Sorted by path, as listed.
+    #[default]
+    Path,
+    /// Random order, so freshly written trees aren't read back sequentially.
+    Shuffle,
+    /// Sorted by device and inode number, roughly the on-disk order on many filesystems.
+    Inode,
+    /// Largest files first, so big files don't all land at the end on one thread.
+    LargestFirst,
+    /// Smallest files first.
+    SmallestFirst,
+}
+
+/// Buffer sizes for `--sweep-buf`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SizeSweep(pub Vec<u64>);
+
+impl SizeSweep {
+    fn parse(s: &str) -> Result<Self, String> {
+        let sizes = match s.split_once("..") {
+            Some((lo, hi)) => {
+                let lo = parse_size(lo)?.max(1);
+                let hi = parse_size(hi)?;
+                if lo > hi {
+                    return Err(format!("invalid size range: {s:?} (min > max)"));
+                }
+                let mut sizes = Vec::new();
+                let mut size = lo;
+                while size < hi {
+                    sizes.push(size);
+                    size = size.saturating_mul(2);
+                }
+                sizes.push(hi);
+                sizes
+            }
+            None => s
+                .split(',')
+                .map(parse_size)
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+        Ok(Self(sizes))
+    }
+}
+
+/// Limits that end a read pass before every listed file has been read.
+#[derive(Default)]
+struct ReadBudget {
+    deadline: Option<Instant>,
+    /// Number of files that may still be started.
+    files_remaining: Option<AtomicU64>,
+    max_bytes: Option<u64>,
+    max_errors: Option<u64>,
+    bytes_read: AtomicU64,
+    files_read: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl ReadBudget {
+    /// The limits on a measured read pass starting at `start`.
+    fn for_read_phase(options: &ReadTreeArgs, start: Instant) -> Self {
+        Self {
+            deadline: options.duration.map(|duration| start + duration),
+            files_remaining: options.max_files.map(AtomicU64::new),
+            max_bytes: options.max_bytes,
+            max_errors: options.max_errors,
+            ..Self::default()
+        }
+    }
+
+    /// Claim permission to read one more file, or return false if the budget is used up.
+    fn try_start_file(&self) -> bool {
+        signals::wait_while_paused();
+        if signals::interrupted() || self.too_many_errors() {
+            return false;
+        }
+        if self
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return false;
+        }
+        if self
+            .max_bytes
+            .is_some_and(|max| self.bytes_read.load(Ordering::Relaxed) >= max)
+        {
+            return false;
+        }
+        self.files_remaining.as_ref().is_none_or(|remaining| {
+            remaining
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+                .is_ok()
+        })
+    }
+
+    /// Count a finished file and the bytes read from it, against the byte
+    /// and error limits and for the time series.
+    fn finish_file(&self, bytes: u64, failed: bool) {
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+        self.files_read.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn too_many_errors(&self) -> bool {
+        self.max_errors
+            .is_some_and(|max| self.errors.load(Ordering::Relaxed) >= max)
+    }
+
+    /// Which limit stopped the pass, once some file has been refused.
+    fn limit_reached(&self) -> &'static str {
+        if signals::interrupted() {
+            "interrupt"
+        } else if self.too_many_errors() {
+            "error limit"
+        } else if self
+            .max_bytes
+            .is_some_and(|max| self.bytes_read.load(Ordering::Relaxed) >= max)
+        {
+            "byte limit"
+        } else if self
+            .files_remaining
+            .as_ref()
+            .is_some_and(|remaining| remaining.load(Ordering::Relaxed) == 0)
+        {
+            "file limit"
+        } else {
+            "duration limit"
+        }
+    }
+}
+
+pub fn build_pool(threads: u32) -> rayon::ThreadPool {
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(threads as usize)
+        .build()
+        .expect("thread pool")
+}
+
+/// Run the passes over the files in `dirs`, or over `loaded` if the list of
+/// them was loaded with --load-list.
+fn run_read_tree(
+    options: &ReadTreeArgs,
+    dirs: Vec<PathBuf>,
+    loaded: Option<Vec<ListedFile>>,
+) -> Vec<RunRecord> {
+    let run_start = Instant::now();
+    let mut spans = options.otlp_endpoint.as_ref().map(|_| SpanRecorder::new());
+    let root = spans.as_mut().map_or(0, SpanRecorder::new_id);
+    let thread_counts = options
+        .threads
+        .iter()
+        .map(|&t| t.max(1))
+        .collect::<Vec<_>>();
+    let buf_sizes = match &options.sweep_buf {
+        Some(SizeSweep(sizes)) => sizes.clone(),
+        None => vec![options.engine.buf_size],
+    };
+    let thread_sweep = thread_counts.len() > 1;
+    let buf_sweep = buf_sizes.len() > 1;
+    let engine = &options.engine;
+    let threads_desc = thread_counts
+        .iter()
+        .map(u32::to_string)
+        .collect::<Vec<_>>()
+        .join(",");
+    let list_threads = options
+        .list_threads
+        .unwrap_or_else(|| thread_counts.iter().copied().max().unwrap_or(1))
+        .max(1);
+    if options.list_threads.is_some() {
+        report!("-- reading {dirs:?} using {threads_desc} threads, {list_threads} for listing ({engine})");
+    } else {
+        report!("-- reading {dirs:?} using {threads_desc} threads ({engine})");
+    }
+    if buf_sweep {
+        let sizes = buf_sizes
+            .iter()
+            .map(|&b| format_size(b))
+            .collect::<Vec<_>>();
+        report!("-- buffer sizes: {}", sizes.join(", "));
+    }
+    let resumed = options.resume.as_deref().map(|path| {
+        let checkpoint = match Checkpoint::read(path) {
+            Ok(checkpoint) => checkpoint,
+            Err(err) => {
+                error!("error reading checkpoint {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        };
+        if checkpoint.paths != dirs {
+            error!(
+                "checkpoint {} is of a run over {:?}, not {dirs:?}",
+                path.to_string_lossy(),
+                checkpoint.paths
+            );
+            std::process::exit(1);
+        }
+        checkpoint
+    });
+    let seed = resumed
+        .as_ref()
+        .map(|checkpoint| checkpoint.seed)
+        .or(options.seed)
+        .unwrap_or_else(|| Rng::from_time().next_u64());
+    if options.pipeline {
+        let timestamp = OffsetDateTime::now_utc();
+        let threads = thread_counts[0];
+        if options.drop_caches {
+            drop_caches();
+        }
+        signals::catch_interrupt();
+        let mut result = pipelined_read_tree(options, &dirs, list_threads, threads, seed);
+        save_manifest(options, &mut result.stats.checksums);
+        let buf_size = options.engine.effective_buf_size() as u64;
+        let name = format!("run 1: {threads} threads, {} buffer", format_size(buf_size));
+        if let Some(spans) = &mut spans {
+            add_pass_spans(spans, root, &name, &result, options);
+            export_spans(options, spans, root, run_start, &threads_desc);
+        }
+        save_file_events(
+            options,
+            &[PassTrace {
+                name,
+                events: result.stats.trace,
+            }],
+        );
+        return vec![RunRecord {
+            timestamp,
+            paths: dirs,
+            threads,
+            buf_size,
+            engine: options.engine.engine,
+            files: result.files_read,
+            bytes: result.stats.bytes,
+            duration_s: result.duration_s,
+            errors: result.stats.errors,
+            series: result.series,
+            latency: result.stats.latency,
+            sizes: result.stats.sizes,
+        }];
+    }
+    let list_start = Instant::now();
+    let all_files = loaded.unwrap_or_else(|| {
+        list_tree(
+            &dirs,
+            list_threads,
+            &options.walk,
+            options.listing_limit(),
+            options.progress,
+        )
+    });
+    if let Some(path) = &options.save_list {
+        save_list(path, &dirs, &all_files);
+    }
+    if let Some(spans) = &mut spans {
+        let id = spans.new_id();
+        spans.add(Span {
+            id,
+            parent: Some(root),
+            name: "list".to_string(),
+            start: list_start,
+            end: Instant::now(),
+            attributes: vec![("iobench.files", Attribute::Int(all_files.len() as i64))],
+            failed: false,
+        });
+    }
+    let all_files = sample_files(options, seed, all_files);
+    if options.size_histogram || options.disk_usage {
+        let sizes = stat_sizes(&all_files, list_threads);
+        if options.disk_usage {
+            print_disk_usage(&sizes);
+        }
+        if options.size_histogram {
+            print_size_histogram(&sizes);
+        }
+    }
+    let all_files = order_files(options.order, seed, list_threads, all_files);
+
+    let all_files = if options.warmup.is_some() || options.warmup_files.is_some() {
+        warm_up(options, &build_pool(thread_counts[0]), all_files)
+    } else {
+        all_files
+    };
+
+    let skipped = resumed
+        .as_ref()
+        .map_or(0, |checkpoint| check_resumed(checkpoint, &all_files));
+    let progress = options.checkpoint_path().map(|_| {
+        Progress::new(resumed.clone().unwrap_or_else(|| Checkpoint {
+            paths: dirs.clone(),
+            seed,
+            listed: all_files.len() as u64,
+            ..Checkpoint::default()
+        }))
+    });
+    let all_files = &all_files[skipped..];
+
+    if options.prefetch.is_some() && !options.drop_caches && !options.engine.evict_after_read {
+        report!("-- note: without --drop-caches or --evict-after-read, each prefetched pass reads files cached by the pass before it");
+    }
+
+    signals::catch_interrupt();
+    let runs = options.runs.max(1);
+    let mut records = Vec::new();
+    let mut traces = Vec::new();
+    let mut cachestat = options.cachestat;
+    'passes: for &threads in &thread_counts {
+        let pool = build_pool(threads);
+        for &buf_size in &buf_sizes {
+            let engine = EngineArgs {
+                buf_size,
+                ..options.engine.clone()
+            };
+            match (thread_sweep, buf_sweep) {
+                (true, true) => {
+                    report!("-- threads: {threads}   buffer: {}", format_size(buf_size))
+                }
+                (true, false) => report!("-- threads: {threads}"),
+                (false, true) => report!("-- buffer: {}", format_size(buf_size)),
+                (false, false) => {}
+            }
+            let first_run = records.len();
+            for run in 1..=runs {
+                if runs > 1 {
+                    report!("-- run {run} of {runs}");
+                }
+                if options.drop_caches {
+                    drop_caches();
+                }
+                if cachestat {
+                    cachestat = print_cachestat("before", &pool, all_files);
+                }
+                let timestamp = OffsetDateTime::now_utc();
+                let mut result = match options.prefetch {
+                    Some(window) => {
+                        report!("-- without prefetch:");
+                        let baseline = read_phase(options, &engine, &pool, all_files, None, None);
+                        if options.drop_caches {
+                            drop_caches();
+                        }
+                        report!("-- with prefetch ({window} files ahead):");
+                        let result =
+                            read_phase(options, &engine, &pool, all_files, Some(window), None);
+                        print_prefetch_gain(&baseline, &result);
+                        result
+                    }
+                    None => read_phase(options, &engine, &pool, all_files, None, progress.as_ref()),
+                };
+                if cachestat {
+                    cachestat = print_cachestat("after", &pool, all_files);
+                }
+                if records.is_empty() {
+                    save_manifest(options, &mut result.stats.checksums);
+                }
+                let name = format!(
+                    "run {}: {threads} threads, {} buffer",
+                    records.len() + 1,
+                    format_size(engine.effective_buf_size() as u64)
+                );
+                if let Some(spans) = &mut spans {
+                    add_pass_spans(spans, root, &name, &result, options);
+                }
+                let mut record = RunRecord {
+                    timestamp,
+                    paths: dirs.clone(),
+                    threads,
+                    buf_size: engine.effective_buf_size() as u64,
+                    engine: engine.engine,
+                    files: result.files_read,
+                    bytes: result.stats.bytes,
+                    duration_s: result.duration_s,
+                    errors: result.stats.errors,
+                    series: result.series,
+                    latency: result.stats.latency,
+                    sizes: result.stats.sizes,
+                };
+                if let Some(resumed) = &resumed {
+                    add_resumed(&mut record, resumed);
+                }
+                records.push(record);
+                metrics::publish(&records);
+                if options.keeps_file_events() {
+                    traces.push(PassTrace {
+                        name,
+                        events: result.stats.trace,
+                    });
+                }
+                if options.error_limit_reached(&records) || signals::interrupted() {
+                    break 'passes;
+                }
+            }
+            if runs > 1 {
+                print_runs_summary(&records[first_run..]);
+            }
+        }
+    }
+    // A sweep cut short by --max-errors or Ctrl-C has nothing to compare.
+    let aborted = options.error_limit_reached(&records) || signals::interrupted();
+    if thread_sweep && !aborted {
+        for &buf_size in &buf_sizes {
+            let engine = EngineArgs {
+                buf_size,
+                ..options.engine.clone()
+            };
+            let buf_size = engine.effective_buf_size() as u64;
+            let group = records
+                .iter()
+                .filter(|r| r.buf_size == buf_size)
+                .cloned()
+                .collect::<Vec<_>>();
+            print_scaling_table(&thread_counts, &group, buf_sweep.then_some(buf_size));
+        }
+    }
+    if buf_sweep && !aborted {
+        for &threads in &thread_counts {
+            let group = records
+                .iter()
+                .filter(|r| r.threads == threads)
+                .cloned()
+                .collect::<Vec<_>>();
+            print_buffer_table(&group, thread_sweep.then_some(threads));
+        }
+    }
+    save_file_events(options, &traces);
+    if let Some(spans) = &mut spans {
+        export_spans(options, spans, root, run_start, &threads_desc);
+    }
+    records
+}
+
+/// Check that the tree listed to resume from a checkpoint is the one it was
+/// taken of, returning how many files at the start of the list were done.
+fn check_resumed(checkpoint: &Checkpoint, all_files: &[ListedFile]) -> usize {
+    let done = checkpoint.done as usize;
+    let same_last = match (&checkpoint.last, done.checked_sub(1)) {
+        (Some(last), Some(index)) => all_files
+            .get(index)
+            .is_some_and(|entry| entry.path == *last),
+        (None, None) => true,
+        _ => false,
+    };
+    if all_files.len() as u64 != checkpoint.listed || !same_last {
+        error!(
+            "the tree has changed since the checkpoint: {} files listed, {} then",
+            all_files.len(),
+            checkpoint.listed
+        );
+        std::process::exit(1);
+    }
+    report!(
+        "-- resuming: {done} of {} files were done before, {:.1} MB in {:.3} s",
+        checkpoint.listed,
+        checkpoint.bytes as f64 / 1e6,
+        checkpoint.duration_s
+    );
+    done
+}
+
+/// Add what was read before resuming to the record of the resumed pass, and
+/// print the totals.
+fn add_resumed(record: &mut RunRecord, resumed: &Checkpoint) {
+    record.files += resumed.done;
+    record.bytes += resumed.bytes;
+    record.errors += resumed.errors;
+    record.duration_s += resumed.duration_s;
+    report!(
+        "-- with the files read before resuming: {:.0} MB/s   {:.0} files/s  ({} files, {} MB in {} s)",
+        record.mb_per_s(),
+        record.files_per_s(),
+        record.files,
+        record.bytes as f64 / 1e6,
+        record.duration_s,
+    );
+}
+
+/// Save a checkpoint of the pass over `all_files` that started at `start`
+/// every `interval`, until `done` is set.
+fn save_checkpoints(
+    progress: &Progress,
+    path: &Path,
+    all_files: &[ListedFile],
+    interval: Duration,
+    start: Instant,
+    done: &AtomicBool,
+) {
+    let mut next = start + interval;
+    while !done.load(Ordering::Relaxed) {
+        let now = Instant::now();
+        if now < next {
+            std::thread::sleep((next - now).min(Duration::from_millis(50)));
+            continue;
+        }
+        next += interval;
+        save_checkpoint(progress, path, all_files, start.elapsed().as_secs_f64());
+    }
+}
+
+fn save_checkpoint(
+    progress: &Progress,
+    path: &Path,
+    all_files: &[ListedFile],
+    duration_s: f64,
+) -> Option<Checkpoint> {
+    let checkpoint = progress.checkpoint(duration_s, |index| all_files[index].path.clone());
+    match checkpoint.write(path) {
+        Ok(()) => {
+            debug!(
+                "saved checkpoint of {} files to {}",
+                checkpoint.done,
+                path.to_string_lossy()
+            );
+            Some(checkpoint)
+        }
+        Err(err) => {
+            error!("error saving checkpoint {}: {err}", path.to_string_lossy());
+            None
+        }
+    }
+}
+
+/// Page cache state of a set of files.
+#[derive(Default)]
+struct CacheSnapshot {
+    stat: CacheStat,
+    /// Pages the files would take up if fully cached.
+    pages: u64,
+    full: u64,
+    partial: u64,
+    cold: u64,
+    /// Files whose state couldn't be queried.
+    errors: u64,
+}
+
+/// Query the page cache state of every file and print the totals, returning
+/// false if cachestat() isn't supported here and needn't be tried again.
+fn print_cachestat(when: &str, pool: &rayon::ThreadPool, all_files: &[ListedFile]) -> bool {
+    let page_size = cache::page_size() as u64;
+    let results = pool.install(|| {
+        all_files
+            .par_iter()
+            .map(|entry| {
+                let f = File::open(&entry.path)?;
+                let pages = f.metadata()?.len().div_ceil(page_size);
+                Ok((pages, cache::cachestat(&f)?))
+            })
+            .collect::<Vec<Result<_, io::Error>>>()
+    });
+    let mut snapshot = CacheSnapshot::default();
+    for (entry, result) in all_files.iter().zip(results) {
+        let (pages, stat) = match result {
+            Ok(result) => result,
+            Err(err) if err.raw_os_error() == Some(libc::ENOSYS) => {
+                report!("-- cachestat: not available ({err}); it needs Linux 6.5 or later");
+                return false;
+            }
+            Err(err) => {
+                // Only the owner, or a process that could write the file, may query it.
+                debug!(
+                    "error querying page cache state: {}: {err}",
+                    entry.path.to_string_lossy()
+                );
+                snapshot.errors += 1;
+                continue;
+            }
+        };
+        snapshot.stat.add(&stat);
+        snapshot.pages += pages;
+        match stat.cached {
+            0 if pages > 0 => snapshot.cold += 1,
+            cached if cached < pages => snapshot.partial += 1,
+            _ => snapshot.full += 1,
+        }
+    }
+    let CacheSnapshot {
+        stat,
+        pages,
+        full,
+        partial,
+        cold,
+        errors,
+    } = snapshot;
+    let cached_pct = if pages > 0 {
+        100.0 * stat.cached as f64 / pages as f64
+    } else {
+        100.0
+    };
+    report!(
+        "-- cachestat {when}: {} of {pages} pages cached ({cached_pct:.1}%), {} dirty, {} under writeback, {} evicted ({} recently)",
+        stat.cached, stat.dirty, stat.writeback, stat.evicted, stat.recently_evicted,
+    );
+    report!("--   files: {full} fully cached, {partial} partly cached, {cold} not cached");
+    if errors > 0 {
+        report!("--   not queried: {errors} files (cachestat needs the owner or write permission)");
+    }
+    true
+}
+
+/// Write the checksums gathered by --checksum to the manifest file.
+fn save_manifest(options: &ReadTreeArgs, checksums: &mut [FileChecksum]) {
+    let Some(kind) = options.engine.checksum else {
+        return;
+    };
+    let path = options
+        .manifest
+        .clone()
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
+    match write_manifest(&path, kind, checksums) {
+        Ok(()) => report!(
+            "-- manifest: {} checksums written to {path:?}",
+            checksums.len()
+        ),
+        Err(err) => {
+            error!("error writing manifest {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Write the file reads of the measured passes asked for by --trace-out,
+/// --records-out and --per-file-csv.
+fn save_file_events(options: &ReadTreeArgs, traces: &[PassTrace]) {
+    if let Some(path) = &options.trace_out {
+        match write_trace(path, traces) {
+            Ok(events) => report!("-- trace: {events} file reads written to {path:?}"),
+            Err(err) => {
+                error!("error writing trace {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &options.records_out {
+        match parquet::write_records(path, traces) {
+            Ok(rows) => report!("-- records: {rows} file reads written to {path:?}"),
+            Err(err) => {
+                error!("error writing records {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(path) = &options.per_file_csv {
+        match output::write_file_csv(path, traces) {
+            Ok(rows) => report!("-- per-file CSV: {rows} file reads written to {path:?}"),
+            Err(err) => {
+                error!("error writing {}: {err}", path.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Add a span for a measured pass, and for each of its files that took long
+/// enough to read for --otlp-file-spans.
+fn add_pass_spans(
+    spans: &mut SpanRecorder,
+    parent: u64,
+    name: &str,
+    phase: &ReadPhase,
+    options: &ReadTreeArgs,
+) {
+    let id = spans.new_id();
+    let mb_per_s = phase.stats.bytes as f64 / 1e6 / phase.duration_s;
+    spans.add(Span {
+        id,
+        parent: Some(parent),
+        name: format!("read {name}"),
+        start: phase.started,
+        end: phase.started + Duration::from_secs_f64(phase.duration_s),
+        attributes: vec![
+            ("iobench.files", Attribute::Int(phase.files_read as i64)),
+            ("iobench.bytes", Attribute::Int(phase.stats.bytes as i64)),
+            ("iobench.mb_per_s", Attribute::Double(mb_per_s)),
+            ("iobench.errors", Attribute::Int(phase.stats.errors as i64)),
+        ],
+        failed: false,
+    });
+    let Some(threshold) = options.otlp_file_spans else {
+        return;
+    };
+    for event in &phase.stats.trace {
+        let duration = Duration::from_nanos(event.duration_ns);
+        if duration < threshold {
+            continue;
+        }
+        let file_id = spans.new_id();
+        spans.add(Span {
+            id: file_id,
+            parent: Some(id),
+            name: "read file".to_string(),
+            start: event.start,
+            end: event.start + duration,
+            attributes: vec![
+                (
+                    "file.path",
+                    Attribute::Str(event.path.to_string_lossy().into_owned()),
+                ),
+                ("iobench.bytes", Attribute::Int(event.bytes as i64)),
+                ("thread.id", Attribute::Int(event.thread as i64)),
+            ],
+            failed: event.failed,
+        });
+    }
+}
+
+/// Add the span for the whole run and send every span to --otlp-endpoint.
+/// A failure is only logged, so the results of the run are still written.
+fn export_spans(
+    options: &ReadTreeArgs,
+    spans: &mut SpanRecorder,
+    root: u64,
+    start: Instant,
+    threads: &str,
+) {
+    let Some(endpoint) = &options.otlp_endpoint else {
+        return;
+    };
+    spans.add(Span {
+        id: root,
+        parent: None,
+        name: "read-tree".to_string(),
+        start,
+        end: Instant::now(),
+        attributes: vec![
+            (
+                "iobench.paths",
+                Attribute::Str(format!("{:?}", options.target_paths())),
+            ),
+            ("iobench.threads", Attribute::Str(threads.to_string())),
+            ("iobench.engine", Attribute::Str(options.engine.to_string())),
+        ],
+        failed: false,
+    });
+    match spans.export(endpoint) {
+        Ok(sent) => report!("-- otlp: {sent} spans sent to {endpoint}"),
+        Err(err) => error!("error sending spans to {endpoint}: {err}"),
+    }
+}
+
+/// Mean of a metric over the records that match a predicate.
+fn mean_where(
+    records: &[RunRecord],
+    matches: impl Fn(&RunRecord) -> bool,
+    metric: fn(&RunRecord) -> f64,
+) -> f64 {
+    let values = records
+        .iter()
+        .filter(|r| matches(r))
+        .map(metric)
+        .collect::<Vec<_>>();
+    Summary::of(&values).mean
+}
+
+fn list_tree(
+    dirs: &[PathBuf],
+    threads: u32,
+    walk: &WalkArgs,
+    limit: Option<u64>,
+    progress: bool,
+) -> Vec<ListedFile> {
+    let t1 = Instant::now();
+    let mut all_files = Vec::new();
+    let mut bar = progress.then(|| ProgressBar::new("files listed", None));
+    for dir in dirs {
+        let remaining = limit.map_or(usize::MAX, |limit| {
+            (limit as usize).saturating_sub(all_files.len())
+        });
+        for entry in walk_files(dir, threads, walk)
+            .map(ListedFile::from)
+            .take(remaining)
+        {
+            all_files.push(entry);
+            if let Some(bar) = &mut bar {
+                bar.update(all_files.len() as u64, None);
+            }
+        }
+    }
+    if let Some(bar) = bar {
+        bar.clear();
+    }
+    let t2 = Instant::now();
+    let dur_s = (t2 - t1).as_secs_f64();
+    report!(
+        "-- list: {:.0} files/s  ({} files in {} s)",
+        all_files.len() as f64 / dur_s,
+        all_files.len(),
+        dur_s,
+    );
+    print_walk_report();
+    all_files
+}
+
+fn load_list(path: &Path) -> FileList {
+    let t1 = Instant::now();
+    match file_list::load(path) {
+        Ok(list) => {
+            report!(
+                "-- list: loaded {} files from {path:?} in {} s",
+                list.files.len(),
+                t1.elapsed().as_secs_f64()
+            );
+            list
+        }
+        Err(err) => {
+            error!("error loading file list {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Read the paths of the files given to read-list, ended by NULs if `null`.
+fn read_paths(path: &Path, null: bool) -> FileList {
+    let t1 = Instant::now();
+    let end = if null { b'\0' } else { b'\n' };
+    let files = if path == Path::new("-") {
+        file_list::read_paths(io::stdin().lock(), end)
+    } else {
+        File::open(path).and_then(|f| file_list::read_paths(io::BufReader::new(f), end))
+    };
+    match files {
+        Ok(files) => {
+            report!(
+                "-- list: read {} paths from {} in {} s",
+                files.len(),
+                if path == Path::new("-") {
+                    "stdin".to_string()
+                } else {
+                    format!("{path:?}")
+                },
+                t1.elapsed().as_secs_f64()
+            );
+            FileList {
+                dirs: vec![path.to_path_buf()],
+                files,
+            }
+        }
+        Err(err) => {
+            error!("error reading file list {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+}
+
+fn save_list(path: &Path, dirs: &[PathBuf], all_files: &[ListedFile]) {
+    match file_list::save(path, dirs, all_files) {
+        Ok(()) => report!("-- list: {} files saved to {path:?}", all_files.len()),
+        Err(err) => {
+            error!("error saving file list {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Choose the random subset of files asked for by --sample or --sample-files,
+/// keeping them in listing order.
+fn sample_files(options: &ReadTreeArgs, seed: u64, all_files: Vec<ListedFile>) -> Vec<ListedFile> {
+    let listed = all_files.len() as u64;
+    let wanted = match (options.sample, options.sample_files) {
+        (Some(fraction), _) => (fraction * listed as f64).round() as u64,
+        (None, Some(n)) => n.min(listed),
+        (None, None) => return all_files,
+    };
+    // Selection sampling: picks exactly `wanted` files, each equally likely.
+    let mut rng = Rng::new(seed);
+    let mut needed = wanted;
+    let sampled = all_files
+        .into_iter()
+        .enumerate()
+        .filter(|&(i, _)| {
+            let left = listed - i as u64;
+            let take = needed > 0 && rng.range_inclusive(0, left - 1) < needed;
+            needed -= u64::from(take);
+            take
+        })
+        .map(|(_, entry)| entry)
+        .collect::<Vec<_>>();
+    report!("-- sample: {wanted} of {listed} files (seed {seed})");
+    sampled
+}
+
+/// Rearrange the file list into the order given by --order.
+fn order_files(
+    order: ReadOrder,
+    seed: u64,
+    threads: u32,
+    mut all_files: Vec<ListedFile>,
+) -> Vec<ListedFile> {
+    match order {
+        ReadOrder::Path => {}
+        ReadOrder::Shuffle => {
+            // Offset from the sampling seed so the two don't draw the same numbers.
+            let mut rng = Rng::new(seed.wrapping_add(1));
+            for i in (1..all_files.len()).rev() {
+                let j = rng.range_inclusive(0, i as u64) as usize;
+                all_files.swap(i, j);
+            }
+            report!("-- order: shuffled (seed {seed})");
+        }
+        ReadOrder::Inode => {
+            all_files = sort_by_metadata(all_files, threads, "by inode", |metadata| {
+                metadata.map_or((u64::MAX, u64::MAX), |m| (m.dev(), m.ino()))
+            });
+        }
+        ReadOrder::LargestFirst => {
+            all_files = sort_by_metadata(all_files, threads, "largest first", |metadata| {
+                Reverse(metadata.map_or(0, |m| m.len()))
+            });
+        }
+        ReadOrder::SmallestFirst => {
+            all_files = sort_by_metadata(all_files, threads, "smallest first", |metadata| {
+                metadata.map_or(u64::MAX, |m| m.len())
+            });
+        }
+    }
+    all_files
+}
+
+/// The apparent size and the space allocated on disk of each file that could
+/// be stat'ed, stat'ing them in parallel.
+fn stat_sizes(all_files: &[ListedFile], threads: u32) -> Vec<(u64, u64)> {
+    let t1 = Instant::now();
+    let sizes = build_pool(threads).install(|| {
+        all_files
+            .par_iter()
+            .filter_map(|entry| entry.metadata().ok().map(|m| (m.len(), m.blocks() * 512)))
+            .collect::<Vec<_>>()
+    });
+    report!(
+        "-- stat: {} files stat'ed in {} s",
+        sizes.len(),
+        t1.elapsed().as_secs_f64()
+    );
+    sizes
+}
+
+/// Print the total apparent and on-disk size of the files, which differ with
+/// sparse files and filesystem compression.
+fn print_disk_usage(sizes: &[(u64, u64)]) {
+    let apparent = sizes.iter().map(|&(apparent, _)| apparent).sum::<u64>();
+    let allocated = sizes.iter().map(|&(_, allocated)| allocated).sum::<u64>();
+    report!(
+        "-- disk usage: {:.1} MB apparent size, {:.1} MB on disk ({:.2}x apparent to on-disk)",
+        apparent as f64 / 1e6,
+        allocated as f64 / 1e6,
+        apparent as f64 / allocated.max(1) as f64,
+    );
+}
+
+/// Print how many of the files to read, and how many of their bytes, are in
+/// each power-of-two size range.
+fn print_size_histogram(sizes: &[(u64, u64)]) {
+    // Bucket 0 is empty files, and bucket i + 1 sizes from 2^i to 2^(i+1) - 1.
+    let mut buckets = [(0u64, 0u64); 65];
+    for &(size, _) in sizes {
+        let bucket = if size == 0 {
+            0
+        } else {
+            size.ilog2() as usize + 1
+        };
+        buckets[bucket].0 += 1;
+        buckets[bucket].1 += size;
+    }
+    let total_bytes = sizes.iter().map(|&(size, _)| size).sum::<u64>();
+    report!(
+        "-- file sizes: {} files, {:.1} MB",
+        sizes.len(),
+        total_bytes as f64 / 1e6,
+    );
+    let Some(first) = buckets.iter().position(|&(files, _)| files > 0) else {
+        return;
+    };
+    let last = buckets
+        .iter()
+        .rposition(|&(files, _)| files > 0)
+        .unwrap_or(first);
+    let mut cumulative = 0;
+    for (bucket, &(files, bytes)) in buckets.iter().enumerate().take(last + 1).skip(first) {
+        cumulative += bytes;
+        let range = if bucket == 0 {
+            "empty".to_string()
+        } else {
+            let low = 1u64 << (bucket - 1);
+            format!(
+                "{} to {}",
+                format_size(low),
+                format_size(low.saturating_mul(2))
+            )
+        };
+        report!(
+            "   {range:>14}  {files:>9} files {:>5.1}%  {:>12.1} MB  {:>5.1}% of bytes so far",
+            100.0 * files as f64 / sizes.len() as f64,
+            bytes as f64 / 1e6,
+            100.0 * cumulative as f64 / total_bytes.max(1) as f64,
+        );
+    }
+}
+
+/// Sort files by a key computed from their metadata (None if they can't be
+/// stat'ed), stat'ing them in parallel.
+fn sort_by_metadata<K: Ord + Send>(
+    all_files: Vec<ListedFile>,
+    threads: u32,
+    desc: &str,
+    key: impl Fn(Option<&Metadata>) -> K + Sync,
+) -> Vec<ListedFile> {
+    let t1 = Instant::now();
+    let keys = build_pool(threads).install(|| {
+        all_files
+            .par_iter()
+            .map(|entry| key(entry.metadata().ok().as_ref()))
+            .collect::<Vec<_>>()
+    });
+    let mut keyed = keys.into_iter().zip(all_files).collect::<Vec<_>>();
+    keyed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let dur_s = t1.elapsed().as_secs_f64();
+    report!(
+        "-- order: {desc} ({} files stat'ed in {dur_s} s)",
+        keyed.len()
+    );
+    keyed.into_iter().map(|(_, entry)| entry).collect()
+}
+
+/// Most columns in the end-of-pass throughput sparkline.
+const SPARKLINE_WIDTH: usize = 60;
+
+/// Number of listed files that may wait in the pipeline for a reader.
+const PIPELINE_CAPACITY: usize = 4096;
+
+/// Single pass in which the walker feeds files to the readers through a
+/// bounded channel, so reading starts at once and memory use doesn't grow
+/// with the size of the tree.
+fn pipelined_read_tree(
+    options: &ReadTreeArgs,
+    dirs: &[PathBuf],
+    list_threads: u32,
+    read_threads: u32,
+    seed: u64,
+) -> ReadPhase {
+    let pool = build_pool(read_threads);
+    let (tx, rx) = crossbeam_channel::bounded(PIPELINE_CAPACITY);
+    let stop_walking = AtomicBool::new(false);
+    let t1 = Instant::now();
+    let budget = ReadBudget::for_read_phase(options, t1);
+
+    let done = AtomicBool::new(false);
+    let activity = options
+        .tui
+        .then(|| Activity::new(pool.current_num_threads()));
+    std::thread::scope(|scope| {
+        let (stop_walking, done, budget) = (&stop_walking, &done, &budget);
+        let activity = activity.as_ref();
+        let sampler =
+            scope.spawn(move || sample_throughput(budget, t1, done, options, None, activity));
+        let walker = scope.spawn(move || {
+            let limit = options.listing_limit().unwrap_or(u64::MAX);
+            let mut rng = Rng::new(seed);
+            if let Some(fraction) = options.sample {
+                report!("-- sampling {}% of files (seed {seed})", 100.0 * fraction);
+            }
+            let mut listed = 0u64;
+            'walk: for dir in dirs {
+                for entry in walk_files(dir, list_threads, &options.walk).map(ListedFile::from) {
+                    // The tree's size isn't known up front, so sample each file independently.
+                    if options
+                        .sample
+                        .is_some_and(|fraction| rng.next_f64() >= fraction)
+                    {
+                        continue;
+                    }
+                    if listed >= limit
+                        || stop_walking.load(Ordering::Relaxed)
+                        || tx.send(entry).is_err()
+                    {
+                        break 'walk;
+                    }
+                    listed += 1;
+                }
+            }
+            (listed, t1.elapsed())
+        });
+
+        let thread_stats = pool.broadcast(|ctx| {
+            let mut stats = ReadFilesStats::default();
+            for entry in rx.iter() {
+                if !budget.try_start_file() {
+                    stop_walking.store(true, Ordering::Relaxed);
+                    stats.skipped += 1;
+                    continue;
+                }
+                let file_stats =
+                    read_file_tracked(&entry, &options.engine, activity, ctx.index(), options);
+                budget.finish_file(file_stats.bytes, file_stats.errors > 0);
+                metrics::count_file(file_stats.bytes, file_stats.errors > 0);
+                stats = stats.combine(file_stats);
+            }
+            stats
+        });
+        let elapsed = t1.elapsed();
+        done.store(true, Ordering::Relaxed);
+        let (series, paused) = sampler.join().expect("sampler thread panicked");
+        let dur_s = elapsed.saturating_sub(paused).as_secs_f64();
+
+        let (listed, list_time) = walker.join().expect("walker thread panicked");
+        let list_s = list_time.as_secs_f64();
+        report!(
+            "-- list: {:.0} files/s  ({listed} files in {list_s} s, overlapped with reading)",
+            listed as f64 / list_s,
+        );
+        print_walk_report();
+        finish_read_phase(
+            thread_stats,
+            listed,
+            t1,
+            dur_s,
+            budget,
+            series,
+            options.per_thread,
+        )
+    })
+}
+
+/// Outcome of one measured pass over the file list.
+struct ReadPhase {
+    stats: ReadFilesStats,
+    started: Instant,
+    files_read: u64,
+    duration_s: f64,
+    series: Vec<Sample>,
+}
+
+/// Read every file in `all_files` with the thread pool, with a prefetch
+/// thread keeping `prefetch` files ahead of the readers if given, and saving
+/// checkpoints of the files done in `progress` if given.
+fn read_phase(
+    options: &ReadTreeArgs,
+    engine: &EngineArgs,
+    pool: &rayon::ThreadPool,
+    all_files: &[ListedFile],
+    prefetch: Option<usize>,
+    progress: Option<&Progress>,
+) -> ReadPhase {
+    let t1 = Instant::now();
+    let budget = ReadBudget::for_read_phase(options, t1);
+
+    // Every thread takes the next file from a shared cursor, so files are
+    // started in list order whatever --order produced.
+    let next = AtomicUsize::new(0);
+    let done = AtomicBool::new(false);
+    let activity = options
+        .tui
+        .then(|| Activity::new(pool.current_num_threads()));
+    let (thread_stats, prefetched, series, paused, t2) = std::thread::scope(|scope| {
+        let (next, done, budget) = (&next, &done, &budget);
+        let total = Some(all_files.len() as u64);
+        let activity = activity.as_ref();
+        let sampler =
+            scope.spawn(move || sample_throughput(budget, t1, done, options, total, activity));
+        let prefetcher = prefetch.map(|window| {
+            scope.spawn(move || prefetch_files(all_files, next, window, engine.head, done))
+        });
+        let checkpointer = progress
+            .zip(options.checkpoint_path())
+            .map(|(progress, path)| {
+                let interval = options.checkpoint_interval;
+                scope.spawn(move || save_checkpoints(progress, path, all_files, interval, t1, done))
+            });
+        let thread_stats = pool.broadcast(|ctx| {
+            let mut stats = ReadFilesStats::default();
+            loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(entry) = all_files.get(index) else {
+                    break;
+                };
+                if !budget.try_start_file() {
+                    stats.skipped += 1;
+                    continue;
+                }
+                let file_stats = read_file_tracked(entry, engine, activity, ctx.index(), options);
+                let failed = file_stats.errors > 0;
+                budget.finish_file(file_stats.bytes, failed);
+                metrics::count_file(file_stats.bytes, failed);
+                if let Some(progress) = progress {
+                    progress.finish_file(index, file_stats.bytes, failed);
+                }
+                stats = stats.combine(file_stats);
+            }
+            stats
+        });
+        // Before the helpers are stopped, since they sleep between polls.
+        let t2 = Instant::now();
+        done.store(true, Ordering::Relaxed);
+        let prefetched =
+            prefetcher.map(|prefetcher| prefetcher.join().expect("prefetch thread panicked"));
+        if let Some(checkpointer) = checkpointer {
+            checkpointer.join().expect("checkpoint thread panicked");
+        }
+        let (series, paused) = sampler.join().expect("sampler thread panicked");
+        (thread_stats, prefetched, series, paused, t2)
+    });
+
+    if let Some(prefetched) = prefetched {
+        report!("-- prefetch: readahead issued for {prefetched} files");
+    }
+    let dur_s = (t2 - t1).saturating_sub(paused).as_secs_f64();
+    if let Some((progress, path)) = progress.zip(options.checkpoint_path()) {
+        if let Some(checkpoint) = save_checkpoint(progress, path, all_files, dur_s) {
+            report!(
+                "-- checkpoint: {} of {} files done, saved to {}",
+                checkpoint.done,
+                checkpoint.listed,
+                path.to_string_lossy()
+            );
+        }
+    }
+    finish_read_phase(
+        thread_stats,
+        all_files.len() as u64,
+        t1,
+        dur_s,
+        &budget,
+        series,
+        options.per_thread,
+    )
+}
+
+/// Record the bytes and files read in each second from `start` until `done`
+/// is set, printing each second's throughput with --live and keeping the
+/// progress line (--progress) or dashboard (--tui, from `activity`) up to
+/// date, out of `total` files if known. Seconds paused with SIGUSR2 are left
+/// out; returns the samples and how long the reads were paused.
+fn sample_throughput(
+    budget: &ReadBudget,
+    start: Instant,
+    done: &AtomicBool,
+    options: &ReadTreeArgs,
+    total: Option<u64>,
+    activity: Option<&Activity>,
+) -> (Vec<Sample>, Duration) {
+    let mut start = start;
+    let mut paused = Duration::ZERO;
+    let mut paused_since = None;
+    let mut series = Vec::new();
+    let mut last = Sample::default();
+    let mut bar = options.progress.then(|| ProgressBar::new("files", total));
+    let mut dashboard = activity.map(|_| {
+        Dashboard::new(format!(
+            "read-tree {:?} ({})",
+            options.target_paths(),
+            options.engine
+        ))
+    });
+    while !done.load(Ordering::Relaxed) {
+        if signals::paused() && !signals::interrupted() {
+            if paused_since.is_none() {
+                if let Some(bar) = &bar {
+                    bar.clear();
+                }
+                report!("-- paused: finishing the reads in progress; send SIGUSR2 again to resume");
+                paused_since = Some(Instant::now());
+            }
+            std::thread::sleep(Duration::from_millis(50));
+            continue;
+        }
+        if let Some(since) = paused_since.take() {
+            let pause = since.elapsed();
+            paused += pause;
+            start += pause;
+            report!("-- resumed after {:.1} s", pause.as_secs_f64());
+        }
+        let files = budget.files_read.load(Ordering::Relaxed);
+        let bytes = budget.bytes_read.load(Ordering::Relaxed);
+        if let Some(bar) = &mut bar {
+            bar.update(files, Some(bytes));
+        }
+        if let (Some(dashboard), Some(activity)) = (&mut dashboard, activity) {
+            let totals = Totals {
+                files,
+                total_files: total,
+                bytes,
+                elapsed: start.elapsed(),
+            };
+            dashboard.draw(activity, &series, &totals);
+        }
+        if signals::take_status_request() {
+            if let Some(bar) = &bar {
+                bar.clear();
+            }
+            print_status(start, files, total, bytes, series.last());
+        }
+        let tick = start + Duration::from_secs(series.len() as u64 + 1);
+        let now = Instant::now();
+        if now < tick {
+            // Poll rather than sleep a whole second, so the end of the pass isn't delayed.
+            std::thread::sleep((tick - now).min(Duration::from_millis(50)));
+            continue;
+        }
+        let total = Sample {
+            bytes: budget.bytes_read.load(Ordering::Relaxed),
+            files: budget.files_read.load(Ordering::Relaxed),
+        };
+        let sample = Sample {
+            bytes: total.bytes - last.bytes,
+            files: total.files - last.files,
+        };
+        last = total;
+        series.push(sample);
+        if options.live {
+            if let Some(bar) = &bar {
+                bar.clear();
+            }
+            report!(
+                "-- {:>4} s: {:.0} MB/s   {} files/s",
+                series.len(),
+                sample.bytes as f64 / 1e6,
+                sample.files,
+            );
+        }
+    }
+    if let Some(bar) = bar {
+        bar.clear();
+    }
+    if let Some(since) = paused_since {
+        paused += since.elapsed();
+    }
+    (series, paused)
+}
+
+/// Print how far a read pass that started at `start` has got, when asked
+/// with SIGUSR1. The current MB/s is that of the last whole second.
+fn print_status(
+    start: Instant,
+    files: u64,
+    total: Option<u64>,
+    bytes: u64,
+    last_second: Option<&Sample>,
+) {
+    let elapsed_s = start.elapsed().as_secs_f64();
+    let mean = bytes as f64 / 1e6 / elapsed_s;
+    let files = match total {
+        Some(total) => format!("{files} of {total} files"),
+        None => format!("{files} files"),
+    };
+    let current = last_second.map_or(mean, |second| second.bytes as f64 / 1e6);
+    report!(
+        "-- status: {elapsed_s:.1} s elapsed   {files}   {:.1} MB   {current:.0} MB/s now   {mean:.0} MB/s mean",
+        bytes as f64 / 1e6,
+    );
+}
+
+/// Issue readahead() for the files up to `window` places past the readers'
+/// cursor, until every file has been prefetched or the readers are done.
+/// Returns the number of files prefetched.
+fn prefetch_files(
+    all_files: &[ListedFile],
+    next: &AtomicUsize,
+    window: usize,
+    head: Option<u64>,
+    done: &AtomicBool,
+) -> u64 {
+    let mut prefetched = 0;
+    let mut ahead = 0;
+    while ahead < all_files.len() && !done.load(Ordering::Relaxed) {
+        let cursor = next.load(Ordering::Relaxed);
+        if ahead < cursor {
+            // The readers have already started on these files.
+            ahead = cursor;
+            continue;
+        }
+        if ahead >= cursor + window {
+            std::thread::sleep(Duration::from_micros(100));
+            continue;
+        }
+        let entry = &all_files[ahead];
+        ahead += 1;
+        let len = match entry.metadata() {
+            Ok(metadata) => head.map_or(metadata.size(), |head| head.min(metadata.size())),
+            Err(err) => {
+                debug!(
+                    "error prefetching file {}: {err}",
+                    entry.path.to_string_lossy()
+                );
+                continue;
+            }
+        };
+        match readahead(&entry.path, len) {
+            Ok(()) => prefetched += 1,
+            Err(err) => debug!(
+                "error prefetching file {}: {err}",
+                entry.path.to_string_lossy()
+            ),
+        }
+    }
+    prefetched
+}
+
+/// Print how much faster a pass with prefetching was than one without.
+fn print_prefetch_gain(baseline: &ReadPhase, prefetched: &ReadPhase) {
+    let mb_per_s = |phase: &ReadPhase| phase.stats.bytes as f64 / 1e6 / phase.duration_s;
+    let (without, with) = (mb_per_s(baseline), mb_per_s(prefetched));
+    let gain = if without > 0.0 {
+        100.0 * (with / without - 1.0)
+    } else {
+        0.0
+    };
+    report!("-- prefetch gain: {with:.0} MB/s with vs {without:.0} MB/s without ({gain:+.1}%)");
+}
+
+/// Print the results of a read pass over `listed` files that started at
+/// `started` and took `dur_s` seconds, given the statistics of each reader
+/// thread, and a line for each of them if `per_thread`.
+fn finish_read_phase(
+    mut thread_stats: Vec<ReadFilesStats>,
+    listed: u64,
+    started: Instant,
+    dur_s: f64,
+    budget: &ReadBudget,
+    series: Vec<Sample>,
+    per_thread: bool,
+) -> ReadPhase {
+    let threads = thread_stats
+        .iter()
+        .map(|stats| GroupStats {
+            files: stats.latency.count(),
+            bytes: stats.bytes,
+            busy_ns: stats.busy_ns,
+            errors: stats.errors,
+        })
+        .collect::<Vec<_>>();
+    let last_files = thread_stats
+        .iter_mut()
+        .map(|stats| stats.last_file.take())
+        .collect::<Vec<_>>();
+    let all_stats = thread_stats
+        .into_iter()
+        .fold(ReadFilesStats::default(), ReadFilesStats::combine);
+    let total_size_mb = all_stats.bytes as f64 / 1_000_000.0;
+    let files_read = listed - all_stats.skipped;
+    report!(
+        "-- read: {:.0} MB/s   {:.0} files/s  ({} MB in {} s)",
+        total_size_mb / dur_s,
+        files_read as f64 / dur_s,
+        total_size_mb,
+        dur_s,
+    );
+    if all_stats.skipped > 0 {
+        report!(
+            "-- stopped at the {} after {files_read} of {listed} files",
+            budget.limit_reached(),
+        );
+    }
+    if all_stats.evicted > 0 {
+        report!(
+            "-- evicted {} of {files_read} files from the page cache after reading",
+            all_stats.evicted
+        );
+    }
+    print_series_range(&series);
+    print_sparkline(&series);
+    print_engine_report();
+    print_latency("read", &all_stats.latency);
+    print_latency("first byte", &all_stats.first_byte);
+    print_phase_times(&all_stats);
+    if all_stats.retries > 0 {
+        report!(
+            "-- retries: {} reads retried after transient errors, {} files then read",
+            all_stats.retries,
+            all_stats.recovered
+        );
+    }
+    if all_stats.timed_out > 0 {
+        report!(
+            "-- timed out: gave up on {} files, leaving a thread stuck in each",
+            all_stats.timed_out
+        );
+    }
+    all_stats.error_kinds.print();
+    print_slowest(&all_stats.slowest);
+    all_stats.groups.print();
+    if per_thread {
+        print_threads(&threads, dur_s);
+    }
+    print_balance(&threads);
+    print_straggler(&last_files, dur_s);
+
+    ReadPhase {
+        stats: all_stats,
+        started,
+        files_read,
+        duration_s: dur_s,
+        series,
+    }
+}
+
+/// Print the slowest and fastest seconds of a read pass, which the mean hides.
+fn print_series_range(series: &[Sample]) {
+    if series.len() < 2 {
+        return;
+    }
+    let mb_per_s = |second: usize| series[second].bytes as f64 / 1e6;
+    let slowest = (0..series.len())
+        .min_by(|&a, &b| mb_per_s(a).total_cmp(&mb_per_s(b)))
+        .unwrap_or(0);
+    let fastest = (0..series.len())
+        .max_by(|&a, &b| mb_per_s(a).total_cmp(&mb_per_s(b)))
+        .unwrap_or(0);
+    report!(
+        "-- per second: min {:.0} MB/s (second {})   max {:.0} MB/s (second {})",
+        mb_per_s(slowest),
+        slowest + 1,
+        mb_per_s(fastest),
+        fastest + 1,
+    );
+}
+
+/// Print the throughput of each second of a read pass as a sparkline, with
+/// neighbouring seconds averaged so a long pass still fits on one line.
+fn print_sparkline(series: &[Sample]) {
+    if series.len() < 2 {
+        return;
+    }
+    let per_column = series.len().div_ceil(SPARKLINE_WIDTH);
+    let values = series
+        .chunks(per_column)
+        .map(|seconds| {
+            seconds.iter().map(|sample| sample.bytes).sum::<u64>() as f64
+                / 1e6
+                / seconds.len() as f64
+        })
+        .collect::<Vec<_>>();
+    let peak = values.iter().copied().fold(0.0, f64::max);
+    report!(
+        "-- throughput: {}  (peak {peak:.0} MB/s, {per_column} s per column)",
+        sparkline(&values)
+    );
+}
+
+/// Print mean throughput per thread count, with speedup and parallel
+/// efficiency relative to the first thread count.
+fn print_scaling_table(thread_counts: &[u32], records: &[RunRecord], buf_size: Option<u64>) {
+    let base_threads = thread_counts[0];
+    let base = mean_where(records, |r| r.threads == base_threads, RunRecord::mb_per_s);
+    match buf_size {
+        Some(buf_size) => report!("-- scaling with {} buffer:", format_size(buf_size)),
+        None => report!("-- scaling:"),
+    }
+    report!(
+        "--   {:>7}  {:>10}  {:>10}  {:>8}  {:>10}",
+        "threads",
+        "MB/s",
+        "files/s",
+        "speedup",
+        "efficiency"
+    );
+    for &threads in thread_counts {
+        let mb_per_s = mean_where(records, |r| r.threads == threads, RunRecord::mb_per_s);
+        let files_per_s = mean_where(records, |r| r.threads == threads, RunRecord::files_per_s);
+        let speedup = if base > 0.0 { mb_per_s / base } else { 0.0 };
+        let efficiency = speedup * f64::from(base_threads) / f64::from(threads);
+        report!(
+            "--   {threads:>7}  {mb_per_s:>10.0}  {files_per_s:>10.0}  {speedup:>7.2}x  {:>9.0}%",
+            100.0 * efficiency,
+        );
+    }
+}
+
+/// Print mean throughput per buffer size, relative to the best buffer size.
+fn print_buffer_table(records: &[RunRecord], threads: Option<u32>) {
+    let mut buf_sizes = records.iter().map(|r| r.buf_size).collect::<Vec<_>>();
+    buf_sizes.dedup();
+    let rows = buf_sizes
+        .iter()
+        .map(|&buf_size| {
+            let matches = |r: &RunRecord| r.buf_size == buf_size;
+            (
+                buf_size,
+                mean_where(records, matches, RunRecord::mb_per_s),
+                mean_where(records, matches, RunRecord::files_per_s),
+            )
+        })
+        .collect::<Vec<_>>();
+    let best = rows.iter().map(|&(_, mb, _)| mb).fold(0.0, f64::max);
+    match threads {
+        Some(threads) => report!("-- buffer sizes with {threads} threads:"),
+        None => report!("-- buffer sizes:"),
+    }
+    report!(
+        "--   {:>7}  {:>10}  {:>10}  {:>8}",
+        "buffer",
+        "MB/s",
+        "files/s",
+        "vs best"
+    );
+    for (buf_size, mb_per_s, files_per_s) in rows {
+        let relative = if best > 0.0 {
+            100.0 * mb_per_s / best
+        } else {
+            0.0
+        };
+        report!(
+            "--   {:>7}  {mb_per_s:>10.0}  {files_per_s:>10.0}  {relative:>7.0}%",
+            format_size(buf_size),
+        );
+    }
+}
+
+/// Print the spread of throughput across repeated runs.
+fn print_runs_summary(records: &[RunRecord]) {
+    let mb_per_s = Summary::of(&records.iter().map(RunRecord::mb_per_s).collect::<Vec<_>>());
+    let files_per_s = Summary::of(
+        &records
+            .iter()
+            .map(RunRecord::files_per_s)
+            .collect::<Vec<_>>(),
+    );
+    report!("-- summary of {} runs:", records.len());
+    for (name, summary) in [("MB/s", mb_per_s), ("files/s", files_per_s)] {
+        report!(
+            "--   {name:<8} mean {:.0}   stddev {:.1} ({:.1}%)   min {:.0}   max {:.0}",
+            summary.mean,
+            summary.stddev,
+            summary.relative_stddev(),
+            summary.min,
+            summary.max,
+        );
+    }
+}
+
+/// Read files until the warm-up budget runs out, discarding the statistics,
+/// and return the files that are left to be measured.
+fn warm_up(
+    options: &ReadTreeArgs,
+    pool: &rayon::ThreadPool,
+    all_files: Vec<ListedFile>,
+) -> Vec<ListedFile> {
+    let t1 = Instant::now();
+    let budget = ReadBudget {
+        deadline: options.warmup.map(|duration| t1 + duration),
+        files_remaining: options.warmup_files.map(AtomicU64::new),
+        ..ReadBudget::default()
+    };
+    let warmed = pool.install(|| {
+        all_files
+            .par_iter()
+            .map(|entry| {
+                if !budget.try_start_file() {
+                    return false;
+                }
+                read_file(entry, &options.engine, options);
+                true
+            })
+            .collect::<Vec<bool>>()
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+    let warmed_count = warmed.iter().filter(|&&w| w).count();
+    report!("-- warmup: {warmed_count} files in {dur_s} s (excluded from results)");
+    print_engine_report();
+    all_files
+        .into_iter()
+        .zip(warmed)
+        .filter_map(|(entry, warmed)| (!warmed).then_some(entry))
+        .collect()
+}
+
+/// Print how the reader threads' busy time divides between opening files,
+/// getting their size and reading them, so slow path lookups can be told
+/// apart from slow data transfer.
+fn print_phase_times(stats: &ReadFilesStats) {
+    if stats.busy_ns == 0 || stats.file_count == 0 {
+        return;
+    }
+    let other_ns = stats
+        .busy_ns
+        .saturating_sub(stats.open_ns + stats.stat_ns + stats.read_ns);
+    let phases = [
+        ("open", stats.open_ns),
+        ("stat", stats.stat_ns),
+        ("read", stats.read_ns),
+        ("other", other_ns),
+    ]
+    .map(|(name, ns)| {
+        format!(
+            "{name} {:.3} s ({:.0}%, {} per file)",
+            ns as f64 / 1e9,
+            100.0 * ns as f64 / stats.busy_ns as f64,
+            format_latency(ns / stats.file_count),
+        )
+    });
+    report!("-- busy time: {}", phases.join("   "));
+}
+
+/// Print the files and bytes each reader thread read in a pass of `dur_s`
+/// seconds, with the time it spent busy with files and idle.
+fn print_threads(threads: &[GroupStats], dur_s: f64) {
+    report!("-- threads:");
+    for (thread, stats) in threads.iter().enumerate() {
+        let busy_s = stats.busy_ns as f64 / 1e9;
+        let errors = if stats.errors > 0 {
+            format!("  ({} errors)", stats.errors)
+        } else {
+            String::new()
+        };
+        report!(
+            "   {thread:>4}  {:>9} files  {:>10.1} MB  {:>8.1} MB/s  busy {busy_s:>8.3} s  idle {:>8.3} s  ({:>3.0}% busy){errors}",
+            stats.files,
+            stats.bytes as f64 / 1e6,
+            stats.bytes as f64 / 1e6 / busy_s.max(1e-9),
+            (dur_s - busy_s).max(0.0),
+            100.0 * busy_s / dur_s.max(1e-9),
+        );
+    }
+}
+
+/// Print how evenly the work was spread over the reader threads, from the
+/// time each spent busy with files.
+fn print_balance(threads: &[GroupStats]) {
+    if threads.len() < 2 {
+        return;
+    }
+    let busy_s = threads
+        .iter()
+        .map(|thread| thread.busy_ns as f64 / 1e9)
+        .collect::<Vec<_>>();
+    let busy = Summary::of(&busy_s);
+    let over_mean = if busy.mean > 0.0 {
+        100.0 * (busy.max / busy.mean - 1.0)
+    } else {
+        0.0
+    };
+    report!(
+        "-- balance: thread busy time min {:.3} s   mean {:.3} s   max {:.3} s   (busiest {over_mean:.0}% over the mean)",
+        busy.min, busy.mean, busy.max,
+    );
+    let mb = Summary::of(
+        &threads
+            .iter()
+            .map(|thread| thread.bytes as f64 / 1e6)
+            .collect::<Vec<_>>(),
+    );
+    // There is no ratio with a thread that read nothing, as when there were
+    // fewer files than threads.
+    let ratio = if mb.min > 0.0 {
+        format!("{:.2}x", mb.max / mb.min)
+    } else {
+        "n/a".to_string()
+    };
+    report!(
+        "-- fairness: thread bytes min {:.1} MB   max {:.1} MB   (max/min {ratio}, CV {:.1}%), busy time CV {:.1}%",
+        mb.min,
+        mb.max,
+        mb.relative_stddev(),
+        busy.relative_stddev(),
+    );
+}
+
+/// Threads finishing more than this share of the pass after all the others
+/// are reported as stragglers.
+const STRAGGLER_SHARE: f64 = 0.2;
+
+/// Point out a reader thread that kept going long after every other one was
+/// done, so the pass's wall time came down to it, along with the file it read
+/// last: typically one huge file started near the end. `last_files` holds
+/// when each thread finished its last file, and which it was. Threads that
+/// read no files, as when there were fewer than threads, aren't compared.
+fn print_straggler(last_files: &[Option<(Instant, SlowFile)>], dur_s: f64) {
+    let mut ends = last_files
+        .iter()
+        .enumerate()
+        .filter_map(|(thread, last)| Some((last.as_ref()?.0, thread)))
+        .collect::<Vec<_>>();
+    if ends.len() < 2 {
+        return;
+    }
+    ends.sort();
+    let (last_end, thread) = ends[ends.len() - 1];
+    let tail_s = (last_end - ends[ends.len() - 2].0).as_secs_f64();
+    if tail_s < STRAGGLER_SHARE * dur_s {
+        return;
+    }
+    let Some((_, file)) = &last_files[thread] else {
+        return;
+    };
+    report!(
+        "-- straggler: thread {thread} finished {tail_s:.3} s after the others ({:.0}% of the pass), last reading {} ({} in {})",
+        100.0 * tail_s / dur_s,
+        file.path.to_string_lossy(),
+        format_size(file.bytes),
+        format_latency(file.ns),
+    );
+}
+
+#[derive(Default)]
+struct ReadFilesStats {
+    bytes: u64,
+    file_count: u64,
+    /// Time taken to open and read each successfully read file, in nanoseconds.
+    latency: Histogram,
+    /// Time from opening each successfully read file to its first data
+    /// arriving, in nanoseconds; empty files have none.
+    first_byte: Histogram,
+    /// Bytes read from each successfully read file.
+    sizes: Histogram,
+    /// Listed files not read because the read phase was stopped early.
+    skipped: u64,
+    /// Time spent opening and reading files, including ones that failed.
+    busy_ns: u64,
+    /// The parts of busy_ns spent opening files, getting their size and
+    /// reading them.
+    open_ns: u64,
+    stat_ns: u64,
+    read_ns: u64,
+    /// Checksums of the files read in full, with --checksum.
+    checksums: Vec<FileChecksum>,
+    /// Files dropped from the page cache after reading, with --evict-after-read.
+    evicted: u64,
+    /// Files that couldn't be read.
+    errors: u64,
+    /// Those files by kind of error.
+    error_kinds: ErrorSummary,
+    /// Reads retried after a transient error, and files read after retrying.
+    retries: u64,
+    recovered: u64,
+    /// Files given up on after --file-timeout.
+    timed_out: u64,
+    /// Files read, with --trace-out, or the slow ones with --otlp-file-spans.
+    trace: Vec<FileEvent>,
+    /// The slowest files read, with --slowest.
+    slowest: SlowestFiles,
+    /// What was read from each group of files, with --group-by.
+    groups: Groups,
+    /// The file finished last and when, to spot a straggler thread.
+    last_file: Option<(Instant, SlowFile)>,
+}
+
+impl ReadFilesStats {
+    fn combine(mut self, other: Self) -> Self {
+        self.bytes += other.bytes;
+        self.file_count += other.file_count;
+        self.skipped += other.skipped;
+        self.busy_ns += other.busy_ns;
+        self.open_ns += other.open_ns;
+        self.stat_ns += other.stat_ns;
+        self.read_ns += other.read_ns;
+        self.latency.merge(&other.latency);
+        self.first_byte.merge(&other.first_byte);
+        self.sizes.merge(&other.sizes);
+        self.checksums.extend(other.checksums);
+        self.evicted += other.evicted;
+        self.errors += other.errors;
+        self.error_kinds = self.error_kinds.combine(other.error_kinds);
+        self.retries += other.retries;
+        self.recovered += other.recovered;
+        self.timed_out += other.timed_out;
+        self.trace.extend(other.trace);
+        self.slowest = self.slowest.combine(other.slowest);
+        self.groups = self.groups.combine(other.groups);
+        self.last_file = match (self.last_file, other.last_file) {
+            (Some(a), Some(b)) => Some(if a.0 >= b.0 { a } else { b }),
+            (a, b) => a.or(b),
+        };
+        self
+    }
+}
+
+/// A successfully read file, for --slowest.
+struct SlowFile {
+    path: PathBuf,
+    bytes: u64,
+    /// Time to open and read it.
+    ns: u64,
+}
+
+impl SlowFile {
+    fn mb_per_s(&self) -> f64 {
+        self.bytes as f64 * 1e3 / self.ns.max(1) as f64
+    }
+}
+
+/// The `limit` files that took longest to read, and the `limit` non-empty
+/// files read at the lowest MB/s, slowest first.
+#[derive(Default)]
+struct SlowestFiles {
+    limit: usize,
+    by_time: Vec<SlowFile>,
+    by_rate: Vec<SlowFile>,
+}
+
+impl SlowestFiles {
+    fn new(limit: usize, path: PathBuf, bytes: u64, ns: u64) -> Self {
+        let by_rate = (bytes > 0)
+            .then(|| SlowFile {
+                path: path.clone(),
+                bytes,
+                ns,
+            })
+            .into_iter()
+            .collect();
+        Self {
+            limit,
+            by_time: vec![SlowFile { path, bytes, ns }],
+            by_rate,
+        }
+    }
+
+    fn combine(mut self, other: Self) -> Self {
+        self.limit = self.limit.max(other.limit);
+        self.by_time.extend(other.by_time);
+        self.by_time.sort_by_key(|file| std::cmp::Reverse(file.ns));
+        self.by_time.truncate(self.limit);
+        self.by_rate.extend(other.by_rate);
+        self.by_rate
+            .sort_by(|a, b| a.mb_per_s().total_cmp(&b.mb_per_s()));
+        self.by_rate.truncate(self.limit);
+        self
+    }
+}
+
+/// Print the files found by --slowest, so a few bad files or a bad server
+/// behind part of the tree show up by name.
+fn print_slowest(slowest: &SlowestFiles) {
+    if slowest.by_time.is_empty() {
+        return;
+    }
+    report!("-- slowest files by time:");
+    for file in &slowest.by_time {
+        report!(
+            "   {:>12}  {:>9}  {}",
+            format_latency(file.ns),
+            format_size(file.bytes),
+            file.path.to_string_lossy()
+        );
+    }
+    if slowest.by_rate.is_empty() {
+        return;
+    }
+    report!("-- slowest files by MB/s:");
+    for file in &slowest.by_rate {
+        report!(
+            "   {:>12}  {:>9}  {}",
+            format!("{:.1} MB/s", file.mb_per_s()),
+            format_size(file.bytes),
+            file.path.to_string_lossy()
+        );
+    }
+}
+
+/// Read a file, keeping the --tui dashboard's view of reader `thread` up to
+/// date, and recording the read for the per-file outputs, --slowest and
+/// --group-by as the options ask.
+fn read_file_tracked(
+    entry: &ListedFile,
+    engine: &EngineArgs,
+    activity: Option<&Activity>,
+    thread: usize,
+    options: &ReadTreeArgs,
+) -> ReadFilesStats {
+    if let Some(activity) = activity {
+        activity.start_file(thread, &entry.path);
+    }
+    let start = Instant::now();
+    let mut stats = read_file(entry, engine, options);
+    if let Some(activity) = activity {
+        activity.finish_file(thread, (stats.errors == 0).then_some(stats.busy_ns));
+    }
+    if options
+        .trace_threshold()
+        .is_some_and(|threshold| stats.busy_ns >= threshold)
+    {
+        stats.trace.push(FileEvent {
+            path: entry.path.clone(),
+            thread,
+            start,
+            duration_ns: stats.busy_ns,
+            open_ns: stats.open_ns,
+            bytes: stats.bytes,
+            failed: stats.errors > 0,
+        });
+    }
+    stats.last_file = Some((
+        Instant::now(),
+        SlowFile {
+            path: entry.path.clone(),
+            bytes: stats.bytes,
+            ns: stats.busy_ns,
+        },
+    ));
+    if let Some(limit) = options.slowest.filter(|_| stats.errors == 0) {
+        stats.slowest = SlowestFiles::new(limit, entry.path.clone(), stats.bytes, stats.busy_ns);
+    }
+    if let Some(by) = options.group_by {
+        let group = GroupStats {
+            files: stats.file_count - stats.errors.min(stats.file_count),
+            bytes: stats.bytes,
+            busy_ns: stats.busy_ns,
+            errors: stats.errors,
+        };
+        // By the file's size rather than what was read of it, which --head,
+        // --max-bytes or an error may have cut short.
+        let size = match by {
+            GroupBy::Size => entry
+                .metadata()
+                .map_or(stats.bytes, |metadata| metadata.len()),
+            _ => 0,
+        };
+        stats.groups = Groups::one(by, by.key(&entry.path, entry.depth, size), group);
+    }
+    stats
+}
+
+/// Read a file, retrying it after transient errors as --retries allows. Its
+/// time includes the failed attempts and the waits between them.
+fn read_file(entry: &ListedFile, engine: &EngineArgs, options: &ReadTreeArgs) -> ReadFilesStats {
+    let path = &entry.path;
+
+    let t1 = Instant::now();
+    let mut retries = 0;
+    let (mut stats, result) = loop {
+        let attempt = match options.file_timeout {
+            Some(timeout) => read_on_worker(path, engine, timeout),
+            None => {
+                let mut stats = ReadFilesStats::default();
+                let result = do_read_file(path, engine, &mut stats);
+                (stats, result)
+            }
+        };
+        match attempt {
+            (_, Err(err)) if retries < options.retries && is_transient(&err) => {
+                let delay = options
+                    .retry_delay
+                    .saturating_mul(2u32.saturating_pow(retries));
+                debug!(
+                    "retrying file {} in {delay:?}: {err}",
+                    path.to_string_lossy()
+                );
+                retries += 1;
+                std::thread::sleep(delay);
+            }
+            attempt => break attempt,
+        }
+    };
+    stats.busy_ns = t1.elapsed().as_nanos() as u64;
+    stats.retries = u64::from(retries);
+    if retries > 0 && result.is_ok() {
+        stats.recovered = 1;
+    }
+    match result {
+        Ok(()) => {
+            stats.latency.record(stats.busy_ns);
+            stats.sizes.record(stats.bytes);
+            trace!("done reading file {}", path.to_string_lossy());
+        }
+        Err(err) => {
+            stats.errors += 1;
+            debug!("error reading file {}: {err}", path.to_string_lossy());
+            stats.error_kinds = ErrorSummary::one(&err, path.clone());
+        }
+    }
+
+    stats
+}
+
+/// Whether an error may go away if the file is read again, as when a
+/// network filesystem hiccups.
+fn is_transient(err: &std::io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EAGAIN | libc::ESTALE | libc::ETIMEDOUT)
+    )
+}
+
+/// A file to read on a ReadWorker.
+struct ReadJob {
+    path: PathBuf,
+    engine: EngineArgs,
+}
+
+/// A thread that does the reads of one reader thread with --file-timeout, so
+/// a read that hangs can be abandoned: the reader stops waiting for it and
+/// starts a new worker, leaving the stuck one behind.
+struct ReadWorker {
+    jobs: mpsc::Sender<ReadJob>,
+    results: mpsc::Receiver<(ReadFilesStats, io::Result<()>)>,
+}
+
+thread_local! {
+    static READ_WORKER: RefCell<Option<ReadWorker>> = const { RefCell::new(None) };
+}
+
+impl ReadWorker {
+    fn spawn() -> Self {
+        let (jobs, job_rx) = mpsc::channel::<ReadJob>();
+        let (result_tx, results) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("read-worker".to_string())
+            .spawn(move || {
+                for job in job_rx {
+                    let mut stats = ReadFilesStats::default();
+                    let result = do_read_file(&job.path, &job.engine, &mut stats);
+                    if result_tx.send((stats, result)).is_err() {
+                        // The reader gave up waiting for this file.
+                        break;
+                    }
+                }
+            })
+            .expect("read worker thread");
+        Self { jobs, results }
+    }
+}
+
+/// Read a file on this reader thread's worker, giving up on it after
+/// `timeout`.
+fn read_on_worker(
+    path: &Path,
+    engine: &EngineArgs,
+    timeout: Duration,
+) -> (ReadFilesStats, io::Result<()>) {
+    READ_WORKER.with(|worker| {
+        let mut worker = worker.borrow_mut();
+        let job = ReadJob {
+            path: path.to_path_buf(),
+            engine: engine.clone(),
+        };
+        worker
+            .get_or_insert_with(ReadWorker::spawn)
+            .jobs
+            .send(job)
+            .expect("read worker exited");
+        let result = worker
+            .as_ref()
+            .map(|worker| worker.results.recv_timeout(timeout));
+        match result {
+            Some(Ok(result)) => result,
+            Some(Err(mpsc::RecvTimeoutError::Timeout)) => {
+                *worker = None;
+                let stats = ReadFilesStats {
+                    timed_out: 1,
+                    ..ReadFilesStats::default()
+                };
+                let err = io::Error::new(
+                    io::ErrorKind::TimedOut,
+                    format!("gave up after {timeout:?}"),
+                );
+                (stats, Err(err))
+            }
+            _ => panic!("read worker exited"),
+        }
+    })
+}
+
+fn do_read_file(
+    path: &Path,
+    engine: &EngineArgs,
+    stats: &mut ReadFilesStats,
+) -> Result<(), std::io::Error> {
+    let pathstr = path.to_string_lossy();
+    trace!("open file: {}", pathstr);
+    let head = engine.head;
+    let mut hasher = engine.checksum.map(|_| Xxh3::default());
+    let f = engine.with_thread_engine(|engine| {
+        // Clear what's left by a file that failed.
+        take_first_byte();
+        let open_start = Instant::now();
+        let opened = engine.open(path);
+        stats.open_ns = open_start.elapsed().as_nanos() as u64;
+        let mut f = opened?;
+        stats.file_count += 1;
+        let read_start;
+        if let Some(len) = head {
+            trace!("begin reading first {len} bytes of file: {}", pathstr);
+            read_start = Instant::now();
+            engine.read_head(&mut f, len, &pathstr, &mut stats.bytes)?;
+        } else {
+            // fstat, so the size is that of the file opened, whatever the
+            // path names by now.
+            let stat_start = Instant::now();
+            let metadata = f.metadata();
+            read_start = Instant::now();
+            stats.stat_ns = (read_start - stat_start).as_nanos() as u64;
+            let size = metadata?.size();
+            trace!("begin reading file: {}", pathstr);
+            engine.read_contents(&mut f, size, &pathstr, &mut stats.bytes, hasher.as_mut())?;
+        }
+        stats.read_ns = read_start.elapsed().as_nanos() as u64;
+        if let Some(first) = take_first_byte() {
+            stats
+                .first_byte
+                .record((first - open_start).as_nanos() as u64);
+        }
+        Ok(f)
+    })?;
+    stats.evicted += u64::from(engine.finish_file(&f, &pathstr));
+    if let Some(hasher) = hasher {
+        stats.checksums.push(FileChecksum {
+            path: path.to_path_buf(),
+            size: stats.bytes,
+            hash: hasher.digest(),
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+//! The results history database (`--db`): every run recorded in SQLite with
+//! its parameters and the host it ran on, for following a target's
+//! performance across months of scheduled runs.
+
+use std::path::{Path, PathBuf};
+
+use time::format_description::well_known::Rfc3339;
+
+use crate::{
+    otlp::hostname,
+    output::RunRecord,
+    sqlite::{Connection, Value},
+};
+
+/// Version of the schema, kept in the database's user_version.
+const SCHEMA_VERSION: i64 = 1;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    -- When the run started, in RFC 3339 format and UTC.
+    timestamp TEXT NOT NULL,
+    -- The run's number among those of the command that made it.
+    run INTEGER NOT NULL,
+    command TEXT NOT NULL,
+    version TEXT NOT NULL,
+    host TEXT NOT NULL,
+    kernel TEXT NOT NULL,
+    cpus INTEGER NOT NULL,
+    engine TEXT NOT NULL,
+    threads INTEGER NOT NULL,
+    buf_size INTEGER NOT NULL,
+    files INTEGER NOT NULL,
+    bytes INTEGER NOT NULL,
+    errors INTEGER NOT NULL,
+    duration_s REAL NOT NULL,
+    mb_per_s REAL,
+    files_per_s REAL,
+    p50_latency_ns INTEGER,
+    p99_latency_ns INTEGER,
+    max_latency_ns INTEGER
+);
+CREATE TABLE IF NOT EXISTS run_paths (
+    run_id INTEGER NOT NULL REFERENCES runs (id),
+    path TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS run_paths_by_path ON run_paths (path, run_id);
+";
+
+/// Open the database at `path`, creating it or its tables if needed.
+pub fn open(path: &Path) -> Result<Connection, String> {
+    let db = Connection::open(path)?;
+    let version = db.query("PRAGMA user_version", &[], |row| row[0].as_f64())?;
+    match version.first().copied().flatten().map(|v| v as i64) {
+        Some(0) => {
+            db.execute_batch(SCHEMA)?;
+            db.execute(&format!("PRAGMA user_version = {SCHEMA_VERSION}"), &[])?;
+        }
+        Some(SCHEMA_VERSION) => {}
+        Some(version) => {
+            return Err(format!(
+            "the database has schema version {version}, newer than this version of iobench knows"
+        ))
+        }
+        None => return Err("the database has no user_version".to_string()),
+    }
+    Ok(db)
+}
+
+/// Record the runs of this command in the database at `path`, in a single
+/// transaction.
+pub fn record_runs(path: &Path, records: &[RunRecord]) -> Result<(), String> {
+    let db = open(path)?;
+    let command = std::env::args().collect::<Vec<_>>().join(" ");
+    let host = hostname();
+    let kernel = kernel_release();
+    let cpus = std::thread::available_parallelism().map_or(0, |n| n.get() as i64);
+    db.execute("BEGIN", &[])?;
+    for (run, record) in records.iter().enumerate() {
+        let timestamp = record
+            .timestamp
+            .format(&Rfc3339)
+            .map_err(|err| err.to_string())?;
+        let finite = |value: f64| {
+            if value.is_finite() {
+                Value::Real(value)
+            } else {
+                Value::Null
+            }
+        };
+        let latency = |percentile: f64| {
+            if record.latency.count() > 0 {
+                Value::Int(record.latency.percentile(percentile) as i64)
+            } else {
+                Value::Null
+            }
+        };
+        db.execute(
+            "INSERT INTO runs (timestamp, run, command, version, host, kernel, cpus, engine, \
+             threads, buf_size, files, bytes, errors, duration_s, mb_per_s, files_per_s, \
+             p50_latency_ns, p99_latency_ns, max_latency_ns) \
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            &[
+                Value::Text(timestamp),
+                Value::Int(run as i64 + 1),
+                Value::Text(command.clone()),
+                Value::Text(env!("CARGO_PKG_VERSION").to_string()),
+                Value::Text(host.clone()),
+                Value::Text(kernel.clone()),
+                Value::Int(cpus),
+                Value::Text(record.engine.name().to_string()),
+                Value::Int(i64::from(record.threads)),
+                Value::Int(record.buf_size as i64),
+                Value::Int(record.files as i64),
+                Value::Int(record.bytes as i64),
+                Value::Int(record.errors as i64),
+                Value::Real(record.duration_s),
+                finite(record.mb_per_s()),
+                finite(record.files_per_s()),
+                latency(50.0),
+                latency(99.0),
+                latency(100.0),
+            ],
+        )?;
+        let id = db
+            .query("SELECT last_insert_rowid()", &[], |row| row[0].clone())?
+            .remove(0);
+        for path in &record.paths {
+            db.execute(
+                "INSERT INTO run_paths (run_id, path) VALUES (?, ?)",
+                &[id.clone(), Value::Text(path.to_string_lossy().into_owned())],
+            )?;
+        }
+    }
+    db.execute("COMMIT", &[])
+}
+
+/// A run as recorded in the database.
+#[derive(Debug, Clone)]
+pub struct StoredRun {
+    /// When it started, in RFC 3339 format.
+    pub timestamp: String,
+    /// The same time as a Julian day number, for working out trends.
+    pub day: f64,
+    pub host: String,
+    pub engine: String,
+    pub threads: i64,
+    pub buf_size: i64,
+    /// The paths it read, separated by spaces.
+    pub paths: String,
+    pub files: i64,
+    pub errors: i64,
+    pub mb_per_s: Option<f64>,
+    pub files_per_s: Option<f64>,
+    pub p99_latency_ns: Option<f64>,
+}
+
+/// The runs in the database at `path`, oldest first: those that read
+/// `target`, if given, and that ran on `host`.
+pub fn load_runs(
+    path: &Path,
+    target: Option<&Path>,
+    host: Option<&str>,
+) -> Result<Vec<StoredRun>, String> {
+    let db = open(path)?;
+    let text = |path: PathBuf| Value::Text(path.to_string_lossy().into_owned());
+    // Runs record their paths as given, so match the canonical path too.
+    let params = [
+        target.map_or(Value::Null, |target| text(target.to_path_buf())),
+        target
+            .and_then(|target| std::fs::canonicalize(target).ok())
+            .map_or(Value::Null, text),
+        host.map_or(Value::Null, |host| Value::Text(host.to_string())),
+    ];
+    db.query(
+        "SELECT timestamp, julianday(timestamp), host, engine, threads, buf_size, \
+         (SELECT group_concat(path, ' ') FROM run_paths WHERE run_id = runs.id), \
+         files, errors, mb_per_s, files_per_s, p99_latency_ns \
+         FROM runs \
+         WHERE (?1 IS NULL OR id IN (SELECT run_id FROM run_paths WHERE path IN (?1, ?2))) \
+         AND (?3 IS NULL OR host = ?3) \
+         ORDER BY timestamp, id",
+        &params,
+        |row| {
+            let text = |i: usize| row[i].as_str().unwrap_or_default().to_string();
+            let int = |i: usize| row[i].as_i64().unwrap_or_default();
+            StoredRun {
+                timestamp: text(0),
+                day: row[1].as_f64().unwrap_or_default(),
+                host: text(2),
+                engine: text(3),
+                threads: int(4),
+                buf_size: int(5),
+                paths: text(6),
+                files: int(7),
+                errors: int(8),
+                mb_per_s: row[9].as_f64(),
+                files_per_s: row[10].as_f64(),
+                p99_latency_ns: row[11].as_f64(),
+            }
+        },
+    )
+}
+
+/// The release of the running kernel, as `uname -r` prints it.
+fn kernel_release() -> String {
+    // SAFETY: utsname is plain data, filled in by uname.
+    let mut uts = unsafe { std::mem::zeroed::<libc::utsname>() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return "unknown".to_string();
+    }
+    // SAFETY: uname NUL-terminates each field.
+    unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) }
+        .to_string_lossy()
+        .into_owned()
+}
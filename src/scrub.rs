@@ -0,0 +1,173 @@
+//! Scrub: re-read a tree and compare every file against a manifest saved by
+//! `read-tree --checksum`, to detect bit rot in archives.
+
+use std::{
+    collections::HashMap,
+    io,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::Args;
+use jwalk::rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use tracing::error;
+
+use crate::{
+    checksum::Xxh3,
+    engine::{print_engine_report, EngineArgs},
+    manifest::{read_manifest, FileChecksum, DEFAULT_MANIFEST},
+    read_tree::build_pool,
+    walk::{paths_or_cwd, walk_files, WalkArgs},
+};
+
+/// Options for the `scrub` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct ScrubArgs {
+    /// Manifest to compare against, as written by `read-tree --checksum`.
+    #[arg(long, value_name = "PATH", default_value = DEFAULT_MANIFEST)]
+    pub manifest: PathBuf,
+    /// Number of concurrent threads to use, for both listing and reading.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    #[command(flatten)]
+    pub engine: EngineArgs,
+
+    /// Directories to scrub, given as they were when the manifest was written
+    /// [default: CWD].
+    pub paths: Vec<PathBuf>,
+}
+
+pub fn scrub(options: &ScrubArgs) {
+    let (kind, expected) = match read_manifest(&options.manifest) {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            error!(
+                "error reading manifest {}: {err}",
+                options.manifest.to_string_lossy()
+            );
+            std::process::exit(1);
+        }
+    };
+    if options.engine.head.is_some() {
+        error!("--head cannot be used with scrub, which must read whole files");
+        std::process::exit(1);
+    }
+    let engine = EngineArgs {
+        checksum: Some(kind),
+        ..options.engine.clone()
+    };
+    if let Err(err) = engine.check_available() {
+        error!("{engine} is not available: {err}");
+        std::process::exit(1);
+    }
+
+    let dirs = paths_or_cwd(&options.paths);
+    let threads = options.threads.max(1);
+    println!(
+        "-- scrubbing {dirs:?} against {} ({} files) using {threads} threads ({engine})",
+        options.manifest.to_string_lossy(),
+        expected.len(),
+    );
+
+    let t1 = Instant::now();
+    let mut files = Vec::new();
+    for dir in &dirs {
+        files.extend(walk_files(dir, threads, &options.walk).map(|entry| entry.path()));
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- list: {:.0} files/s  ({} files in {dur_s} s)",
+        files.len() as f64 / dur_s,
+        files.len(),
+    );
+
+    let t1 = Instant::now();
+    let results = build_pool(threads).install(|| {
+        files
+            .par_iter()
+            .map(|path| hash_file(path, &engine))
+            .collect::<Vec<_>>()
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+    let bytes = results
+        .iter()
+        .filter_map(|result| result.as_ref().ok())
+        .map(|(size, _)| size)
+        .sum::<u64>();
+    let total_size_mb = bytes as f64 / 1_000_000.0;
+    println!(
+        "-- read: {:.0} MB/s   {:.0} files/s  ({} MB in {} s)",
+        total_size_mb / dur_s,
+        files.len() as f64 / dur_s,
+        total_size_mb,
+        dur_s,
+    );
+    print_engine_report();
+
+    let mut by_path = expected
+        .iter()
+        .map(|checksum| (checksum.path.as_path(), checksum))
+        .collect::<HashMap<_, _>>();
+    let (mut good, mut mismatched, mut unreadable, mut new) = (0u64, 0u64, 0u64, 0u64);
+    for (path, result) in files.iter().zip(results) {
+        let Some(FileChecksum { size, hash, .. }) = by_path.remove(path.as_path()) else {
+            println!("-- new: {}", path.display());
+            new += 1;
+            continue;
+        };
+        match result {
+            Ok((actual_size, _)) if actual_size != *size => {
+                println!(
+                    "-- mismatch: {} is {actual_size} bytes, expected {size}",
+                    path.display()
+                );
+                mismatched += 1;
+            }
+            Ok((_, actual_hash)) if actual_hash != *hash => {
+                println!(
+                    "-- mismatch: {} has checksum {actual_hash:016x}, expected {hash:016x}",
+                    path.display()
+                );
+                mismatched += 1;
+            }
+            Ok(_) => good += 1,
+            Err(err) => {
+                println!("-- unreadable: {}: {err}", path.display());
+                unreadable += 1;
+            }
+        }
+    }
+    // Report missing files in manifest order, which is sorted by path.
+    let missing = expected
+        .iter()
+        .filter(|checksum| by_path.contains_key(checksum.path.as_path()))
+        .inspect(|checksum| println!("-- missing: {}", checksum.path.display()))
+        .count();
+
+    println!(
+        "-- scrub: {good} good, {mismatched} mismatched, {unreadable} unreadable, {missing} missing, {new} new"
+    );
+    if mismatched > 0 || unreadable > 0 || missing > 0 {
+        println!("-- FAILED: the tree does not match the manifest");
+        std::process::exit(1);
+    }
+}
+
+/// Read the whole of a file, returning its size and checksum.
+fn hash_file(path: &Path, engine: &EngineArgs) -> Result<(u64, u64), io::Error> {
+    let pathstr = path.to_string_lossy();
+    let mut hasher = Xxh3::default();
+    let mut bytes = 0;
+    let f = engine.with_thread_engine(|engine| {
+        let mut f = engine.open(path)?;
+        let size = f.metadata()?.len();
+        engine.read_contents(&mut f, size, &pathstr, &mut bytes, Some(&mut hasher))?;
+        Ok(f)
+    })?;
+    engine.finish_file(&f, &pathstr);
+    Ok((bytes, hasher.digest()))
+}
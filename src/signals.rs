@@ -0,0 +1,80 @@
+//! Signals that steer a read-tree run while it goes on: Ctrl-C stops it early
+//! with the results so far, SIGUSR1 asks for a progress report, and SIGUSR2
+//! pauses and resumes the reads.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use crate::exit_code;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static STATUS_REQUESTED: AtomicBool = AtomicBool::new(false);
+static PAUSED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::Relaxed) {
+        // A second Ctrl-C doesn't wait for the reads in progress.
+        // SAFETY: _exit is async-signal-safe.
+        unsafe { libc::_exit(exit_code::INTERRUPTED) };
+    }
+}
+
+/// From now on, have SIGINT set `interrupted()` rather than kill the process,
+/// unless it comes twice.
+pub fn catch_interrupt() {
+    install(libc::SIGINT, on_interrupt);
+}
+
+/// Whether SIGINT has been received since `catch_interrupt()`.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+extern "C" fn on_status_request(_signal: libc::c_int) {
+    STATUS_REQUESTED.store(true, Ordering::Relaxed);
+}
+
+/// Have SIGUSR1 ask for a progress report rather than kill the process.
+pub fn catch_status_request() {
+    install(libc::SIGUSR1, on_status_request);
+}
+
+/// Whether SIGUSR1 has asked for a progress report since the last call.
+pub fn take_status_request() -> bool {
+    STATUS_REQUESTED.swap(false, Ordering::Relaxed)
+}
+
+extern "C" fn on_pause_toggle(_signal: libc::c_int) {
+    PAUSED.fetch_xor(true, Ordering::Relaxed);
+}
+
+/// Have SIGUSR2 pause the reads, and resume them when it comes again.
+pub fn catch_pause_toggle() {
+    install(libc::SIGUSR2, on_pause_toggle);
+}
+
+pub fn paused() -> bool {
+    PAUSED.load(Ordering::Relaxed)
+}
+
+/// Wait while the reads are paused, unless interrupted.
+pub fn wait_while_paused() {
+    while paused() && !interrupted() {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+}
+
+fn install(signal: libc::c_int, handler: extern "C" fn(libc::c_int)) {
+    // SAFETY: a zeroed sigaction is valid, and the handler only touches
+    // atomics and async-signal-safe calls. SA_RESTART keeps reads in progress
+    // from failing with EINTR.
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(signal, &action, std::ptr::null_mut());
+    }
+}
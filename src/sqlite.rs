@@ -0,0 +1,303 @@
+//! Just enough of SQLite's C API for the results database (`--db`), from the
+//! system's libsqlite3, loaded when first needed. Nothing links against it,
+//! so iobench still runs where it isn't installed; only `--db` fails there.
+
+use std::{
+    ffi::{c_char, c_int, c_void, CStr, CString},
+    path::Path,
+    ptr,
+    sync::OnceLock,
+};
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_INTEGER: c_int = 1;
+const SQLITE_FLOAT: c_int = 2;
+const SQLITE_NULL: c_int = 5;
+const SQLITE_OPEN_READWRITE: c_int = 0x2;
+const SQLITE_OPEN_CREATE: c_int = 0x4;
+
+/// Library names to try, the unversioned one only being there with the
+/// development package.
+const LIBRARIES: [&CStr; 2] = [c"libsqlite3.so.0", c"libsqlite3.so"];
+
+type Db = c_void;
+type Stmt = c_void;
+
+/// The functions used, looked up in the library.
+struct Api {
+    open_v2: unsafe extern "C" fn(*const c_char, *mut *mut Db, c_int, *const c_char) -> c_int,
+    close: unsafe extern "C" fn(*mut Db) -> c_int,
+    errmsg: unsafe extern "C" fn(*mut Db) -> *const c_char,
+    prepare_v2: unsafe extern "C" fn(
+        *mut Db,
+        *const c_char,
+        c_int,
+        *mut *mut Stmt,
+        *mut *const c_char,
+    ) -> c_int,
+    bind_int64: unsafe extern "C" fn(*mut Stmt, c_int, i64) -> c_int,
+    bind_double: unsafe extern "C" fn(*mut Stmt, c_int, f64) -> c_int,
+    bind_text: unsafe extern "C" fn(*mut Stmt, c_int, *const c_char, c_int, isize) -> c_int,
+    bind_null: unsafe extern "C" fn(*mut Stmt, c_int) -> c_int,
+    step: unsafe extern "C" fn(*mut Stmt) -> c_int,
+    column_count: unsafe extern "C" fn(*mut Stmt) -> c_int,
+    column_type: unsafe extern "C" fn(*mut Stmt, c_int) -> c_int,
+    column_int64: unsafe extern "C" fn(*mut Stmt, c_int) -> i64,
+    column_double: unsafe extern "C" fn(*mut Stmt, c_int) -> f64,
+    column_text: unsafe extern "C" fn(*mut Stmt, c_int) -> *const u8,
+    finalize: unsafe extern "C" fn(*mut Stmt) -> c_int,
+}
+
+/// SQLITE_TRANSIENT: have SQLite copy bound text before the call returns.
+const TRANSIENT: isize = -1;
+
+fn api() -> Result<&'static Api, String> {
+    static API: OnceLock<Result<Api, String>> = OnceLock::new();
+    API.get_or_init(load).as_ref().map_err(Clone::clone)
+}
+
+fn load() -> Result<Api, String> {
+    // SAFETY: dlopen is given NUL-terminated names; a null result is handled.
+    let handle = LIBRARIES
+        .iter()
+        .map(|name| unsafe { libc::dlopen(name.as_ptr(), libc::RTLD_NOW) })
+        .find(|handle| !handle.is_null())
+        .ok_or("SQLite isn't installed: libsqlite3.so.0 couldn't be loaded")?;
+    // SAFETY: each field's type is that of the function, as declared in
+    // sqlite3.h.
+    unsafe {
+        Ok(Api {
+            open_v2: symbol(handle, c"sqlite3_open_v2")?,
+            close: symbol(handle, c"sqlite3_close")?,
+            errmsg: symbol(handle, c"sqlite3_errmsg")?,
+            prepare_v2: symbol(handle, c"sqlite3_prepare_v2")?,
+            bind_int64: symbol(handle, c"sqlite3_bind_int64")?,
+            bind_double: symbol(handle, c"sqlite3_bind_double")?,
+            bind_text: symbol(handle, c"sqlite3_bind_text")?,
+            bind_null: symbol(handle, c"sqlite3_bind_null")?,
+            step: symbol(handle, c"sqlite3_step")?,
+            column_count: symbol(handle, c"sqlite3_column_count")?,
+            column_type: symbol(handle, c"sqlite3_column_type")?,
+            column_int64: symbol(handle, c"sqlite3_column_int64")?,
+            column_double: symbol(handle, c"sqlite3_column_double")?,
+            column_text: symbol(handle, c"sqlite3_column_text")?,
+            finalize: symbol(handle, c"sqlite3_finalize")?,
+        })
+    }
+}
+
+/// Look up a function in a library loaded with dlopen.
+///
+/// # Safety
+///
+/// `T` must be a function pointer of the function's type.
+unsafe fn symbol<T>(handle: *mut c_void, name: &CStr) -> Result<T, String> {
+    let symbol = libc::dlsym(handle, name.as_ptr());
+    if symbol.is_null() {
+        return Err(format!(
+            "{} is missing from libsqlite3",
+            name.to_string_lossy()
+        ));
+    }
+    Ok(std::mem::transmute_copy::<*mut c_void, T>(&symbol))
+}
+
+/// A value bound to a statement parameter or read from a column.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Int(i64),
+    Real(f64),
+    Text(String),
+}
+
+impl Value {
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(n) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Int(n) => Some(n as f64),
+            Value::Real(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// An open database connection.
+pub struct Connection {
+    api: &'static Api,
+    db: *mut Db,
+}
+
+impl Connection {
+    /// Open the database at `path`, creating it if it doesn't exist.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let api = api()?;
+        let name = CString::new(path.as_os_str().as_encoded_bytes())
+            .map_err(|_| "the path has a NUL in it".to_string())?;
+        let mut db = ptr::null_mut();
+        // SAFETY: name is NUL-terminated and db receives the handle, which
+        // SQLite allocates even on failure, so it's closed either way.
+        let rc = unsafe {
+            (api.open_v2)(
+                name.as_ptr(),
+                &mut db,
+                SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE,
+                ptr::null(),
+            )
+        };
+        let connection = Self { api, db };
+        if rc != SQLITE_OK {
+            return Err(connection.error());
+        }
+        Ok(connection)
+    }
+
+    /// The message for the last error on this connection.
+    fn error(&self) -> String {
+        // SAFETY: the message is a NUL-terminated string owned by SQLite.
+        unsafe { CStr::from_ptr((self.api.errmsg)(self.db)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Run a statement that returns no rows, with its parameters bound to
+    /// `params` in order.
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<(), String> {
+        self.query(sql, params, |_| ())?;
+        Ok(())
+    }
+
+    /// Run several statements with no parameters, such as a schema.
+    pub fn execute_batch(&self, sql: &str) -> Result<(), String> {
+        sql.split(';')
+            .filter(|statement| !statement.trim().is_empty())
+            .try_for_each(|statement| self.execute(statement, &[]))
+    }
+
+    /// Run a query with its parameters bound to `params`, returning what
+    /// `row` makes of each row's columns.
+    pub fn query<T>(
+        &self,
+        sql: &str,
+        params: &[Value],
+        mut row: impl FnMut(&[Value]) -> T,
+    ) -> Result<Vec<T>, String> {
+        let statement = Statement::prepare(self, sql)?;
+        for (i, param) in params.iter().enumerate() {
+            statement.bind(i as c_int + 1, param)?;
+        }
+        let mut rows = Vec::new();
+        while let Some(columns) = statement.step()? {
+            rows.push(row(&columns));
+        }
+        Ok(rows)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        // SAFETY: every statement is finalized by its own Drop first.
+        unsafe { (self.api.close)(self.db) };
+    }
+}
+
+struct Statement<'a> {
+    connection: &'a Connection,
+    stmt: *mut Stmt,
+}
+
+impl<'a> Statement<'a> {
+    fn prepare(connection: &'a Connection, sql: &str) -> Result<Self, String> {
+        let mut stmt = ptr::null_mut();
+        // SAFETY: the SQL's length is passed, so it needn't be NUL-terminated.
+        let rc = unsafe {
+            (connection.api.prepare_v2)(
+                connection.db,
+                sql.as_ptr().cast(),
+                sql.len() as c_int,
+                &mut stmt,
+                ptr::null_mut(),
+            )
+        };
+        if rc != SQLITE_OK {
+            return Err(connection.error());
+        }
+        Ok(Self { connection, stmt })
+    }
+
+    fn bind(&self, index: c_int, value: &Value) -> Result<(), String> {
+        let api = self.connection.api;
+        // SAFETY: the statement is prepared, and SQLite copies bound text
+        // (SQLITE_TRANSIENT), whose length is given.
+        let rc = unsafe {
+            match value {
+                Value::Null => (api.bind_null)(self.stmt, index),
+                Value::Int(n) => (api.bind_int64)(self.stmt, index, *n),
+                Value::Real(x) => (api.bind_double)(self.stmt, index, *x),
+                Value::Text(s) => (api.bind_text)(
+                    self.stmt,
+                    index,
+                    s.as_ptr().cast(),
+                    s.len() as c_int,
+                    TRANSIENT,
+                ),
+            }
+        };
+        if rc != SQLITE_OK {
+            return Err(self.connection.error());
+        }
+        Ok(())
+    }
+
+    /// Step to the next row, returning its columns, or None when done.
+    fn step(&self) -> Result<Option<Vec<Value>>, String> {
+        let api = self.connection.api;
+        // SAFETY: the statement is prepared and its parameters are bound.
+        match unsafe { (api.step)(self.stmt) } {
+            SQLITE_DONE => Ok(None),
+            SQLITE_ROW => {
+                // SAFETY: the statement is on a row, so its columns can be
+                // read; text is copied out before the next step.
+                let row = unsafe {
+                    (0..(api.column_count)(self.stmt))
+                        .map(|i| match (api.column_type)(self.stmt, i) {
+                            SQLITE_NULL => Value::Null,
+                            SQLITE_INTEGER => Value::Int((api.column_int64)(self.stmt, i)),
+                            SQLITE_FLOAT => Value::Real((api.column_double)(self.stmt, i)),
+                            _ => {
+                                let text = (api.column_text)(self.stmt, i);
+                                Value::Text(
+                                    CStr::from_ptr(text.cast()).to_string_lossy().into_owned(),
+                                )
+                            }
+                        })
+                        .collect()
+                };
+                Ok(Some(row))
+            }
+            _ => Err(self.connection.error()),
+        }
+    }
+}
+
+impl Drop for Statement<'_> {
+    fn drop(&mut self) {
+        // SAFETY: the statement was prepared and is finalized only here.
+        unsafe { (self.connection.api.finalize)(self.stmt) };
+    }
+}
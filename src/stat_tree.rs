@@ -0,0 +1,104 @@
+//! Metadata benchmark: list a directory tree, then stat every entry in it
+//! without opening any files.
+
+use std::{fs, path::PathBuf, time::Instant};
+
+use clap::Args;
+use jwalk::{
+    rayon::iter::{IntoParallelRefIterator, ParallelIterator},
+    DirEntry,
+};
+use tracing::debug;
+
+use crate::{
+    histogram::Histogram,
+    output::print_latency,
+    read_tree::build_pool,
+    walk::{paths_or_cwd, walk_entries, WalkArgs},
+};
+
+/// Options for the `stat-tree` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct StatTreeArgs {
+    /// Number of concurrent threads to use, for both listing and stat'ing.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+
+    #[command(flatten)]
+    pub walk: WalkArgs,
+
+    /// Directories to walk [default: CWD].
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct StatStats {
+    entries: u64,
+    errors: u64,
+    /// Latency of each successful stat, in nanoseconds.
+    latency: Histogram,
+}
+
+impl StatStats {
+    fn combine(mut self, other: Self) -> Self {
+        self.entries += other.entries;
+        self.errors += other.errors;
+        self.latency.merge(&other.latency);
+        self
+    }
+}
+
+pub fn stat_tree(options: &StatTreeArgs) {
+    let dirs = paths_or_cwd(&options.paths);
+    let threads = options.threads.max(1);
+    println!("-- stat'ing {dirs:?} using {threads} threads");
+
+    let t1 = Instant::now();
+    let mut entries = Vec::new();
+    for dir in &dirs {
+        entries.extend(walk_entries(dir, threads, &options.walk));
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- list: {:.0} entries/s  ({} entries in {dur_s} s)",
+        entries.len() as f64 / dur_s,
+        entries.len(),
+    );
+
+    let pool = build_pool(threads);
+    let t1 = Instant::now();
+    let stats = pool.install(|| {
+        entries
+            .par_iter()
+            .map(stat_entry)
+            .reduce(StatStats::default, StatStats::combine)
+    });
+    let dur_s = t1.elapsed().as_secs_f64();
+    println!(
+        "-- stat: {:.0} entries/s  ({} entries in {dur_s} s)",
+        stats.entries as f64 / dur_s,
+        stats.entries,
+    );
+    if stats.errors > 0 {
+        println!("-- {} entries could not be stat'ed", stats.errors);
+    }
+    print_latency("stat", &stats.latency);
+}
+
+/// Stat one entry without following symlinks, as `ls -l` or `du` would.
+fn stat_entry(entry: &DirEntry<((), ())>) -> StatStats {
+    let mut stats = StatStats::default();
+    let path = entry.path();
+    let t1 = Instant::now();
+    match fs::symlink_metadata(&path) {
+        Ok(_) => {
+            stats.latency.record(t1.elapsed().as_nanos() as u64);
+            stats.entries = 1;
+        }
+        Err(err) => {
+            debug!("error stat'ing {}: {err}", path.to_string_lossy());
+            stats.errors = 1;
+        }
+    }
+    stats
+}
@@ -0,0 +1,199 @@
+//! Descriptive statistics over the results of repeated runs.
+
+/// Mean, spread and range of a set of measurements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub mean: f64,
+    /// Sample standard deviation (n - 1 denominator); zero for a single value.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    pub fn of(values: &[f64]) -> Self {
+        let count = values.len();
+        if count == 0 {
+            return Self {
+                count,
+                mean: 0.0,
+                stddev: 0.0,
+                min: 0.0,
+                max: 0.0,
+            };
+        }
+        let mean = values.iter().sum::<f64>() / count as f64;
+        let stddev = if count > 1 {
+            let sum_sq = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>();
+            (sum_sq / (count - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+        Self {
+            count,
+            mean,
+            stddev,
+            min: values.iter().copied().fold(f64::INFINITY, f64::min),
+            max: values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        }
+    }
+
+    /// Standard deviation as a percentage of the mean.
+    pub fn relative_stddev(&self) -> f64 {
+        if self.mean == 0.0 {
+            0.0
+        } else {
+            100.0 * self.stddev / self.mean
+        }
+    }
+}
+
+/// Welch's t-test of the difference between the means of two sets of
+/// measurements, which needn't have the same variance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TTest {
+    /// The second mean less the first.
+    pub difference: f64,
+    /// Two-sided p-value: the chance of a difference at least this large if
+    /// the means were the same.
+    pub p: f64,
+    /// Half the width of the confidence interval of the difference.
+    pub margin: f64,
+}
+
+impl TTest {
+    /// Test the difference from `a` to `b`, with a confidence interval at
+    /// `confidence` (e.g. 0.95). Both need at least two values.
+    pub fn welch(a: &Summary, b: &Summary, confidence: f64) -> Option<Self> {
+        if a.count < 2 || b.count < 2 {
+            return None;
+        }
+        let va = a.stddev.powi(2) / a.count as f64;
+        let vb = b.stddev.powi(2) / b.count as f64;
+        let se = (va + vb).sqrt();
+        let difference = b.mean - a.mean;
+        if se == 0.0 {
+            let p = if difference == 0.0 { 1.0 } else { 0.0 };
+            return Some(Self {
+                difference,
+                p,
+                margin: 0.0,
+            });
+        }
+        // Welch–Satterthwaite degrees of freedom.
+        let df = (va + vb).powi(2)
+            / (va.powi(2) / (a.count - 1) as f64 + vb.powi(2) / (b.count - 1) as f64);
+        Some(Self {
+            difference,
+            p: (2.0 * t_tail(difference.abs() / se, df)).min(1.0),
+            margin: t_critical((1.0 - confidence) / 2.0, df) * se,
+        })
+    }
+}
+
+/// P(T > t) for Student's t distribution with `df` degrees of freedom, t >= 0.
+fn t_tail(t: f64, df: f64) -> f64 {
+    0.5 * incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+/// The t with P(T > t) = `tail`, found by bisection.
+fn t_critical(tail: f64, df: f64) -> f64 {
+    let mut high = 1.0;
+    while t_tail(high, df) > tail && high < 1e12 {
+        high *= 2.0;
+    }
+    let mut low = 0.0;
+    for _ in 0..100 {
+        let mid = (low + high) / 2.0;
+        if t_tail(mid, df) > tail {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    (low + high) / 2.0
+}
+
+/// The regularized incomplete beta function I_x(a, b), from its continued
+/// fraction (Numerical Recipes, 6.4).
+fn incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The fraction converges quickly only on this side of the mean.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// The continued fraction for the incomplete beta function, by Lentz's method.
+fn beta_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const TINY: f64 = 1e-300;
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut h = d;
+    for m in 1..300 {
+        let m = f64::from(m);
+        let m2 = 2.0 * m;
+        for numerator in [
+            m * (b - m) * x / ((a + m2 - 1.0) * (a + m2)),
+            -(a + m) * (a + b + m) * x / ((a + m2) * (a + m2 + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < 1e-15 {
+            break;
+        }
+    }
+    h
+}
+
+/// ln Γ(x) for x > 0, by the Lanczos approximation (g = 7).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        // Reflection formula.
+        let pi = std::f64::consts::PI;
+        return (pi / (pi * x).sin()).ln() - ln_gamma(1.0 - x);
+    }
+    let x = x - 1.0;
+    let t = x + 7.5;
+    let sum = COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(COEFFICIENTS[0], |sum, (i, c)| {
+            sum + c / (x + i as f64 + 1.0)
+        });
+    0.5 * (2.0 * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln()
+}
@@ -0,0 +1,53 @@
+//! Summary metrics pushed to a statsd daemon over UDP at the end of a run
+//! (`--statsd`), for monitoring that predates anything scraped.
+
+use std::{
+    fmt::Write as _,
+    io,
+    net::{ToSocketAddrs, UdpSocket},
+};
+
+use crate::output::RunRecord;
+
+/// Prefix of every metric name.
+const PREFIX: &str = "iobench.read";
+
+/// Send the results of each run to the statsd daemon at `addr`, a datagram
+/// per run in the order they ran. Returns the number of datagrams sent.
+pub fn send(addr: &str, records: &[RunRecord]) -> Result<usize, io::Error> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::other(format!("{addr} has no addresses")))?;
+    let bind = if addr.is_ipv4() {
+        "0.0.0.0:0"
+    } else {
+        "[::]:0"
+    };
+    let socket = UdpSocket::bind(bind)?;
+    for record in records {
+        socket.send_to(packet(record).as_bytes(), addr)?;
+    }
+    Ok(records.len())
+}
+
+/// The metrics of one run, a line each: gauges for the rates and latency, and
+/// a counter for the files that couldn't be read.
+fn packet(record: &RunRecord) -> String {
+    let mut packet = String::new();
+    for (name, value) in [
+        ("mb_per_s", record.mb_per_s()),
+        ("files_per_s", record.files_per_s()),
+    ] {
+        // statsd can't parse infinities or NaN.
+        if value.is_finite() {
+            let _ = writeln!(packet, "{PREFIX}.{name}:{value:.3}|g");
+        }
+    }
+    if record.latency.count() > 0 {
+        let p99_ms = record.latency.percentile(99.0) as f64 / 1e6;
+        let _ = writeln!(packet, "{PREFIX}.p99_latency_ms:{p99_ms:.3}|g");
+    }
+    let _ = writeln!(packet, "{PREFIX}.errors:{}|c", record.errors);
+    packet
+}
@@ -0,0 +1,297 @@
+//! Minimal TOML reader, for the configuration file (`--config`). Only what
+//! options need is supported: tables of `key = value` lines whose values are
+//! strings, integers, floats, booleans or arrays of them. Dotted keys, inline
+//! tables, arrays of tables, multi-line strings and dates are rejected.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Array(Vec<Value>),
+}
+
+/// A table of a document, with the line of each key for error messages.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    /// The table's name; empty for the keys before any table header.
+    pub name: String,
+    pub entries: Vec<(String, Value, usize)>,
+}
+
+/// Parse a complete TOML document into its tables, the keys before any
+/// table header first.
+pub fn parse(s: &str) -> Result<Vec<Table>, String> {
+    let mut parser = Parser {
+        bytes: s.as_bytes(),
+        pos: 0,
+    };
+    let mut tables = vec![Table::default()];
+    loop {
+        parser.skip_blank_lines();
+        let line = parser.line();
+        match parser.bytes.get(parser.pos) {
+            None => return Ok(tables),
+            Some(b'[') => {
+                parser.pos += 1;
+                if parser.bytes.get(parser.pos) == Some(&b'[') {
+                    return Err(parser.error("arrays of tables aren't supported"));
+                }
+                parser.skip_spaces();
+                let name = parser.key()?;
+                parser.skip_spaces();
+                parser.expect(b']')?;
+                parser.end_of_line()?;
+                if tables.iter().any(|table| table.name == name) {
+                    return Err(format!("table [{name}] defined twice on line {line}"));
+                }
+                tables.push(Table {
+                    name,
+                    entries: Vec::new(),
+                });
+            }
+            Some(_) => {
+                let key = parser.key()?;
+                parser.skip_spaces();
+                parser.expect(b'=')?;
+                let value = parser.value()?;
+                parser.end_of_line()?;
+                let table = tables.last_mut().unwrap();
+                if table.entries.iter().any(|(k, _, _)| *k == key) {
+                    return Err(format!("key {key:?} defined twice on line {line}"));
+                }
+                table.entries.push((key, value, line));
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// The line the parser is on, counting from 1.
+    fn line(&self) -> usize {
+        1 + self.bytes[..self.pos]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count()
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!("{msg} on line {}", self.line())
+    }
+
+    fn skip_spaces(&mut self) {
+        while matches!(self.bytes.get(self.pos), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_comment(&mut self) {
+        if self.bytes.get(self.pos) == Some(&b'#') {
+            while self.bytes.get(self.pos).is_some_and(|&b| b != b'\n') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    /// Skip whitespace, line breaks and comments, as between the lines of a
+    /// table or the items of an array.
+    fn skip_blank_lines(&mut self) {
+        loop {
+            self.skip_spaces();
+            self.skip_comment();
+            match self.bytes.get(self.pos) {
+                Some(b'\n') => self.pos += 1,
+                Some(b'\r') if self.bytes.get(self.pos + 1) == Some(&b'\n') => self.pos += 2,
+                _ => return,
+            }
+        }
+    }
+
+    fn end_of_line(&mut self) -> Result<(), String> {
+        self.skip_spaces();
+        self.skip_comment();
+        match self.bytes.get(self.pos) {
+            None | Some(b'\n') => Ok(()),
+            Some(b'\r') if self.bytes.get(self.pos + 1) == Some(&b'\n') => Ok(()),
+            Some(_) => Err(self.error("expected the end of the line")),
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), String> {
+        if self.bytes.get(self.pos) != Some(&byte) {
+            return Err(self.error(&format!("expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    /// A bare or quoted key.
+    fn key(&mut self) -> Result<String, String> {
+        let key = match self.bytes.get(self.pos) {
+            Some(b'"') => self.basic_string()?,
+            Some(b'\'') => self.literal_string()?,
+            _ => {
+                let start = self.pos;
+                while self
+                    .bytes
+                    .get(self.pos)
+                    .is_some_and(|&b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+                {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return Err(self.error("expected a key"));
+                }
+                String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned()
+            }
+        };
+        self.skip_spaces();
+        if self.bytes.get(self.pos) == Some(&b'.') {
+            return Err(self.error("dotted keys aren't supported"));
+        }
+        Ok(key)
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_spaces();
+        let rest = &self.bytes[self.pos..];
+        match rest.first() {
+            Some(b'"') if rest.starts_with(b"\"\"\"") => {
+                Err(self.error("multi-line strings aren't supported"))
+            }
+            Some(b'\'') if rest.starts_with(b"'''") => {
+                Err(self.error("multi-line strings aren't supported"))
+            }
+            Some(b'"') => self.basic_string().map(Value::String),
+            Some(b'\'') => self.literal_string().map(Value::String),
+            Some(b'[') => self.array(),
+            Some(b'{') => Err(self.error("inline tables aren't supported")),
+            Some(b't') if rest.starts_with(b"true") => {
+                self.pos += 4;
+                Ok(Value::Bool(true))
+            }
+            Some(b'f') if rest.starts_with(b"false") => {
+                self.pos += 5;
+                Ok(Value::Bool(false))
+            }
+            Some(_) => self.number(),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect(b'[')?;
+        let mut items = Vec::new();
+        loop {
+            self.skip_blank_lines();
+            if self.bytes.get(self.pos) == Some(&b']') {
+                self.pos += 1;
+                return Ok(Value::Array(items));
+            }
+            items.push(self.value()?);
+            self.skip_blank_lines();
+            match self.bytes.get(self.pos) {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {}
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    /// An integer or float, with `_` between digits allowed.
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|&b| b.is_ascii_alphanumeric() || b"+-._:".contains(&b))
+        {
+            self.pos += 1;
+        }
+        let text = String::from_utf8_lossy(&self.bytes[start..self.pos]).replace('_', "");
+        if let Ok(n) = text.parse() {
+            return Ok(Value::Integer(n));
+        }
+        if let Ok(x) = text.parse() {
+            return Ok(Value::Float(x));
+        }
+        self.pos = start;
+        Err(self.error("invalid value (strings need quotes)"))
+    }
+
+    /// Parse a string in double quotes, with the parser on its opening quote.
+    fn basic_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let mut out = Vec::new();
+        loop {
+            let Some(&byte) = self.bytes.get(self.pos) else {
+                return Err(self.error("unterminated string"));
+            };
+            self.pos += 1;
+            match byte {
+                b'"' => break,
+                b'\n' => return Err(self.error("unterminated string")),
+                b'\\' => {
+                    let Some(&escape) = self.bytes.get(self.pos) else {
+                        return Err(self.error("unterminated string"));
+                    };
+                    self.pos += 1;
+                    let c = match escape {
+                        b'"' => '"',
+                        b'\\' => '\\',
+                        b'b' => '\u{8}',
+                        b'f' => '\u{c}',
+                        b'n' => '\n',
+                        b'r' => '\r',
+                        b't' => '\t',
+                        b'u' => self.unicode_escape(4)?,
+                        b'U' => self.unicode_escape(8)?,
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    let mut buf = [0; 4];
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+                _ => out.push(byte),
+            }
+        }
+        String::from_utf8(out).map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    /// Parse a string in single quotes, which has no escapes.
+    fn literal_string(&mut self) -> Result<String, String> {
+        self.pos += 1;
+        let start = self.pos;
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(|&b| b != b'\'' && b != b'\n')
+        {
+            self.pos += 1;
+        }
+        if self.bytes.get(self.pos) != Some(&b'\'') {
+            return Err(self.error("unterminated string"));
+        }
+        self.pos += 1;
+        String::from_utf8(self.bytes[start..self.pos - 1].to_vec())
+            .map_err(|_| self.error("invalid UTF-8 in string"))
+    }
+
+    /// Parse the `digits` hex digits of a `\u` or `\U` escape.
+    fn unicode_escape(&mut self, digits: usize) -> Result<char, String> {
+        let code = self
+            .bytes
+            .get(self.pos..self.pos + digits)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error("invalid unicode escape"))?;
+        self.pos += digits;
+        Ok(code)
+    }
+}
@@ -0,0 +1,92 @@
+//! Timeline of every file read (`--trace-out`), in the Chrome trace event
+//! format, for viewing in chrome://tracing or Perfetto. Each measured pass is
+//! shown as a process and each reader thread as a thread in it.
+
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::output::json_string;
+
+/// One file read by a reader thread.
+pub struct FileEvent {
+    pub path: PathBuf,
+    pub thread: usize,
+    pub start: Instant,
+    /// Time to open and read the file, of which `open_ns` was opening it.
+    pub duration_ns: u64,
+    pub open_ns: u64,
+    pub bytes: u64,
+    pub failed: bool,
+}
+
+/// The events of one pass, and the name it is shown by.
+pub struct PassTrace {
+    pub name: String,
+    pub events: Vec<FileEvent>,
+}
+
+/// Write the events of every pass to `path`, with times counted from the
+/// first file started, returning the number of events written.
+pub fn write_trace(path: &Path, passes: &[PassTrace]) -> Result<usize, std::io::Error> {
+    let Some(epoch) = passes
+        .iter()
+        .flat_map(|pass| pass.events.iter().map(|event| event.start))
+        .min()
+    else {
+        std::fs::write(path, "{\"traceEvents\":[]}\n")?;
+        return Ok(0);
+    };
+    let mut out = BufWriter::new(File::create(path)?);
+    write!(out, "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[")?;
+    let mut first = true;
+    let mut separator = |out: &mut BufWriter<File>| {
+        let separator = if first { "\n" } else { ",\n" };
+        first = false;
+        out.write_all(separator.as_bytes())
+    };
+    let mut written = 0;
+    for (pass, trace) in passes.iter().enumerate() {
+        let pid = pass + 1;
+        separator(&mut out)?;
+        write!(
+            out,
+            "{{\"name\":\"process_name\",\"ph\":\"M\",\"pid\":{pid},\"args\":{{\"name\":{}}}}}",
+            json_string(&trace.name)
+        )?;
+        let mut threads = trace
+            .events
+            .iter()
+            .map(|event| event.thread)
+            .collect::<Vec<_>>();
+        threads.sort_unstable();
+        threads.dedup();
+        for thread in threads {
+            separator(&mut out)?;
+            write!(
+                out,
+                "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{pid},\"tid\":{thread},\"args\":{{\"name\":\"reader {thread}\"}}}}"
+            )?;
+        }
+        for event in &trace.events {
+            separator(&mut out)?;
+            write!(
+                out,
+                "{{\"name\":{},\"cat\":\"{}\",\"ph\":\"X\",\"pid\":{pid},\"tid\":{},\"ts\":{:.3},\"dur\":{:.3},\"args\":{{\"bytes\":{}}}}}",
+                json_string(&event.path.to_string_lossy()),
+                if event.failed { "error" } else { "read" },
+                event.thread,
+                (event.start - epoch).as_nanos() as f64 / 1e3,
+                event.duration_ns as f64 / 1e3,
+                event.bytes,
+            )?;
+            written += 1;
+        }
+    }
+    writeln!(out, "\n]}}")?;
+    out.flush()?;
+    Ok(written)
+}
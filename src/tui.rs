@@ -0,0 +1,228 @@
+//! Live dashboard for long read runs (`--tui`): throughput graph, what each
+//! reader thread is doing, errors and the slowest files so far. It is drawn
+//! with ANSI escapes on the terminal's alternate screen, which is restored
+//! when the pass ends so the summary lines appear as usual.
+
+use std::{
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{output::Sample, units::format_latency};
+
+/// Number of slowest files listed.
+const SLOWEST: usize = 5;
+/// How often the dashboard is redrawn at most.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(500);
+/// Height of the throughput graph, in rows.
+const GRAPH_ROWS: usize = 8;
+/// Eighths of a character cell, for the top of each graph column.
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// What the reader threads are doing, updated as each file starts and ends.
+pub struct Activity {
+    threads: Vec<Mutex<ThreadSlot>>,
+    errors: AtomicU64,
+    /// The slowest files read so far, slowest first, with their latency in nanoseconds.
+    slowest: Mutex<Vec<(u64, PathBuf)>>,
+}
+
+#[derive(Default)]
+struct ThreadSlot {
+    current: Option<PathBuf>,
+    files: u64,
+}
+
+impl Activity {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: (0..threads).map(|_| Mutex::default()).collect(),
+            errors: AtomicU64::new(0),
+            slowest: Mutex::default(),
+        }
+    }
+
+    pub fn start_file(&self, thread: usize, path: &Path) {
+        if let Some(slot) = self.threads.get(thread) {
+            slot.lock().unwrap().current = Some(path.to_path_buf());
+        }
+    }
+
+    /// Record the end of the thread's current file, which took `latency`
+    /// nanoseconds to read, or failed if there is none.
+    pub fn finish_file(&self, thread: usize, latency: Option<u64>) {
+        let Some(slot) = self.threads.get(thread) else {
+            return;
+        };
+        let mut slot = slot.lock().unwrap();
+        slot.files += 1;
+        let path = slot.current.take();
+        let Some(latency) = latency else {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+            return;
+        };
+        let mut slowest = self.slowest.lock().unwrap();
+        if slowest.len() < SLOWEST || slowest.last().is_some_and(|&(min, _)| latency > min) {
+            slowest.push((latency, path.unwrap_or_default()));
+            slowest.sort_by_key(|&(latency, _)| std::cmp::Reverse(latency));
+            slowest.truncate(SLOWEST);
+        }
+    }
+}
+
+/// The state of the pass shown at the top of the dashboard.
+pub struct Totals {
+    pub files: u64,
+    pub total_files: Option<u64>,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+/// The dashboard, on the alternate screen while it exists.
+pub struct Dashboard {
+    title: String,
+    last_draw: Option<Instant>,
+}
+
+impl Dashboard {
+    pub fn new(title: String) -> Self {
+        // Switch to the alternate screen and hide the cursor.
+        print_flush("\x1b[?1049h\x1b[?25l");
+        Self {
+            title,
+            last_draw: None,
+        }
+    }
+
+    /// Redraw the whole screen, unless it was redrawn very recently.
+    pub fn draw(&mut self, activity: &Activity, series: &[Sample], totals: &Totals) {
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last| now - last < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.last_draw = Some(now);
+        let width = terminal_width();
+        let secs = totals.elapsed.as_secs_f64();
+        let mut out = String::from("\x1b[H\x1b[2J");
+        let mut line = |text: String| {
+            out.push_str(&truncate(&text, width));
+            out.push_str("\r\n");
+        };
+        line(format!("iobench {}   {:.0} s", self.title, secs));
+        let files = match totals.total_files {
+            Some(total) => format!("{}/{total}", totals.files),
+            None => totals.files.to_string(),
+        };
+        line(format!(
+            "files {files}   {:.1} MB   mean {:.0} MB/s   now {:.0} MB/s   errors {}",
+            totals.bytes as f64 / 1e6,
+            totals.bytes as f64 / 1e6 / secs.max(1e-9),
+            series
+                .last()
+                .map_or(0.0, |sample| sample.bytes as f64 / 1e6),
+            activity.errors.load(Ordering::Relaxed),
+        ));
+        line(String::new());
+
+        // One column per second, as many of the latest as fit.
+        let shown = &series[series.len().saturating_sub(width.saturating_sub(8))..];
+        let max = shown.iter().map(|sample| sample.bytes).max().unwrap_or(0);
+        line(format!("MB/s per second (peak {:.0})", max as f64 / 1e6));
+        for row in (0..GRAPH_ROWS).rev() {
+            let mut text = String::from("  ");
+            for sample in shown {
+                // Height of this column in eighths of a row.
+                let eighths = if max > 0 {
+                    (sample.bytes as f64 / max as f64 * (GRAPH_ROWS * 8) as f64).round() as usize
+                } else {
+                    0
+                };
+                text.push(match eighths.saturating_sub(row * 8) {
+                    0 => ' ',
+                    n => BLOCKS[n.min(8) - 1],
+                });
+            }
+            line(text);
+        }
+        line(String::new());
+
+        line("threads".to_string());
+        for (thread, slot) in activity.threads.iter().enumerate() {
+            let slot = slot.lock().unwrap();
+            let current = slot
+                .current
+                .as_ref()
+                .map_or("idle".to_string(), |path| path.display().to_string());
+            line(format!(
+                "  #{thread:<3} {:>8} files   {current}",
+                slot.files
+            ));
+        }
+        line(String::new());
+
+        line("slowest files".to_string());
+        for (latency, path) in activity.slowest.lock().unwrap().iter() {
+            line(format!(
+                "  {:>10}   {}",
+                format_latency(*latency),
+                path.display()
+            ));
+        }
+        print_flush(&out);
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        // Show the cursor and go back to the normal screen.
+        print_flush("\x1b[?25h\x1b[?1049l");
+    }
+}
+
+/// A one-line chart of the values, one block character per value, scaled so
+/// the largest is a full block. Zero is shown as a space.
+pub fn sparkline(values: &[f64]) -> String {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    values
+        .iter()
+        .map(|&value| {
+            if value <= 0.0 || max <= 0.0 {
+                return ' ';
+            }
+            let level = (value / max * BLOCKS.len() as f64).ceil() as usize;
+            BLOCKS[level.clamp(1, BLOCKS.len()) - 1]
+        })
+        .collect()
+}
+
+fn print_flush(s: &str) {
+    let mut stdout = io::stdout().lock();
+    let _ = stdout.write_all(s.as_bytes());
+    let _ = stdout.flush();
+}
+
+/// Width of the terminal on stdout, or 80 columns if it isn't one.
+fn terminal_width() -> usize {
+    // SAFETY: winsize is plain data, filled in by TIOCGWINSZ.
+    unsafe {
+        let mut size: libc::winsize = mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) == 0 && size.ws_col > 0 {
+            return usize::from(size.ws_col);
+        }
+    }
+    80
+}
+
+/// Cut a line to the terminal width, so it doesn't wrap and scroll the screen.
+fn truncate(s: &str, width: usize) -> String {
+    s.chars().take(width).collect()
+}
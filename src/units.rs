@@ -0,0 +1,103 @@
+//! Parsing of human-friendly quantities given on the command line.
+
+use std::time::Duration;
+
+/// Parse a byte size such as `4096`, `4k`, `64K`, `1M`, `2G` or `1T`.
+///
+/// Suffixes are binary multiples (1k = 1024 bytes) and case-insensitive. An
+/// optional trailing `B` or `iB` is accepted, so `4KiB` and `4kB` both mean 4096.
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let lower = s.to_ascii_lowercase();
+    let lower = lower
+        .strip_suffix("ib")
+        .or_else(|| lower.strip_suffix('b'))
+        .unwrap_or(&lower);
+    let (digits, multiplier) = match lower.chars().last() {
+        Some('k') => (&lower[..lower.len() - 1], 1u64 << 10),
+        Some('m') => (&lower[..lower.len() - 1], 1u64 << 20),
+        Some('g') => (&lower[..lower.len() - 1], 1u64 << 30),
+        Some('t') => (&lower[..lower.len() - 1], 1u64 << 40),
+        _ => (lower, 1),
+    };
+    let value: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid size: {s:?}"))?;
+    value
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size too large: {s:?}"))
+}
+
+/// Parse a duration such as `100ms`, `30s`, `1.5s`, `10m` or `2h`. A bare
+/// number is taken as seconds.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let value: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid duration: {s:?}"))?;
+    let seconds = match unit.trim() {
+        "ns" => value / 1e9,
+        "us" => value / 1e6,
+        "ms" => value / 1e3,
+        "" | "s" => value,
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => {
+            return Err(format!(
+                "invalid duration unit in {s:?} (use ms, s, m or h)"
+            ))
+        }
+    };
+    Duration::try_from_secs_f64(seconds).map_err(|_| format!("invalid duration: {s:?}"))
+}
+
+/// Parse a percentage such as `10%` or `2.5` (the `%` is optional) into a
+/// fraction between 0 and 1.
+pub fn parse_percent(s: &str) -> Result<f64, String> {
+    let s = s.trim();
+    let value: f64 = s
+        .strip_suffix('%')
+        .unwrap_or(s)
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid percentage: {s:?}"))?;
+    if !(0.0..=100.0).contains(&value) {
+        return Err(format!("percentage out of range: {s:?}"));
+    }
+    Ok(value / 100.0)
+}
+
+/// Format a byte count using the largest binary suffix that divides it exactly.
+pub fn format_size(bytes: u64) -> String {
+    const SUFFIXES: [(u64, &str); 4] = [
+        (1 << 40, "T"),
+        (1 << 30, "G"),
+        (1 << 20, "M"),
+        (1 << 10, "k"),
+    ];
+    for (multiplier, suffix) in SUFFIXES {
+        if bytes >= multiplier && bytes.is_multiple_of(multiplier) {
+            return format!("{}{suffix}", bytes / multiplier);
+        }
+    }
+    bytes.to_string()
+}
+
+/// Format a latency in nanoseconds with a unit suited to its magnitude.
+pub fn format_latency(nanos: u64) -> String {
+    let nanos_f = nanos as f64;
+    if nanos < 1_000 {
+        format!("{nanos} ns")
+    } else if nanos < 1_000_000 {
+        format!("{:.1} us", nanos_f / 1e3)
+    } else if nanos < 1_000_000_000 {
+        format!("{:.2} ms", nanos_f / 1e6)
+    } else {
+        format!("{:.2} s", nanos_f / 1e9)
+    }
+}
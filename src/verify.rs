@@ -0,0 +1,258 @@
+//! Integrity test: write files filled with a seeded pseudo-random pattern,
+//! then read them back and check every byte.
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::Args;
+use jwalk::rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tracing::{error, trace};
+
+use crate::{
+    read_tree::build_pool,
+    rng::Rng,
+    units::{format_size, parse_size},
+    write_tree::{file_path, read_marker, tree_levels, write_marker},
+};
+
+/// Options for the `verify` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct VerifyArgs {
+    /// The directory to write the test files in.
+    pub dir: PathBuf,
+    /// Number of files to write.
+    #[arg(short = 'n', long, default_value_t = 100)]
+    pub files: u64,
+    /// Size of each file (e.g. 1M, 1G).
+    #[arg(short, long, default_value = "1M", value_parser = parse_size)]
+    pub size: u64,
+    /// Number of concurrent threads to use.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+    /// Seed for the file contents [default: random, recorded in the tree].
+    #[arg(long)]
+    pub seed: Option<u64>,
+    /// Don't write anything; check a tree written by an earlier `verify` run,
+    /// e.g. after a reboot or a power cut.
+    #[arg(long, conflicts_with_all = ["files", "size", "seed"])]
+    pub check_only: bool,
+}
+
+/// Maximum files per directory in the test tree.
+const FANOUT: u64 = 100;
+
+/// Size of the chunks the pattern is generated, written and checked in.
+const CHUNK: usize = 1 << 20;
+
+/// The parameters that determine the contents of a test tree.
+#[derive(Debug, Clone, Copy)]
+struct Pattern {
+    seed: u64,
+    files: u64,
+    size: u64,
+}
+
+impl Pattern {
+    /// Generator for the contents of the file with the given index.
+    fn rng(&self, index: u64) -> Rng {
+        Rng::new(self.seed ^ index.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+
+    fn path(&self, root: &Path, index: u64) -> PathBuf {
+        file_path(root, index, tree_levels(self.files, FANOUT), FANOUT)
+    }
+
+    fn read_from_marker(root: &Path) -> Result<Self, String> {
+        let fields = read_marker(root).map_err(|err| err.to_string())?;
+        let field = |key: &str| {
+            fields
+                .iter()
+                .find(|(k, _)| k == key)
+                .and_then(|(_, value)| value.parse::<u64>().ok())
+                .ok_or_else(|| format!("missing or invalid {key:?} field"))
+        };
+        Ok(Self {
+            seed: field("seed")?,
+            files: field("files")?,
+            size: field("size")?,
+        })
+    }
+}
+
+pub fn verify(options: &VerifyArgs) {
+    let root = &options.dir;
+    let pool = build_pool(options.threads.max(1));
+    let pattern = if options.check_only {
+        match Pattern::read_from_marker(root) {
+            Ok(pattern) => pattern,
+            Err(err) => {
+                error!("{} is not a verify tree: {err}", root.to_string_lossy());
+                std::process::exit(1);
+            }
+        }
+    } else {
+        let pattern = Pattern {
+            seed: options.seed.unwrap_or_else(|| Rng::from_time().next_u64()),
+            files: options.files,
+            size: options.size,
+        };
+        let fields = [
+            ("seed", pattern.seed.to_string()),
+            ("files", pattern.files.to_string()),
+            ("size", pattern.size.to_string()),
+        ];
+        if let Err(err) = write_marker(root, "verify", &fields) {
+            error!("error creating {}: {err}", root.to_string_lossy());
+            std::process::exit(1);
+        }
+        println!(
+            "-- writing {} files of {} into {root:?} (seed {})",
+            pattern.files,
+            format_size(pattern.size),
+            pattern.seed,
+        );
+        let t1 = Instant::now();
+        let failed = pool.install(|| {
+            (0..pattern.files)
+                .into_par_iter()
+                .filter(|&i| {
+                    let path = pattern.path(root, i);
+                    write_pattern(&path, pattern, i)
+                        .map_err(|err| error!("error writing {}: {err}", path.to_string_lossy()))
+                        .is_err()
+                })
+                .count()
+        });
+        print_throughput("write", pattern, t1.elapsed().as_secs_f64());
+        if failed > 0 {
+            error!("{failed} files could not be written");
+            std::process::exit(1);
+        }
+        pattern
+    };
+
+    println!(
+        "-- checking {} files of {} in {root:?} (seed {})",
+        pattern.files,
+        format_size(pattern.size),
+        pattern.seed,
+    );
+    let t1 = Instant::now();
+    let bad = pool.install(|| {
+        (0..pattern.files)
+            .into_par_iter()
+            .filter(|&i| {
+                let path = pattern.path(root, i);
+                match check_pattern(&path, pattern, i) {
+                    Ok(Check::Good) => false,
+                    Ok(Check::Mismatch(offset)) => {
+                        println!("-- corrupt: {} differs at offset {offset}", path.display());
+                        true
+                    }
+                    Ok(Check::WrongSize(len)) => {
+                        println!(
+                            "-- corrupt: {} is {len} bytes, expected {}",
+                            path.display(),
+                            pattern.size,
+                        );
+                        true
+                    }
+                    Err(err) => {
+                        println!("-- unreadable: {}: {err}", path.display());
+                        true
+                    }
+                }
+            })
+            .count()
+    });
+    print_throughput("verify", pattern, t1.elapsed().as_secs_f64());
+    if bad > 0 {
+        println!(
+            "-- FAILED: {bad} of {} files are corrupt or unreadable",
+            pattern.files
+        );
+        std::process::exit(1);
+    }
+    println!("-- all {} files verified", pattern.files);
+}
+
+fn print_throughput(phase: &str, pattern: Pattern, dur_s: f64) {
+    let total_size_mb = (pattern.files * pattern.size) as f64 / 1_000_000.0;
+    println!(
+        "-- {phase}: {:.0} MB/s   {:.0} files/s  ({} MB in {} s)",
+        total_size_mb / dur_s,
+        pattern.files as f64 / dur_s,
+        total_size_mb,
+        dur_s,
+    );
+}
+
+/// Write a file's pattern and sync it, so the check reads what reached the device.
+fn write_pattern(path: &Path, pattern: Pattern, index: u64) -> Result<(), io::Error> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut f = File::create(path)?;
+    let mut rng = pattern.rng(index);
+    let mut buf = vec![0; CHUNK];
+    let mut remaining = pattern.size;
+    while remaining > 0 {
+        let n = remaining.min(CHUNK as u64) as usize;
+        rng.fill_bytes(&mut buf[..n]);
+        f.write_all(&buf[..n])?;
+        remaining -= n as u64;
+    }
+    f.sync_all()?;
+    trace!("wrote {}", path.to_string_lossy());
+    Ok(())
+}
+
+/// Outcome of checking one file against its pattern.
+enum Check {
+    Good,
+    /// The first byte that differs from the pattern.
+    Mismatch(u64),
+    /// The file has this length instead of the pattern's, but matches as far as it goes.
+    WrongSize(u64),
+}
+
+fn check_pattern(path: &Path, pattern: Pattern, index: u64) -> Result<Check, io::Error> {
+    let mut f = File::open(path)?;
+    let mut rng = pattern.rng(index);
+    let mut expected = vec![0; CHUNK];
+    let mut actual = vec![0; CHUNK];
+    let mut offset = 0;
+    while offset < pattern.size {
+        let n = (pattern.size - offset).min(CHUNK as u64) as usize;
+        rng.fill_bytes(&mut expected[..n]);
+        let got = read_full(&mut f, &mut actual[..n])?;
+        if let Some(i) = (0..got).find(|&i| actual[i] != expected[i]) {
+            return Ok(Check::Mismatch(offset + i as u64));
+        }
+        offset += got as u64;
+        if got < n {
+            return Ok(Check::WrongSize(offset));
+        }
+    }
+    let len = f.metadata()?.len();
+    if len != pattern.size {
+        return Ok(Check::WrongSize(len));
+    }
+    Ok(Check::Good)
+}
+
+/// Read until `buf` is full or the file ends, returning the number of bytes read.
+fn read_full(f: &mut File, buf: &mut [u8]) -> Result<usize, io::Error> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match f.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
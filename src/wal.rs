@@ -0,0 +1,107 @@
+//! Write-ahead log simulation: sequential appends of fixed-size records,
+//! synced after every group of records as a database commits transactions.
+
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    time::Instant,
+};
+
+use clap::Args;
+use tracing::error;
+
+use crate::{
+    fsync::SyncCall,
+    histogram::Histogram,
+    output::print_latency,
+    rng::Rng,
+    units::{format_size, parse_size},
+};
+
+/// Options for the `wal` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct WalArgs {
+    /// Log file to create, which mustn't exist, so no file is overwritten; it
+    /// is removed afterwards unless --keep is given.
+    pub file: PathBuf,
+    /// Size of each record (e.g. 512, 8k).
+    #[arg(short, long, default_value = "512", value_parser = parse_size)]
+    pub record_size: u64,
+    /// Number of records to append.
+    #[arg(short = 'n', long, default_value_t = 10_000)]
+    pub records: u64,
+    /// Number of records appended between syncs, like a group commit.
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub group: u64,
+    /// Which sync call commits each group.
+    #[arg(long, value_enum, default_value_t = SyncCall::Fdatasync)]
+    pub sync: SyncCall,
+    /// Keep the log file instead of removing it.
+    #[arg(long)]
+    pub keep: bool,
+}
+
+pub fn wal(options: &WalArgs) {
+    let path = &options.file;
+    let group = options.group.max(1);
+    println!(
+        "-- appending {} records of {} to {path:?}, calling {} every {group} records",
+        options.records,
+        format_size(options.record_size),
+        options.sync.name(),
+    );
+    let mut f = match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(f) => f,
+        Err(err) => {
+            error!("error creating {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+    };
+    let mut record = vec![0; options.record_size.max(1) as usize];
+    Rng::from_time().fill_bytes(&mut record);
+
+    // A commit runs from the first append of a group until its sync returns.
+    let mut commit_latency = Histogram::default();
+    let mut sync_latency = Histogram::default();
+    let t1 = Instant::now();
+    let mut appended = 0;
+    while appended < options.records {
+        let t_commit = Instant::now();
+        let in_group = group.min(options.records - appended);
+        let result = (0..in_group)
+            .try_for_each(|_| f.write_all(&record))
+            .and_then(|()| {
+                let t_sync = Instant::now();
+                let result = match options.sync {
+                    SyncCall::Fsync => f.sync_all(),
+                    SyncCall::Fdatasync => f.sync_data(),
+                };
+                sync_latency.record(t_sync.elapsed().as_nanos() as u64);
+                result
+            });
+        if let Err(err) = result {
+            error!("error appending to {}: {err}", path.to_string_lossy());
+            std::process::exit(1);
+        }
+        commit_latency.record(t_commit.elapsed().as_nanos() as u64);
+        appended += in_group;
+    }
+    let dur_s = t1.elapsed().as_secs_f64();
+    let commits = commit_latency.count();
+    println!(
+        "-- wal: {:.0} commits/s   {:.0} records/s   {:.1} MB/s  ({commits} commits in {dur_s} s)",
+        commits as f64 / dur_s,
+        appended as f64 / dur_s,
+        (appended * options.record_size) as f64 / 1e6 / dur_s,
+    );
+    print_latency("commit", &commit_latency);
+    print_latency("sync", &sync_latency);
+
+    drop(f);
+    if !options.keep {
+        if let Err(err) = fs::remove_file(path) {
+            error!("error removing {}: {err}", path.to_string_lossy());
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! Directory traversal shared by the benchmarks that list a tree.
+
+use std::{
+    collections::HashSet,
+    fs::{self, Metadata},
+    io,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Component, Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use clap::Args;
+use jwalk::{DirEntry, WalkDir};
+use tracing::debug;
+
+use crate::{glob::Glob, output::report, units::parse_size};
+
+/// Options selecting which files a walk yields.
+#[derive(Args, Debug, Clone, Default)]
+pub struct WalkArgs {
+    /// Only read files matching this glob; may be repeated. Patterns without a
+    /// `/` match the file name, others the path relative to the walked directory.
+    #[arg(long, value_name = "GLOB", value_parser = Glob::parse)]
+    pub include: Vec<Glob>,
+    /// Skip files and directories matching this glob (e.g. '*.o', 'target/**');
+    /// may be repeated. Excluded directories are not descended into.
+    #[arg(long, value_name = "GLOB", value_parser = Glob::parse)]
+    pub exclude: Vec<Glob>,
+    /// Only read files of at least this size (e.g. 4k).
+    #[arg(long, value_parser = parse_size)]
+    pub min_size: Option<u64>,
+    /// Only read files of at most this size (e.g. 1M).
+    #[arg(long, value_parser = parse_size)]
+    pub max_size: Option<u64>,
+    /// Descend at most this many levels below each directory, like `find
+    /// -maxdepth`: 1 reads only the files directly inside it.
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+    /// Descend into symlinked directories and read symlinked files, instead of
+    /// skipping symlinks. Each directory is walked only once, so loops end.
+    #[arg(long)]
+    pub follow_symlinks: bool,
+    /// Don't descend into directories on other filesystems, like `du -x`.
+    #[arg(short = 'x', long)]
+    pub one_file_system: bool,
+    /// Yield each file with several hard links once, through the first link
+    /// found, so hardlinked snapshots aren't read over and over.
+    #[arg(long)]
+    pub dedup_hardlinks: bool,
+}
+
+/// A regular file found by a walk, or loaded from a saved list.
+#[derive(Debug, Clone)]
+pub struct ListedFile {
+    pub path: PathBuf,
+    /// How many levels below the listed directory it is: 1 if directly inside.
+    pub depth: usize,
+}
+
+impl ListedFile {
+    /// The file's metadata, following a symlink to it.
+    pub fn metadata(&self) -> Result<Metadata, io::Error> {
+        fs::metadata(&self.path)
+    }
+}
+
+impl ListedFile {
+    /// A file named by a path that wasn't found by a walk, at the depth of
+    /// its path's components.
+    pub fn named(path: PathBuf) -> Self {
+        let depth = path
+            .components()
+            .filter(|component| matches!(component, Component::Normal(_)))
+            .count();
+        Self { path, depth }
+    }
+}
+
+impl From<DirEntry<((), ())>> for ListedFile {
+    fn from(entry: DirEntry<((), ())>) -> Self {
+        Self {
+            path: entry.path(),
+            depth: entry.depth,
+        }
+    }
+}
+
+/// Hard links skipped by --dedup-hardlinks, and the bytes in their files.
+static LINKS_SKIPPED: AtomicU64 = AtomicU64::new(0);
+static LINK_BYTES_SKIPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Entries walked that aren't regular files, by type, and entries that
+/// couldn't be read.
+static DIRECTORIES: AtomicU64 = AtomicU64::new(0);
+static SYMLINKS: AtomicU64 = AtomicU64::new(0);
+static SOCKETS: AtomicU64 = AtomicU64::new(0);
+static FIFOS: AtomicU64 = AtomicU64::new(0);
+static DEVICES: AtomicU64 = AtomicU64::new(0);
+static INACCESSIBLE: AtomicU64 = AtomicU64::new(0);
+
+/// Count an entry that passed the filters by its type, as readdir gave it.
+/// The directory walked isn't counted, only what was found in it.
+fn count_entry(entry: &DirEntry<((), ())>) {
+    let file_type = entry.file_type;
+    let counter = if entry.depth == 0 || file_type.is_file() {
+        return;
+    } else if file_type.is_dir() {
+        &DIRECTORIES
+    } else if file_type.is_symlink() {
+        &SYMLINKS
+    } else if file_type.is_socket() {
+        &SOCKETS
+    } else if file_type.is_fifo() {
+        &FIFOS
+    } else {
+        &DEVICES
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Print what the walks since the last report found besides regular files,
+/// and how many hard links --dedup-hardlinks skipped.
+pub fn print_walk_report() {
+    let counts = [
+        (DIRECTORIES.swap(0, Ordering::Relaxed), "directories"),
+        (SYMLINKS.swap(0, Ordering::Relaxed), "symlinks"),
+        (SOCKETS.swap(0, Ordering::Relaxed), "sockets"),
+        (FIFOS.swap(0, Ordering::Relaxed), "FIFOs"),
+        (DEVICES.swap(0, Ordering::Relaxed), "devices"),
+        (INACCESSIBLE.swap(0, Ordering::Relaxed), "inaccessible"),
+    ];
+    if counts.iter().any(|&(count, _)| count > 0) {
+        let counts = counts
+            .iter()
+            .map(|(count, what)| format!("{count} {what}"))
+            .collect::<Vec<_>>();
+        report!("-- other entries: {}", counts.join(", "));
+    }
+    let links = LINKS_SKIPPED.swap(0, Ordering::Relaxed);
+    let bytes = LINK_BYTES_SKIPPED.swap(0, Ordering::Relaxed);
+    if links > 0 {
+        report!(
+            "-- hardlinks: skipped {links} extra links to files already listed ({:.1} MB that would have been read again)",
+            bytes as f64 / 1e6
+        );
+    }
+}
+
+impl WalkArgs {
+    /// Whether any option filtering the files yielded was given.
+    pub fn filters_given(&self) -> bool {
+        !self.include.is_empty()
+            || !self.exclude.is_empty()
+            || self.min_size.is_some()
+            || self.max_size.is_some()
+            || self.max_depth.is_some()
+            || self.one_file_system
+            || self.dedup_hardlinks
+    }
+
+    /// Whether a child entry found under `root` passes the filters.
+    fn keep(&self, root: &Path, entry: &DirEntry<((), ())>) -> bool {
+        if !self.size_in_range(entry) {
+            return false;
+        }
+        if self.include.is_empty() && self.exclude.is_empty() {
+            return true;
+        }
+        let parent = entry.parent_path();
+        let relative = parent
+            .strip_prefix(root)
+            .unwrap_or(parent)
+            .join(&entry.file_name);
+        let relative = relative.to_string_lossy();
+        let name = entry.file_name.to_string_lossy();
+        if self.exclude.iter().any(|g| g.matches(&relative, &name)) {
+            return false;
+        }
+        entry.file_type.is_dir()
+            || self.include.is_empty()
+            || self.include.iter().any(|g| g.matches(&relative, &name))
+    }
+
+    /// Whether a file's size is within `--min-size` and `--max-size`. Files
+    /// that can't be stat'ed are kept, so the error shows up when reading.
+    fn size_in_range(&self, entry: &DirEntry<((), ())>) -> bool {
+        if !entry.file_type.is_file() || (self.min_size.is_none() && self.max_size.is_none()) {
+            return true;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            return true;
+        };
+        let size = metadata.len();
+        self.min_size.is_none_or(|min| size >= min) && self.max_size.is_none_or(|max| size <= max)
+    }
+}
+
+/// The directories to walk: those given, or CWD if there are none.
+pub fn paths_or_cwd(paths: &[PathBuf]) -> Vec<PathBuf> {
+    if paths.is_empty() {
+        vec![std::env::current_dir().unwrap()]
+    } else {
+        paths.to_vec()
+    }
+}
+
+/// Walk a directory tree, yielding the regular files in it that pass the filters.
+pub fn walk_files(
+    dir: &Path,
+    threads: u32,
+    args: &WalkArgs,
+) -> impl Iterator<Item = DirEntry<((), ())>> {
+    walk_entries(dir, threads, args).filter(|entry| entry.file_type.is_file())
+}
+
+/// Walk a directory tree, yielding every entry in it that passes the filters,
+/// starting with `dir` itself. Filtering happens on the walker threads, as
+/// each directory is read.
+pub fn walk_entries(
+    dir: &Path,
+    threads: u32,
+    args: &WalkArgs,
+) -> impl Iterator<Item = DirEntry<((), ())>> {
+    let root = PathBuf::from(dir);
+    let args = args.clone();
+    let visited = Mutex::new(HashSet::new());
+    let linked = Mutex::new(HashSet::new());
+    let root_dev = args
+        .one_file_system
+        .then(|| std::fs::metadata(dir).ok().map(|metadata| metadata.dev()))
+        .flatten();
+    WalkDir::new(dir)
+        .parallelism(jwalk::Parallelism::RayonNewPool(threads as usize))
+        .skip_hidden(false)
+        .sort(true)
+        .max_depth(args.max_depth.unwrap_or(usize::MAX))
+        .follow_links(args.follow_symlinks)
+        .process_read_dir(move |depth, _path, _state, children| {
+            // The root itself (depth None) is always walked.
+            if depth.is_some() {
+                children.retain(|child| match child {
+                    Ok(entry) => args.keep(&root, entry),
+                    Err(_) => true,
+                });
+            }
+            children.iter().flatten().for_each(count_entry);
+            if args.follow_symlinks {
+                for entry in children.iter_mut().flatten() {
+                    if entry.file_type.is_dir() && !first_visit(&visited, entry) {
+                        debug!(
+                            "skipping directory already walked: {}",
+                            entry.path().display()
+                        );
+                        entry.read_children_path = None;
+                    }
+                }
+            }
+            if args.dedup_hardlinks {
+                children.retain(|child| match child {
+                    Ok(entry) => first_link(&linked, entry),
+                    Err(_) => true,
+                });
+            }
+            if let Some(root_dev) = root_dev {
+                for entry in children.iter_mut().flatten() {
+                    if entry.file_type.is_dir()
+                        && entry
+                            .metadata()
+                            .is_ok_and(|metadata| metadata.dev() != root_dev)
+                    {
+                        debug!(
+                            "not crossing into another filesystem: {}",
+                            entry.path().display()
+                        );
+                        entry.read_children_path = None;
+                    }
+                }
+            }
+        })
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                debug!("error walking: {err}");
+                INACCESSIBLE.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        })
+}
+
+/// Record a file as listed, returning false if it has several hard links and
+/// was listed before through another one.
+fn first_link(linked: &Mutex<HashSet<(u64, u64)>>, entry: &DirEntry<((), ())>) -> bool {
+    if !entry.file_type.is_file() {
+        return true;
+    }
+    let Ok(metadata) = entry.metadata() else {
+        return true;
+    };
+    if metadata.nlink() < 2
+        || linked
+            .lock()
+            .unwrap()
+            .insert((metadata.dev(), metadata.ino()))
+    {
+        return true;
+    }
+    debug!(
+        "skipping another link to a file already listed: {}",
+        entry.path().display()
+    );
+    LINKS_SKIPPED.fetch_add(1, Ordering::Relaxed);
+    LINK_BYTES_SKIPPED.fetch_add(metadata.len(), Ordering::Relaxed);
+    false
+}
+
+/// Record a directory as walked, returning false if it was reached before
+/// (through a symlink, or a loop back to an ancestor).
+fn first_visit(visited: &Mutex<HashSet<(u64, u64)>>, entry: &DirEntry<((), ())>) -> bool {
+    match entry.metadata() {
+        Ok(metadata) => visited
+            .lock()
+            .unwrap()
+            .insert((metadata.dev(), metadata.ino())),
+        Err(_) => true,
+    }
+}
@@ -0,0 +1,337 @@
+//! Write benchmark: create a directory tree of files and measure throughput.
+
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use clap::Args;
+use jwalk::rayon::{
+    self,
+    iter::{IntoParallelIterator, ParallelIterator},
+};
+use tracing::{debug, error, trace};
+
+use crate::{
+    rng::Rng,
+    units::{format_size, parse_size},
+};
+
+/// How the sizes of generated files are chosen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SizeDistribution {
+    /// Every file has the same size.
+    Fixed(u64),
+    /// Sizes are uniformly distributed between the bounds (inclusive).
+    Uniform(u64, u64),
+    /// Sizes follow a log-normal distribution with the given median and shape
+    /// (the standard deviation of the size's logarithm), like real file systems:
+    /// mostly small files with a long tail of large ones, up to LOG_NORMAL_MAX.
+    LogNormal { median: u64, sigma: f64 },
+}
+
+/// Largest log-normal size sampled, so a wide shape can't ask for a file that
+/// never finishes writing.
+const LOG_NORMAL_MAX: u64 = 16 << 30;
+
+impl SizeDistribution {
+    /// Parse `SIZE` (fixed), `MIN..MAX` (uniform) or `lognormal:MEDIAN:SIGMA`,
+    /// e.g. `64k`, `4k..1M` or `lognormal:16k:2`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if let Some(params) = s.strip_prefix("lognormal:") {
+            let Some((median, sigma)) = params.split_once(':') else {
+                return Err(format!(
+                    "invalid log-normal sizes: {s:?} (use lognormal:MEDIAN:SIGMA)"
+                ));
+            };
+            let median = parse_size(median)?;
+            let sigma = sigma
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|sigma| sigma.is_finite() && *sigma >= 0.0)
+                .ok_or_else(|| format!("invalid log-normal shape: {s:?}"))?;
+            return Ok(Self::LogNormal { median, sigma });
+        }
+        match s.split_once("..") {
+            Some((lo, hi)) => {
+                let lo = parse_size(lo)?;
+                let hi = parse_size(hi)?;
+                if lo > hi {
+                    return Err(format!("invalid size range: {s:?} (min > max)"));
+                }
+                Ok(Self::Uniform(lo, hi))
+            }
+            None => Ok(Self::Fixed(parse_size(s)?)),
+        }
+    }
+
+    pub fn sample(&self, rng: &mut Rng) -> u64 {
+        match *self {
+            Self::Fixed(size) => size,
+            Self::Uniform(lo, hi) => rng.range_inclusive(lo, hi),
+            Self::LogNormal { median, sigma } => {
+                // Box-Muller transform for a standard normal value.
+                let u1 = 1.0 - rng.next_f64();
+                let u2 = rng.next_f64();
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                // The float-to-int cast saturates, so huge samples become u64::MAX.
+                ((median as f64 * (sigma * z).exp()).round() as u64).min(LOG_NORMAL_MAX)
+            }
+        }
+    }
+}
+
+/// Options for the `write-tree` subcommand.
+#[derive(Args, Debug, Clone)]
+pub struct WriteTreeArgs {
+    /// The directory to create files in.
+    pub dir: PathBuf,
+    /// Number of files to create.
+    #[arg(short = 'n', long, default_value_t = 1000)]
+    pub files: u64,
+    /// File size: fixed (e.g. 64k), a uniform range (e.g. 4k..1M) or
+    /// log-normal with a median and shape (e.g. lognormal:16k:2).
+    #[arg(short, long, default_value = "64k", value_parser = SizeDistribution::parse)]
+    pub size: SizeDistribution,
+    /// Maximum number of files or subdirectories per directory.
+    #[arg(long, default_value_t = 100)]
+    pub fanout: u32,
+    /// Number of directory levels [default: just enough to respect --fanout].
+    /// With fewer levels than that, the deepest directories hold more files.
+    #[arg(long)]
+    pub depth: Option<u32>,
+    /// Number of concurrent threads to use.
+    #[arg(short = 'j', long, default_value_t = 16)]
+    pub threads: u32,
+    /// Size of each write (e.g. 4k, 1M).
+    #[arg(long, default_value = "64k", value_parser = parse_size)]
+    pub buf_size: u64,
+    /// Call fsync on each file after writing it.
+    #[arg(long)]
+    pub fsync: bool,
+    /// Seed for the random file sizes and contents.
+    #[arg(long)]
+    pub seed: Option<u64>,
+}
+
+/// Name of the file written at the root of every generated tree, so `cleanup`
+/// can tell it is safe to delete.
+pub const MARKER_FILE: &str = ".iobench-tree";
+
+/// Number of directory levels needed so no directory holds more than
+/// `fanout` files or subdirectories.
+pub fn tree_levels(files: u64, fanout: u64) -> u32 {
+    let mut levels = 0;
+    let mut capacity = fanout;
+    while capacity < files {
+        capacity = capacity.saturating_mul(fanout);
+        levels += 1;
+    }
+    levels
+}
+
+/// Path of the file with the given index in a tree of `levels` directory levels.
+pub fn file_path(root: &Path, index: u64, levels: u32, fanout: u64) -> PathBuf {
+    let mut path = root.to_path_buf();
+    for level in (1..=levels).rev() {
+        let digit = (index / fanout.pow(level)) % fanout;
+        path.push(format!("d{digit}"));
+    }
+    path.push(format!("f{index}"));
+    path
+}
+
+pub fn write_tree(options: &WriteTreeArgs) {
+    let root = &options.dir;
+    let fanout = u64::from(options.fanout.max(2));
+    let levels = options
+        .depth
+        .unwrap_or_else(|| tree_levels(options.files, fanout));
+    if fanout.checked_pow(levels).is_none() {
+        error!("a tree of {levels} levels with a fanout of {fanout} is too deep to number its directories");
+        std::process::exit(1);
+    }
+    println!(
+        "-- writing {} files into {root:?} (fanout {fanout}, {levels} directory levels) using {} threads ({} buffer)",
+        options.files,
+        options.threads,
+        format_size(options.buf_size),
+    );
+
+    let mut rng = options.seed.map_or_else(Rng::from_time, Rng::new);
+    let sizes = (0..options.files)
+        .map(|_| options.size.sample(&mut rng))
+        .collect::<Vec<u64>>();
+    let mut data = vec![0; options.buf_size.max(1) as usize];
+    rng.fill_bytes(&mut data);
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.threads as usize)
+        .build()
+        .expect("thread pool");
+
+    let mut fields = vec![("files", options.files.to_string())];
+    if let Some(seed) = options.seed {
+        fields.push(("seed", seed.to_string()));
+    }
+    if let Err(err) = write_marker(root, "write-tree", &fields) {
+        error!(
+            "error creating {}: {err}",
+            root.join(MARKER_FILE).to_string_lossy()
+        );
+        std::process::exit(1);
+    }
+
+    let t1 = Instant::now();
+    let mut dirs = (0..options.files)
+        .filter_map(|i| {
+            file_path(root, i, levels, fanout)
+                .parent()
+                .map(Path::to_path_buf)
+        })
+        .collect::<Vec<_>>();
+    // With a fixed --depth, indices wrap around, so equal paths aren't always adjacent.
+    dirs.sort();
+    dirs.dedup();
+    // Every level is created and counted, each directory after its parent,
+    // which sorts first.
+    let parents = dirs
+        .iter()
+        .flat_map(|dir| dir.ancestors().skip(1).take_while(|&dir| dir != root))
+        .map(Path::to_path_buf)
+        .collect::<Vec<_>>();
+    dirs.extend(parents);
+    dirs.sort();
+    dirs.dedup();
+    let mut created = 0;
+    for dir in &dirs {
+        match fs::create_dir(dir) {
+            Ok(()) => created += 1,
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(err) => debug!("error creating directory {}: {err}", dir.to_string_lossy()),
+        }
+    }
+    let t2 = Instant::now();
+    let dur_s = (t2 - t1).as_secs_f64();
+    println!(
+        "-- mkdir: {:.0} dirs/s  ({created} dirs in {} s)",
+        created as f64 / dur_s,
+        dur_s,
+    );
+
+    let t1 = Instant::now();
+
+    let all_stats = pool.install(|| {
+        (0..options.files)
+            .into_par_iter()
+            .map(|i| {
+                let path = file_path(root, i, levels, fanout);
+                write_file(&path, sizes[i as usize], &data, options.fsync)
+            })
+            .reduce(WriteFilesStats::default, |a, b| a.combine(&b))
+    });
+
+    let t2 = Instant::now();
+    let dur_s = (t2 - t1).as_secs_f64();
+    let total_size_mb = all_stats.bytes as f64 / 1_000_000.0;
+    println!(
+        "-- write: {:.0} MB/s   {:.0} files/s  ({} MB in {} s)",
+        total_size_mb / dur_s,
+        all_stats.file_count as f64 / dur_s,
+        total_size_mb,
+        dur_s,
+    );
+}
+
+/// Create the tree's root and mark it as generated by the given subcommand,
+/// recording `key: value` fields describing how. A directory that already
+/// has other files in it is refused unless it is marked already, since
+/// `cleanup` deletes everything under a marker.
+pub fn write_marker(
+    root: &Path,
+    command: &str,
+    fields: &[(&str, String)],
+) -> Result<(), std::io::Error> {
+    fs::create_dir_all(root)?;
+    let marker = root.join(MARKER_FILE);
+    if !marker.is_file() && fs::read_dir(root)?.next().is_some() {
+        return Err(std::io::Error::other(
+            "the directory isn't empty and wasn't generated by iobench; use a new or empty one",
+        ));
+    }
+    let mut f = fs::File::create(marker)?;
+    writeln!(
+        f,
+        "Generated by iobench {command}; `iobench cleanup` may delete this tree."
+    )?;
+    for (key, value) in fields {
+        writeln!(f, "{key}: {value}")?;
+    }
+    Ok(())
+}
+
+/// The `key: value` fields of a tree's marker file.
+pub fn read_marker(root: &Path) -> Result<Vec<(String, String)>, std::io::Error> {
+    let contents = fs::read_to_string(root.join(MARKER_FILE))?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| line.split_once(": "))
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect())
+}
+
+#[derive(Default)]
+struct WriteFilesStats {
+    bytes: u64,
+    file_count: u64,
+}
+
+impl WriteFilesStats {
+    fn combine(&self, other: &Self) -> Self {
+        Self {
+            bytes: self.bytes + other.bytes,
+            file_count: self.file_count + other.file_count,
+        }
+    }
+}
+
+fn write_file(path: &Path, size: u64, data: &[u8], fsync: bool) -> WriteFilesStats {
+    let mut stats = WriteFilesStats::default();
+
+    match do_write_file(path, size, data, fsync, &mut stats) {
+        Ok(()) => {
+            trace!("done writing file {}", path.to_string_lossy());
+        }
+        Err(err) => {
+            debug!("error writing file {}: {err}", path.to_string_lossy());
+        }
+    }
+
+    stats
+}
+
+fn do_write_file(
+    path: &Path,
+    size: u64,
+    data: &[u8],
+    fsync: bool,
+    stats: &mut WriteFilesStats,
+) -> Result<(), std::io::Error> {
+    trace!("create file: {}", path.to_string_lossy());
+    let mut f = fs::File::create(path)?;
+    let mut remaining = size;
+    while remaining > 0 {
+        let n = remaining.min(data.len() as u64) as usize;
+        f.write_all(&data[..n])?;
+        remaining -= n as u64;
+        stats.bytes += n as u64;
+    }
+    if fsync {
+        f.sync_all()?;
+    }
+    stats.file_count += 1;
+    Ok(())
+}